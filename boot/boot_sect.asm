@@ -1,5 +1,6 @@
 [org 0x7c00] ; bootloader offset
-KERNEL_OFFSET equ 0x1000
+KERNEL_OFFSET equ 0x10000
+KERNEL_SECTORS equ 192 ; 96 KiB, up to the kernel heap at 0x28000
 
     mov [BOOT_DRIVE], dl ; BIOS stores boot drive # in dl at boot
     mov bp, 0x9000 ; set the stack
@@ -36,8 +37,10 @@ load_kernel:
     call print_str
     call print_nl
 
-    mov bx, KERNEL_OFFSET ; Read from disk and store in 0x1000
-    mov al, 31  ; Load 8 code sectors and 1 rodata sector
+    mov ax, KERNEL_OFFSET >> 4 ; Read from disk and store in 0x10000
+    mov es, ax
+    xor bx, bx
+    mov al, KERNEL_SECTORS
     mov cl, 2 ; First available sector after boot sector
     mov dl, [BOOT_DRIVE]
     call read_disk 
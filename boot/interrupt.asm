@@ -65,7 +65,6 @@ global isr4
 global isr5
 global isr6
 global isr7
-global isr8
 global isr9
 global isr10
 global isr11
@@ -106,6 +105,17 @@ global irq12
 global irq13
 global irq14
 global irq15
+global irq16
+global irq17
+global irq18
+global irq19
+global irq20
+global irq21
+global irq22
+global irq23
+global irq24
+global irq25
+global spurious_irq
 
 ; 0: Divide By Zero Exception
 isr0:
@@ -163,11 +173,7 @@ isr7:
     push byte 7
     jmp isr_common_stub
 
-; 8: Double Fault Exception (With Error Code!)
-isr8:
-    cli
-    push byte 8
-    jmp isr_common_stub
+; 8: Double Fault Exception, handled by a task gate instead.
 
 ; 9: Coprocessor Segment Overrun Exception
 isr9:
@@ -220,10 +226,9 @@ isr16:
     push byte 16
     jmp isr_common_stub
 
-; 17: Alignment Check Exception
+; 17: Alignment Check Exception (With Error Code!)
 isr17:
     cli
-    push byte 0
     push byte 17
     jmp isr_common_stub
 
@@ -420,4 +425,71 @@ irq15:
 	cli
 	push byte 15
 	push byte 47
-	jmp irq_common_stub
\ No newline at end of file
+	jmp irq_common_stub
+
+; IO-APIC pins beyond the legacy ones
+irq16:
+	cli
+	push byte 16
+	push byte 48
+	jmp irq_common_stub
+
+irq17:
+	cli
+	push byte 17
+	push byte 49
+	jmp irq_common_stub
+
+irq18:
+	cli
+	push byte 18
+	push byte 50
+	jmp irq_common_stub
+
+irq19:
+	cli
+	push byte 19
+	push byte 51
+	jmp irq_common_stub
+
+irq20:
+	cli
+	push byte 20
+	push byte 52
+	jmp irq_common_stub
+
+irq21:
+	cli
+	push byte 21
+	push byte 53
+	jmp irq_common_stub
+
+irq22:
+	cli
+	push byte 22
+	push byte 54
+	jmp irq_common_stub
+
+irq23:
+	cli
+	push byte 23
+	push byte 55
+	jmp irq_common_stub
+
+; Local APIC timer
+irq24:
+	cli
+	push byte 24
+	push byte 56
+	jmp irq_common_stub
+
+; Wake-up IPI for idle application processors
+irq25:
+	cli
+	push byte 25
+	push byte 57
+	jmp irq_common_stub
+
+; Spurious interrupts of the local APIC must not be acknowledged with an EOI.
+spurious_irq:
+	iret
@@ -1,7 +1,17 @@
 [bits 32]
 [extern kernel_main]
+[extern __bss_start]
+[extern __bss_end]
 section .text.kernel_entry
     global kernel_entry
 kernel_entry:
+    ; The boot sector loads whole sectors, so .bss isn't guaranteed to be
+    ; zero. Clear it before any Rust code runs.
+    mov edi, __bss_start
+    mov ecx, __bss_end
+    sub ecx, edi
+    xor eax, eax
+    cld
+    rep stosb
     call kernel_main
     jmp $
\ No newline at end of file
@@ -1,42 +1,48 @@
+SECTORS_PER_TRACK equ 18 ; 1.44 MB floppy geometry
 ; in
 ; - al: number of sectors
 ; - cl: sector number
 ; - es:bx: buffer start
+; Sectors are read one at a time, so a read may span multiple tracks and
+; cross 64 KiB boundaries.
 read_disk:
     pusha
+    push es
 
-    push ax ; back up sector count
-
-    mov ah, 0x02
     mov ch, 0 ; track/cyl number
     mov dh, 0 ; head number
     ; mov dl, 0 ; drive number, set by BIOS
 
+read_disk_loop:
+    push ax ; back up sector count
+    mov ax, 0x0201 ; read a single sector
     int 0x13
-
-    mov bl, al ; move actual sector count
+    jc disk_error ; error in carry bit
     pop ax
 
-    jc disk_error ; error in carry bit
+    mov si, es ; move the buffer 512 bytes ahead
+    add si, 0x20
+    mov es, si
+
+    inc cl ; next sector, head or cylinder
+    cmp cl, SECTORS_PER_TRACK
+    jbe read_disk_next
+    mov cl, 1
+    xor dh, 1
+    jnz read_disk_next
+    inc ch
+
+read_disk_next:
+    dec al
+    jnz read_disk_loop
 
 disk_done:
+    pop es
     popa
     ret
 
 disk_error:
-    cmp bl, al 
-    jl too_few_sectors
-    cmp ah, 0
-    jg disk_status
-    jmp disk_done
-
-too_few_sectors:
-    mov bx, TOO_FEW_SECTORS_MSG
-    call print_str 
-    call print_nl
-    jmp disk_done
-
-disk_status:
+    pop bx ; discard the sector count
     mov bx, DISK_ERROR_STATUS_MSG
     call print_str 
     mov dl, ah
@@ -45,13 +51,10 @@ disk_status:
     jmp disk_done
 
 
-TOO_FEW_SECTORS_MSG:
-    db 'Too few sectors', 0
-
 DISK_ERROR_STATUS_MSG:
     db 'Disk Err: ', 0
 
 BEFORE:
     db 'B', 0
 AFTER:
-    db 'A', 0
\ No newline at end of file
+    db 'A', 0
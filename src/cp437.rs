@@ -0,0 +1,67 @@
+// Code page 437, the character set of the VGA text mode font
+//
+// The lower half is ASCII. The upper half mostly holds box drawing characters,
+// with some accented letters and symbols in between; those are mapped here.
+
+/// The non-ASCII characters of code page 437 that keyboard layouts can type.
+const UPPER_HALF: [(char, u8); 48] = [
+    ('Ç', 0x80),
+    ('ü', 0x81),
+    ('é', 0x82),
+    ('â', 0x83),
+    ('ä', 0x84),
+    ('à', 0x85),
+    ('å', 0x86),
+    ('ç', 0x87),
+    ('ê', 0x88),
+    ('ë', 0x89),
+    ('è', 0x8A),
+    ('ï', 0x8B),
+    ('î', 0x8C),
+    ('ì', 0x8D),
+    ('Ä', 0x8E),
+    ('Å', 0x8F),
+    ('É', 0x90),
+    ('æ', 0x91),
+    ('Æ', 0x92),
+    ('ô', 0x93),
+    ('ö', 0x94),
+    ('ò', 0x95),
+    ('û', 0x96),
+    ('ù', 0x97),
+    ('ÿ', 0x98),
+    ('Ö', 0x99),
+    ('Ü', 0x9A),
+    ('¢', 0x9B),
+    ('£', 0x9C),
+    ('¥', 0x9D),
+    ('á', 0xA0),
+    ('í', 0xA1),
+    ('ó', 0xA2),
+    ('ú', 0xA3),
+    ('ñ', 0xA4),
+    ('Ñ', 0xA5),
+    ('¿', 0xA8),
+    ('¬', 0xAA),
+    ('½', 0xAB),
+    ('¡', 0xAD),
+    ('ß', 0xE1),
+    ('µ', 0xE6),
+    ('±', 0xF1),
+    ('°', 0xF8),
+    ('·', 0xFA),
+    ('²', 0xFD),
+    ('§', 0x15),
+    ('¶', 0x14),
+];
+
+/// The code page 437 byte that displays a character, if the font has it.
+pub fn from_char(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        return Some(c as u8);
+    }
+    UPPER_HALF
+        .iter()
+        .find(|(from, _)| *from == c)
+        .map(|(_, byte)| *byte)
+}
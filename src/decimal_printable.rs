@@ -18,16 +18,22 @@ where
 {
     unsafe fn as_decimal<'a>(&'a self) -> Result<DynArray<'a, u8>, KernelError> {
         unsafe {
-            let byte_count = Self::decimal_digits();
             let mem = KERNEL.get()?.memory_manager();
-            let mut chars = DynArray::new(byte_count, false, mem);
-            let mut remainder = *self;
             let ten = T::from(10);
             let zero = T::from(0);
-            for i in 0..byte_count {
-                if remainder == zero {
-                    break;
-                }
+
+            // Digits come out least significant first, so count them before
+            // filling the buffer from the end. Zero still has one digit.
+            let mut len = 1;
+            let mut remainder = *self / ten;
+            while remainder != zero {
+                len += 1;
+                remainder = remainder / ten;
+            }
+
+            let mut chars = DynArray::new(len.min(Self::decimal_digits()), false, mem)?;
+            let mut remainder = *self;
+            for i in (0..chars.len()).rev() {
                 let digit = (remainder % ten).extract_low_byte();
                 remainder = remainder / ten;
                 chars.set(i, digit + b'0')?;
@@ -5,27 +5,42 @@ where
     T: Sized,
 {
     start: *mut T,
-    size: usize,
+    len: usize,
     mem: &'a spin::Mutex<MemoryManager>,
 }
 
 impl<'a, T: Sized> DynArray<'a, T> {
-    pub unsafe fn new(count: usize, align: bool, mem: &'a spin::Mutex<MemoryManager>) -> Self {
+    pub unsafe fn new(
+        count: usize,
+        align: bool,
+        mem: &'a spin::Mutex<MemoryManager>,
+    ) -> Result<Self, KernelError> {
         unsafe {
             let size = core::mem::size_of::<T>() * count;
-            let start = mem.lock().malloc(size, align) as *mut T;
-            Self { start, size, mem }
+            let start = mem
+                .lock()
+                .malloc(size, align)
+                .ok_or(KernelError::OutOfMemory)? as *mut T;
+            Ok(Self {
+                start,
+                len: count,
+                mem,
+            })
         }
     }
 
     pub fn len(&self) -> usize {
-        self.size
+        self.len
     }
 
     pub unsafe fn get(&self, i: usize) -> Result<&T, KernelError> {
         unsafe { Ok(&*self.elem_ptr(i)?) }
     }
 
+    pub unsafe fn get_mut(&mut self, i: usize) -> Result<&mut T, KernelError> {
+        unsafe { Ok(&mut *self.elem_ptr(i)?) }
+    }
+
     pub unsafe fn set(&mut self, i: usize, v: T) -> Result<(), KernelError> {
         unsafe {
             let addr = self.elem_ptr(i)?;
@@ -35,13 +50,17 @@ impl<'a, T: Sized> DynArray<'a, T> {
     }
 
     unsafe fn elem_ptr(&self, i: usize) -> Result<*mut T, KernelError> {
-        if i < self.size {
-            unsafe {
-                let addr = self.start.add(i * core::mem::size_of::<T>());
-                Ok(addr)
-            }
+        if i < self.len {
+            unsafe { Ok(self.start.add(i)) }
         } else {
             Err(KernelError::OutOfBounds)
         }
     }
 }
+
+impl<'a, T: Sized> Drop for DynArray<'a, T> {
+    fn drop(&mut self) {
+        let size = core::mem::size_of::<T>() * self.len;
+        unsafe { self.mem.lock().free(self.start as *mut u8, size) };
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{
+    kernel::isr::next_event,
+    sys_event::{EventFilter, InterruptEvent},
+};
+
+const MAX_SUBSCRIBERS: usize = 8;
+
+/// Something that wants to be notified of system events.
+pub trait EventSubscriber {
+    unsafe fn on_event(&mut self, event: &InterruptEvent);
+}
+
+pub enum EventBusError {
+    Full,
+}
+
+struct Subscription<'a> {
+    filter: EventFilter,
+    subscriber: &'a mut dyn EventSubscriber,
+}
+
+/// Dispatches events raised by interrupt handlers to the subscribers that
+/// asked for them. Subscribers are called in order of subscription.
+pub struct EventBus<'a> {
+    subscriptions: [Option<Subscription<'a>>; MAX_SUBSCRIBERS],
+}
+
+impl<'a> EventBus<'a> {
+    pub fn new() -> Self {
+        Self {
+            subscriptions: [const { None }; MAX_SUBSCRIBERS],
+        }
+    }
+
+    /// Registers a subscriber for the kinds of events in `filter`.
+    pub fn subscribe(
+        &mut self,
+        filter: EventFilter,
+        subscriber: &'a mut dyn EventSubscriber,
+    ) -> Result<(), EventBusError> {
+        let slot = self
+            .subscriptions
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(EventBusError::Full)?;
+        *slot = Some(Subscription { filter, subscriber });
+        Ok(())
+    }
+
+    /// Passes an event to every subscriber interested in its kind.
+    pub unsafe fn dispatch(&mut self, event: &InterruptEvent) {
+        let kind = event.event.kind();
+        for subscription in self.subscriptions.iter_mut().flatten() {
+            if subscription.filter.matches(kind) {
+                unsafe { subscription.subscriber.on_event(event) };
+            }
+        }
+    }
+
+    /// Dispatches all events queued by interrupt handlers. Must only be called
+    /// from the kernel's main loop, since it consumes the event queue.
+    pub unsafe fn dispatch_pending(&mut self) {
+        unsafe {
+            while let Some(event) = next_event() {
+                self.dispatch(&event);
+            }
+        }
+    }
+}
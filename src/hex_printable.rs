@@ -13,9 +13,10 @@ pub trait HexPrintable {
         unsafe {
             let byte_count = core::mem::size_of::<Self::Item>();
             let mem = KERNEL.get()?.memory_manager();
-            let mut buf = DynArray::new(byte_count, false, mem);
+            // The scratch buffer is allocated last, so it can be freed.
+            let mut hex_chars = DynArray::new(byte_count * 2, false, mem)?;
+            let mut buf = DynArray::new(byte_count, false, mem)?;
             self.convert_to_bytes(&mut buf)?;
-            let mut hex_chars = DynArray::new(byte_count * 2, false, mem);
             for i in 0..byte_count {
                 let byte = buf.get(i)?;
                 hex_chars.set(i * 2, Self::half_byte_to_hex_ascii(byte >> 4))?;
@@ -80,3 +81,21 @@ impl HexPrintable for u64 {
         }
     }
 }
+
+/// Formats a `u32` as 8 hexadecimal ASCII characters without allocating.
+/// This is meant for contexts where the memory manager cannot be relied upon,
+/// such as exception handlers.
+pub const fn u32_to_hex_ascii(n: u32) -> [u8; 8] {
+    let mut out = [b'0'; 8];
+    let mut i = 0;
+    while i < 8 {
+        let nibble = ((n >> (28 - 4 * i)) & 0x0F) as u8;
+        out[i] = if nibble <= 9 {
+            b'0' + nibble
+        } else {
+            b'A' + (nibble - 10)
+        };
+        i += 1;
+    }
+    out
+}
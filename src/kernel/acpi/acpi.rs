@@ -1,7 +1,12 @@
 use crate::kernel::acpi::iter::SDTIterator;
 
 const SIGNATURE: [u8; 8] = [b'R', b'S', b'D', b' ', b'P', b'T', b'R', b' '];
-const EBDA_ADDR: *const u8 = 0x80000 as *const u8;
+// The BIOS data area holds the real mode segment of the EBDA.
+const EBDA_SEGMENT_PTR: *const u16 = 0x40E as *const u16;
+const EBDA_SEARCH_LEN: usize = 0x400;
+
+/// The size of the ACPI 1.0 part of the RSDP, which `checksum` covers.
+const RSDP_V1_LENGTH: usize = 20;
 
 #[repr(C, packed)]
 pub struct RSDP {
@@ -10,6 +15,33 @@ pub struct RSDP {
     oem_id: [u8; 6],
     revision: u8,
     rsdt_addr: u32,
+    // Fields below are only present from revision 2 (ACPI 2.0) onwards.
+    length: u32,
+    xsdt_addr: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+impl RSDP {
+    /// Checks the ACPI 1.0 checksum and, for revision 2 and later, the
+    /// extended checksum over the whole structure.
+    unsafe fn is_valid(&self) -> bool {
+        let ptr = self as *const RSDP as *const u8;
+        unsafe {
+            if checksum(ptr, RSDP_V1_LENGTH) != 0 {
+                return false;
+            }
+            self.revision < 2 || checksum(ptr, self.length as usize) == 0
+        }
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    pub fn oem_id(&self) -> [u8; 6] {
+        self.oem_id
+    }
 }
 
 #[repr(C, packed)]
@@ -25,15 +57,61 @@ pub struct ACPISDTHeader {
     creator_revision: u32,
 }
 
+impl ACPISDTHeader {
+    /// The length of the table, including this header.
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    pub fn oem_id(&self) -> [u8; 6] {
+        self.oem_id
+    }
+
+    pub fn oem_table_id(&self) -> [u8; 8] {
+        self.oem_table_id
+    }
+
+    /// Whether all bytes of the table, including the checksum, sum to zero.
+    pub unsafe fn is_valid(&self) -> bool {
+        unsafe { checksum(self as *const Self as *const u8, self.length as usize) == 0 }
+    }
+}
+
+/// The Generic Address Structure describes registers in system memory or IO
+/// space.
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct GenericAddress {
+    pub address_space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    pub const SYSTEM_MEMORY: u8 = 0;
+    pub const SYSTEM_IO: u8 = 1;
+    pub const PCI_CONFIG: u8 = 2;
+}
+
 pub struct ACPI {
     // Fixed pointer to the RSDP
     rsdp_ptr: *const RSDP,
-    // Remaining SDT pointers are part of the RSDT. We calculate the amount up-front.
-    pub(crate) sdt_ptrs: *const *const ACPISDTHeader,
+    // Remaining SDT pointers are part of the RSDT or XSDT. We calculate the
+    // amount up-front. XSDT entries are 64 bits wide, RSDT entries 32 bits.
+    pub(crate) sdt_ptrs: *const u8,
     pub(crate) sdt_cnt: usize,
+    pub(crate) sdt_entry_size: usize,
 }
 
 impl ACPI {
+    /// Finds and validates the RSDP and the root table it points to. The XSDT
+    /// is preferred over the RSDT when the RSDP has one that is addressable.
     pub unsafe fn load() -> Option<Self> {
         unsafe {
             // unsafe Option<>::or_else does not exist apparently :(
@@ -42,34 +120,38 @@ impl ACPI {
                 rsdp_ptr = Self::find_rsdp_in_bios_area();
             }
             let rsdp_ptr = rsdp_ptr?;
-            let rsdt_ptr = (*rsdp_ptr).rsdt_addr as *const ACPISDTHeader;
-            let sdt_cnt = ((*rsdt_ptr).length as usize - core::mem::size_of::<ACPISDTHeader>())
-                / core::mem::size_of::<*const ACPISDTHeader>();
-            let sdt_ptrs = (rsdt_ptr as *const u8)
-                .add(core::mem::size_of::<ACPISDTHeader>())
-                .cast();
+            let rsdp = &*rsdp_ptr;
+
+            let (root_ptr, sdt_entry_size) =
+                if rsdp.revision >= 2 && rsdp.xsdt_addr != 0 && rsdp.xsdt_addr <= u32::MAX as u64 {
+                    (rsdp.xsdt_addr as usize as *const ACPISDTHeader, 8)
+                } else {
+                    (rsdp.rsdt_addr as *const ACPISDTHeader, 4)
+                };
+            let root = &*root_ptr;
+            if !root.is_valid() || (root.length as usize) < core::mem::size_of::<ACPISDTHeader>() {
+                return None;
+            }
+
+            let sdt_cnt =
+                (root.length as usize - core::mem::size_of::<ACPISDTHeader>()) / sdt_entry_size;
+            let sdt_ptrs = (root_ptr as *const u8).add(core::mem::size_of::<ACPISDTHeader>());
 
             Some(Self {
                 rsdp_ptr,
                 sdt_ptrs,
                 sdt_cnt,
+                sdt_entry_size,
             })
         }
     }
 
     unsafe fn find_rsdp_in_ebda() -> Option<*const RSDP> {
         unsafe {
-            for offset in (0x0..0x400).step_by(0x10) {
-                let mut found = true;
-                for i in 0..8 {
-                    let addr = EBDA_ADDR.add(offset + i);
-                    if *addr != SIGNATURE[i] {
-                        found = false;
-                        break;
-                    }
-                }
-                if found {
-                    return Some(EBDA_ADDR.add(offset).cast());
+            let ebda_addr = ((*EBDA_SEGMENT_PTR as usize) << 4) as *const u8;
+            for offset in (0x0..EBDA_SEARCH_LEN).step_by(0x10) {
+                if let Some(rsdp) = Self::rsdp_at(ebda_addr.add(offset)) {
+                    return Some(rsdp);
                 }
             }
             None
@@ -79,23 +161,71 @@ impl ACPI {
     unsafe fn find_rsdp_in_bios_area() -> Option<*const RSDP> {
         unsafe {
             for base_addr in (0xE0000..0xFFFFF).step_by(0x10) {
-                let mut found = true;
-                for i in 0..8 {
-                    let addr = (base_addr + i) as *const u8;
-                    if *addr != SIGNATURE[i] {
-                        found = false;
-                        break;
-                    }
-                }
-                if found {
-                    return Some(base_addr as *const RSDP);
+                if let Some(rsdp) = Self::rsdp_at(base_addr as *const u8) {
+                    return Some(rsdp);
                 }
             }
             None
         }
     }
 
+    /// Returns the RSDP at `addr` if both its signature and checksum match.
+    unsafe fn rsdp_at(addr: *const u8) -> Option<*const RSDP> {
+        unsafe {
+            for (i, byte) in SIGNATURE.iter().enumerate() {
+                if *addr.add(i) != *byte {
+                    return None;
+                }
+            }
+            let rsdp: *const RSDP = addr.cast();
+            if (*rsdp).is_valid() { Some(rsdp) } else { None }
+        }
+    }
+
+    pub fn rsdp(&self) -> &RSDP {
+        unsafe { &*self.rsdp_ptr }
+    }
+
+    /// Whether the tables are listed by the 64 bit XSDT instead of the RSDT.
+    pub fn uses_xsdt(&self) -> bool {
+        self.sdt_entry_size == 8
+    }
+
     pub unsafe fn iter(&'_ self) -> SDTIterator<'_> {
         unsafe { SDTIterator::new(self) }
     }
+
+    /// Finds the first table with the given signature and a valid checksum.
+    pub unsafe fn find(&self, signature: &[u8; 4]) -> Option<&ACPISDTHeader> {
+        unsafe {
+            let mut iter = self.iter();
+            while let Some(header) = iter.next() {
+                if header.signature == *signature && header.is_valid() {
+                    return Some(header);
+                }
+            }
+            None
+        }
+    }
+
+    /// Finds a table and interprets it as `T`, which must start with an
+    /// `ACPISDTHeader`. Tables shorter than `T` are rejected.
+    pub unsafe fn find_table<T>(&self, signature: &[u8; 4]) -> Option<&T> {
+        unsafe {
+            let header = self.find(signature)?;
+            if (header.length as usize) < core::mem::size_of::<T>() {
+                return None;
+            }
+            Some(&*(header as *const ACPISDTHeader).cast())
+        }
+    }
+}
+
+/// Sums `len` bytes starting at `ptr`. Valid ACPI structures sum to zero.
+unsafe fn checksum(ptr: *const u8, len: usize) -> u8 {
+    let mut sum: u8 = 0;
+    for i in 0..len {
+        sum = sum.wrapping_add(unsafe { *ptr.add(i) });
+    }
+    sum
 }
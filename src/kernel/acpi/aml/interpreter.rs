@@ -0,0 +1,1399 @@
+// AML interpreter
+//
+// Tables and method bodies are executed directly from the byte code. Term
+// lists are walked with a `Cursor`, and every package (scope, method, If
+// body...) gets its own cursor bounded by its package length.
+//
+// Byte code is only accessed through `get`, so malformed AML results in an
+// error rather than a panic.
+
+use core::cmp::Ordering;
+
+use crate::kernel::{
+    acpi::{
+        acpi::GenericAddress,
+        aml::{
+            AMLError,
+            namespace::{FieldKind, FieldUnit, NameString, Namespace, NodeId, NodeKind, ROOT},
+            opcodes::*,
+            value::{AMLValue, Buffer, IndexRef, Package},
+        },
+    },
+    pci::config::{ConfigSpace, PCIAddress},
+    ports::{
+        io_delay_us, read_port_byte, read_port_dword, read_port_word, write_port_byte,
+        write_port_dword, write_port_word,
+    },
+};
+
+const NUM_ARGS: usize = 7;
+const NUM_LOCALS: usize = 8;
+const MAX_CALL_DEPTH: usize = 16;
+const MAX_LOOP_ITERATIONS: u32 = 0x10000;
+/// Returned by the `Revision` opcode.
+const INTERPRETER_REVISION: u64 = 1;
+
+const ADR_NAME: [u8; 4] = *b"_ADR";
+const BBN_NAME: [u8; 4] = *b"_BBN";
+const STA_NAME: [u8; 4] = *b"_STA";
+/// The status of a device without `_STA`: present, enabled, shown and
+/// functioning.
+const DEFAULT_STATUS: u64 = 0x0F;
+
+const FIELD_ACCESS_TYPE_MASK: u8 = 0x0F;
+const FIELD_UPDATE_RULE_SHIFT: u8 = 5;
+const UPDATE_WRITE_AS_ONES: u8 = 1;
+const UPDATE_WRITE_AS_ZEROS: u8 = 2;
+
+#[derive(Clone, Copy)]
+struct Cursor {
+    aml: &'static [u8],
+    pos: usize,
+}
+
+impl Cursor {
+    fn new(aml: &'static [u8]) -> Self {
+        Self { aml, pos: 0 }
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.aml.len()
+    }
+
+    fn peek(&self) -> Result<u8, AMLError> {
+        self.peek_at(0).ok_or(AMLError::UnexpectedEnd)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.aml.get(self.pos + offset).copied()
+    }
+
+    fn byte(&mut self) -> Result<u8, AMLError> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'static [u8], AMLError> {
+        let bytes = self
+            .aml
+            .get(self.pos..self.pos + n)
+            .ok_or(AMLError::UnexpectedEnd)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// Reads a little endian integer of `n` bytes.
+    fn le(&mut self, n: usize) -> Result<u64, AMLError> {
+        Ok(self
+            .bytes(n)?
+            .iter()
+            .rev()
+            .fold(0, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    /// Takes everything up to the end of this cursor.
+    fn rest(&mut self) -> &'static [u8] {
+        let rest = self.aml.get(self.pos..).unwrap_or(&[]);
+        self.pos = self.aml.len();
+        rest
+    }
+
+    /// Reads a PkgLength. Bits 7-6 of the lead byte are the number of bytes
+    /// following it. Those hold bits 11 and up of the length, with bits 3-0 of
+    /// the lead byte as the lowest bits.
+    fn pkg_length(&mut self) -> Result<usize, AMLError> {
+        let lead = self.byte()?;
+        let follow = (lead >> 6) as usize;
+        if follow == 0 {
+            return Ok((lead & 0x3F) as usize);
+        }
+        let mut len = (lead & 0x0F) as usize;
+        for i in 0..follow {
+            len |= (self.byte()? as usize) << (4 + 8 * i);
+        }
+        Ok(len)
+    }
+
+    /// Reads a PkgLength and returns a cursor over the rest of the package,
+    /// moving this cursor past it. The length includes its own encoding.
+    fn package(&mut self) -> Result<Cursor, AMLError> {
+        let start = self.pos;
+        let len = self.pkg_length()?;
+        let body = self
+            .aml
+            .get(self.pos..start + len)
+            .ok_or(AMLError::UnexpectedEnd)?;
+        self.pos = start + len;
+        Ok(Cursor::new(body))
+    }
+
+    fn name_string(&mut self) -> Result<NameString, AMLError> {
+        let mut root = false;
+        let mut parents = 0;
+        if self.peek()? == ROOT_CHAR {
+            root = true;
+            self.pos += 1;
+        } else {
+            while self.peek()? == PARENT_PREFIX_CHAR {
+                parents += 1;
+                self.pos += 1;
+            }
+        }
+        let count = match self.peek()? {
+            ZERO_OP => {
+                self.pos += 1;
+                0
+            }
+            DUAL_NAME_PREFIX => {
+                self.pos += 1;
+                2
+            }
+            MULTI_NAME_PREFIX => {
+                self.pos += 1;
+                self.byte()? as usize
+            }
+            _ => 1,
+        };
+        Ok(NameString {
+            root,
+            parents,
+            segs: self.bytes(count * 4)?,
+        })
+    }
+}
+
+struct Frame {
+    /// The scope new objects are created in and names are resolved from.
+    scope: NodeId,
+    args: [AMLValue; NUM_ARGS],
+    locals: [AMLValue; NUM_LOCALS],
+    /// Whether a table is being loaded, in which case objects that fail to
+    /// load are skipped.
+    loading: bool,
+}
+
+impl Frame {
+    fn new(scope: NodeId, loading: bool) -> Self {
+        Self {
+            scope,
+            args: [AMLValue::Uninitialized; NUM_ARGS],
+            locals: [AMLValue::Uninitialized; NUM_LOCALS],
+            loading,
+        }
+    }
+}
+
+enum Flow {
+    Next,
+    Return(AMLValue),
+    Break,
+    Continue,
+}
+
+/// Where the result of an operation is stored.
+#[derive(Clone, Copy)]
+enum Target {
+    None,
+    Local(usize),
+    Arg(usize),
+    Node(NodeId),
+    Index(IndexRef),
+    Debug,
+}
+
+impl<'a> Namespace<'a> {
+    /// Evaluates an object. Methods are called with `args`, other objects
+    /// return their value.
+    pub unsafe fn evaluate(
+        &mut self,
+        node: NodeId,
+        args: &[AMLValue],
+    ) -> Result<AMLValue, AMLError> {
+        unsafe {
+            let mut frame_args = [AMLValue::Uninitialized; NUM_ARGS];
+            for (dst, src) in frame_args.iter_mut().zip(args) {
+                *dst = *src;
+            }
+            match self.node(node).map(|n| n.kind) {
+                Some(NodeKind::Method { .. }) => self.invoke(node, frame_args),
+                Some(_) => self.read_node(node),
+                None => Err(AMLError::NotFound),
+            }
+        }
+    }
+
+    /// Evaluates the child object `name` of `parent`, if there is one.
+    pub unsafe fn evaluate_child(
+        &mut self,
+        parent: NodeId,
+        name: [u8; 4],
+    ) -> Result<Option<AMLValue>, AMLError> {
+        unsafe {
+            match self.child(parent, name) {
+                Some(node) => Ok(Some(self.evaluate(node, &[])?)),
+                None => Ok(None),
+            }
+        }
+    }
+
+    /// The `_STA` status bits of a device.
+    pub unsafe fn device_status(&mut self, device: NodeId) -> Result<u64, AMLError> {
+        unsafe {
+            match self.evaluate_child(device, STA_NAME)? {
+                Some(value) => self.integer_of(value),
+                None => Ok(DEFAULT_STATUS),
+            }
+        }
+    }
+
+    /// Turns a name that couldn't be resolved when a package was created into
+    /// a reference, now that the namespace is complete.
+    pub fn resolve_value(&self, value: AMLValue) -> AMLValue {
+        match value {
+            AMLValue::Unresolved { scope, name } => match self.resolve(scope, &name) {
+                Some(node) => AMLValue::Reference(node),
+                None => value,
+            },
+            _ => value,
+        }
+    }
+
+    pub(super) unsafe fn execute_table(&mut self, aml: &'static [u8]) -> Result<(), AMLError> {
+        unsafe {
+            let mut frame = Frame::new(ROOT, true);
+            self.exec_term_list(Cursor::new(aml), &mut frame)?;
+            Ok(())
+        }
+    }
+
+    unsafe fn invoke(
+        &mut self,
+        node: NodeId,
+        args: [AMLValue; NUM_ARGS],
+    ) -> Result<AMLValue, AMLError> {
+        unsafe {
+            let Some(NodeKind::Method { code, .. }) = self.node(node).map(|n| n.kind) else {
+                return Err(AMLError::TypeMismatch);
+            };
+            if self.depth >= MAX_CALL_DEPTH {
+                return Err(AMLError::LimitExceeded);
+            }
+            let mut frame = Frame::new(node, false);
+            frame.args = args;
+            self.depth += 1;
+            let flow = self.exec_term_list(Cursor::new(code), &mut frame);
+            self.depth -= 1;
+            match flow? {
+                Flow::Return(value) => Ok(value),
+                _ => Ok(AMLValue::Uninitialized),
+            }
+        }
+    }
+
+    unsafe fn exec_term_list(&mut self, mut c: Cursor, f: &mut Frame) -> Result<Flow, AMLError> {
+        unsafe {
+            while !c.done() {
+                match self.exec_term(&mut c, f)? {
+                    Flow::Next => {}
+                    flow => return Ok(flow),
+                }
+            }
+            Ok(Flow::Next)
+        }
+    }
+
+    /// While loading, an object that fails to load is skipped. This is only
+    /// possible for objects with a package length, since their end is known.
+    fn tolerate(&mut self, f: &Frame, result: Result<Flow, AMLError>) -> Result<Flow, AMLError> {
+        if f.loading && result.is_err() {
+            self.load_errors += 1;
+            return Ok(Flow::Next);
+        }
+        result
+    }
+
+    unsafe fn exec_term(&mut self, c: &mut Cursor, f: &mut Frame) -> Result<Flow, AMLError> {
+        unsafe {
+            let op = c.peek()?;
+            match op {
+                NAME_OP => {
+                    c.byte()?;
+                    let name = c.name_string()?;
+                    let value = self.eval_term_arg(c, f)?;
+                    self.declare(f, &name, NodeKind::Name(value))?;
+                }
+                SCOPE_OP => {
+                    c.byte()?;
+                    let body = c.package()?;
+                    let result = self.exec_named_scope(body, f, NodeKind::Scope, 0);
+                    return self.tolerate(f, result);
+                }
+                METHOD_OP => {
+                    c.byte()?;
+                    let mut body = c.package()?;
+                    let name = body.name_string()?;
+                    let flags = body.byte()?;
+                    let code = body.rest();
+                    let result = self.declare(f, &name, NodeKind::Method { code, flags });
+                    return self.tolerate(f, result.map(|_| Flow::Next));
+                }
+                ALIAS_OP => {
+                    c.byte()?;
+                    let source = c.name_string()?;
+                    let alias = c.name_string()?;
+                    let target = self.resolve(f.scope, &source).ok_or(AMLError::NotFound)?;
+                    self.declare(f, &alias, NodeKind::Alias(target))?;
+                }
+                EXTERNAL_OP => {
+                    c.byte()?;
+                    c.name_string()?;
+                    c.bytes(2)?;
+                }
+                IF_OP => {
+                    c.byte()?;
+                    let mut body = c.package()?;
+                    let predicate = self.eval_integer(&mut body, f)?;
+                    let has_else = c.peek_at(0) == Some(ELSE_OP);
+                    if predicate != 0 {
+                        if has_else {
+                            c.byte()?;
+                            c.package()?;
+                        }
+                        return self.exec_term_list(body, f);
+                    } else if has_else {
+                        c.byte()?;
+                        let body = c.package()?;
+                        return self.exec_term_list(body, f);
+                    }
+                }
+                ELSE_OP => {
+                    // Only reached without a preceding If.
+                    c.byte()?;
+                    c.package()?;
+                }
+                WHILE_OP => {
+                    c.byte()?;
+                    let body = c.package()?;
+                    for _ in 0..MAX_LOOP_ITERATIONS {
+                        let mut body = body;
+                        if self.eval_integer(&mut body, f)? == 0 {
+                            return Ok(Flow::Next);
+                        }
+                        match self.exec_term_list(body, f)? {
+                            Flow::Break => return Ok(Flow::Next),
+                            Flow::Return(value) => return Ok(Flow::Return(value)),
+                            Flow::Next | Flow::Continue => {}
+                        }
+                    }
+                    return Err(AMLError::LimitExceeded);
+                }
+                RETURN_OP => {
+                    c.byte()?;
+                    return Ok(Flow::Return(self.eval_term_arg(c, f)?));
+                }
+                BREAK_OP => {
+                    c.byte()?;
+                    return Ok(Flow::Break);
+                }
+                CONTINUE_OP => {
+                    c.byte()?;
+                    return Ok(Flow::Continue);
+                }
+                NOOP_OP | BREAKPOINT_OP => {
+                    c.byte()?;
+                }
+                NOTIFY_OP => {
+                    c.byte()?;
+                    self.parse_target(c, f)?;
+                    self.eval_term_arg(c, f)?;
+                }
+                CREATE_DWORD_FIELD_OP
+                | CREATE_WORD_FIELD_OP
+                | CREATE_BYTE_FIELD_OP
+                | CREATE_BIT_FIELD_OP
+                | CREATE_QWORD_FIELD_OP => {
+                    c.byte()?;
+                    let buffer = self.eval_buffer(c, f)?;
+                    let index = self.eval_integer(c, f)? as usize;
+                    let (bit_offset, bit_len) = match op {
+                        CREATE_BIT_FIELD_OP => (index, 1),
+                        CREATE_BYTE_FIELD_OP => (index * 8, 8),
+                        CREATE_WORD_FIELD_OP => (index * 8, 16),
+                        CREATE_DWORD_FIELD_OP => (index * 8, 32),
+                        _ => (index * 8, 64),
+                    };
+                    let name = c.name_string()?;
+                    self.declare_buffer_field(f, &name, buffer, bit_offset, bit_len)?;
+                }
+                EXT_OP_PREFIX => return self.exec_ext_term(c, f),
+                _ => {
+                    self.eval_term_arg(c, f)?;
+                }
+            }
+            Ok(Flow::Next)
+        }
+    }
+
+    unsafe fn exec_ext_term(&mut self, c: &mut Cursor, f: &mut Frame) -> Result<Flow, AMLError> {
+        unsafe {
+            let Some(ext) = c.peek_at(1) else {
+                return Err(AMLError::UnexpectedEnd);
+            };
+            match ext {
+                MUTEX_OP | EVENT_OP => {
+                    c.bytes(2)?;
+                    let name = c.name_string()?;
+                    if ext == MUTEX_OP {
+                        c.byte()?; // SyncFlags
+                        self.declare(f, &name, NodeKind::Mutex)?;
+                    } else {
+                        self.declare(f, &name, NodeKind::Event)?;
+                    }
+                }
+                OP_REGION_OP => {
+                    c.bytes(2)?;
+                    let name = c.name_string()?;
+                    let space = c.byte()?;
+                    let offset = self.eval_integer(c, f)?;
+                    let len = self.eval_integer(c, f)?;
+                    self.declare(f, &name, NodeKind::OpRegion { space, offset, len })?;
+                }
+                FIELD_OP | INDEX_FIELD_OP | BANK_FIELD_OP => {
+                    c.bytes(2)?;
+                    let body = c.package()?;
+                    let result = self.exec_field(body, f, ext);
+                    return self.tolerate(f, result);
+                }
+                DEVICE_OP | THERMAL_ZONE_OP | PROCESSOR_OP | POWER_RES_OP => {
+                    c.bytes(2)?;
+                    let body = c.package()?;
+                    // Processors have an id, a PBLK address and a PBLK length,
+                    // power resources a system level and a resource order.
+                    let (kind, skip) = match ext {
+                        DEVICE_OP => (NodeKind::Device, 0),
+                        THERMAL_ZONE_OP => (NodeKind::ThermalZone, 0),
+                        PROCESSOR_OP => (NodeKind::Processor, 6),
+                        _ => (NodeKind::PowerResource, 3),
+                    };
+                    let result = self.exec_named_scope(body, f, kind, skip);
+                    return self.tolerate(f, result);
+                }
+                CREATE_FIELD_OP => {
+                    c.bytes(2)?;
+                    let buffer = self.eval_buffer(c, f)?;
+                    let bit_offset = self.eval_integer(c, f)? as usize;
+                    let bit_len = self.eval_integer(c, f)? as usize;
+                    let name = c.name_string()?;
+                    self.declare_buffer_field(f, &name, buffer, bit_offset, bit_len)?;
+                }
+                _ => {
+                    self.eval_term_arg(c, f)?;
+                }
+            }
+            Ok(Flow::Next)
+        }
+    }
+
+    /// Executes the body of a scope, device, processor, power resource or
+    /// thermal zone. `skip` bytes of fixed data follow the name.
+    unsafe fn exec_named_scope(
+        &mut self,
+        mut body: Cursor,
+        f: &Frame,
+        kind: NodeKind,
+        skip: usize,
+    ) -> Result<Flow, AMLError> {
+        unsafe {
+            let name = body.name_string()?;
+            body.bytes(skip)?;
+            let node = match kind {
+                NodeKind::Scope => self.resolve(f.scope, &name).ok_or(AMLError::NotFound)?,
+                _ => self.declare(f, &name, kind)?,
+            };
+            self.exec_term_list(body, &mut Frame::new(node, f.loading))?;
+            Ok(Flow::Next)
+        }
+    }
+
+    unsafe fn exec_field(
+        &mut self,
+        mut body: Cursor,
+        f: &mut Frame,
+        op: u8,
+    ) -> Result<Flow, AMLError> {
+        unsafe {
+            let resolve = |body: &mut Cursor, ns: &Self| {
+                let name = body.name_string()?;
+                ns.resolve(f.scope, &name).ok_or(AMLError::NotFound)
+            };
+            let kind = match op {
+                FIELD_OP => FieldKind::Region(resolve(&mut body, self)?),
+                INDEX_FIELD_OP => FieldKind::Index {
+                    index: resolve(&mut body, self)?,
+                    data: resolve(&mut body, self)?,
+                },
+                _ => {
+                    let region = resolve(&mut body, self)?;
+                    let bank = resolve(&mut body, self)?;
+                    let value = self.eval_integer(&mut body, f)?;
+                    FieldKind::Bank {
+                        region,
+                        bank,
+                        value,
+                    }
+                }
+            };
+
+            let mut flags = body.byte()?;
+            let mut bit_offset = 0;
+            while !body.done() {
+                match body.peek()? {
+                    RESERVED_FIELD => {
+                        body.byte()?;
+                        bit_offset += body.pkg_length()? as u32;
+                    }
+                    ACCESS_FIELD | EXTENDED_ACCESS_FIELD => {
+                        let extended = body.byte()? == EXTENDED_ACCESS_FIELD;
+                        let access_type = body.byte()?;
+                        body.bytes(if extended { 2 } else { 1 })?;
+                        flags = (flags & !FIELD_ACCESS_TYPE_MASK)
+                            | (access_type & FIELD_ACCESS_TYPE_MASK);
+                    }
+                    CONNECT_FIELD => {
+                        body.byte()?;
+                        if body.peek()? == BUFFER_OP {
+                            self.eval_term_arg(&mut body, f)?;
+                        } else {
+                            body.name_string()?;
+                        }
+                    }
+                    _ => {
+                        let seg = body.bytes(4)?;
+                        let name = <[u8; 4]>::try_from(seg).map_err(|_| AMLError::UnexpectedEnd)?;
+                        let bit_len = body.pkg_length()? as u32;
+                        let unit = FieldUnit {
+                            kind,
+                            bit_offset,
+                            bit_len,
+                            flags,
+                        };
+                        self.add(f.scope, name, NodeKind::Field(unit))?;
+                        bit_offset += bit_len;
+                    }
+                }
+            }
+            Ok(Flow::Next)
+        }
+    }
+
+    fn declare(
+        &mut self,
+        f: &Frame,
+        name: &NameString,
+        kind: NodeKind,
+    ) -> Result<NodeId, AMLError> {
+        let (parent, seg) = self
+            .resolve_parent(f.scope, name)
+            .ok_or(AMLError::NotFound)?;
+        self.add(parent, seg, kind)
+    }
+
+    fn declare_buffer_field(
+        &mut self,
+        f: &Frame,
+        name: &NameString,
+        buffer: Buffer,
+        bit_offset: usize,
+        bit_len: usize,
+    ) -> Result<NodeId, AMLError> {
+        self.declare(
+            f,
+            name,
+            NodeKind::BufferField {
+                buffer,
+                bit_offset,
+                bit_len,
+            },
+        )
+    }
+
+    unsafe fn eval_integer(&mut self, c: &mut Cursor, f: &mut Frame) -> Result<u64, AMLError> {
+        unsafe {
+            let value = self.eval_term_arg(c, f)?;
+            self.integer_of(value)
+        }
+    }
+
+    unsafe fn eval_buffer(&mut self, c: &mut Cursor, f: &mut Frame) -> Result<Buffer, AMLError> {
+        unsafe {
+            let value = self.eval_term_arg(c, f)?;
+            self.deref(value)?.as_buffer().ok_or(AMLError::TypeMismatch)
+        }
+    }
+
+    unsafe fn eval_term_arg(
+        &mut self,
+        c: &mut Cursor,
+        f: &mut Frame,
+    ) -> Result<AMLValue, AMLError> {
+        unsafe {
+            let op = c.byte()?;
+            Ok(match op {
+                ZERO_OP => AMLValue::Integer(0),
+                ONE_OP => AMLValue::Integer(1),
+                ONES_OP => AMLValue::Integer(self.truncate(u64::MAX)),
+                BYTE_PREFIX => AMLValue::Integer(c.le(1)?),
+                WORD_PREFIX => AMLValue::Integer(c.le(2)?),
+                DWORD_PREFIX => AMLValue::Integer(c.le(4)?),
+                QWORD_PREFIX => AMLValue::Integer(c.le(8)?),
+                STRING_PREFIX => {
+                    let start = c.pos;
+                    while c.byte()? != 0 {}
+                    AMLValue::String(c.aml.get(start..c.pos - 1).unwrap_or(&[]))
+                }
+                BUFFER_OP => {
+                    let mut body = c.package()?;
+                    let size = self.eval_integer(&mut body, f)? as usize;
+                    let init = body.rest();
+                    let buffer = self.alloc_buffer(size.max(init.len()))?;
+                    for (i, b) in init.iter().enumerate() {
+                        buffer.set(i, *b);
+                    }
+                    AMLValue::Buffer(buffer)
+                }
+                PACKAGE_OP | VAR_PACKAGE_OP => {
+                    let mut body = c.package()?;
+                    let count = if op == PACKAGE_OP {
+                        body.byte()? as usize
+                    } else {
+                        self.eval_integer(&mut body, f)? as usize
+                    };
+                    let package = self.alloc_package(count)?;
+                    let mut i = 0;
+                    while !body.done() {
+                        // Names in a package are references, not evaluated.
+                        let element = if is_name_start(body.peek()?) {
+                            let name = body.name_string()?;
+                            match self.resolve(f.scope, &name) {
+                                Some(node) => AMLValue::Reference(node),
+                                None => AMLValue::Unresolved {
+                                    scope: f.scope,
+                                    name,
+                                },
+                            }
+                        } else {
+                            self.eval_term_arg(&mut body, f)?
+                        };
+                        package.set(i, element);
+                        i += 1;
+                    }
+                    AMLValue::Package(package)
+                }
+                LOCAL0_OP..=LOCAL7_OP => {
+                    self.read_target(f, Target::Local((op - LOCAL0_OP) as usize))?
+                }
+                ARG0_OP..=ARG6_OP => self.read_target(f, Target::Arg((op - ARG0_OP) as usize))?,
+                STORE_OP => {
+                    let value = self.eval_term_arg(c, f)?;
+                    let target = self.parse_target(c, f)?;
+                    self.write_target(f, target, value)?;
+                    value
+                }
+                ADD_OP | SUBTRACT_OP | MULTIPLY_OP | SHIFT_LEFT_OP | SHIFT_RIGHT_OP | AND_OP
+                | NAND_OP | OR_OP | NOR_OP | XOR_OP | MOD_OP => {
+                    let a = self.eval_integer(c, f)?;
+                    let b = self.eval_integer(c, f)?;
+                    let result = match op {
+                        ADD_OP => a.wrapping_add(b),
+                        SUBTRACT_OP => a.wrapping_sub(b),
+                        MULTIPLY_OP => a.wrapping_mul(b),
+                        SHIFT_LEFT_OP if b < 64 => a << b,
+                        SHIFT_RIGHT_OP if b < 64 => a >> b,
+                        SHIFT_LEFT_OP | SHIFT_RIGHT_OP => 0,
+                        AND_OP => a & b,
+                        NAND_OP => !(a & b),
+                        OR_OP => a | b,
+                        NOR_OP => !(a | b),
+                        XOR_OP => a ^ b,
+                        _ => a.checked_rem(b).ok_or(AMLError::DivideByZero)?,
+                    };
+                    self.store_integer(c, f, result)?
+                }
+                DIVIDE_OP => {
+                    let a = self.eval_integer(c, f)?;
+                    let b = self.eval_integer(c, f)?;
+                    let quotient = a.checked_div(b).ok_or(AMLError::DivideByZero)?;
+                    let remainder = a.checked_rem(b).unwrap_or(0);
+                    self.store_integer(c, f, remainder)?;
+                    self.store_integer(c, f, quotient)?
+                }
+                NOT_OP => {
+                    let a = self.eval_integer(c, f)?;
+                    self.store_integer(c, f, !a)?
+                }
+                FIND_SET_LEFT_BIT_OP => {
+                    let a = self.eval_integer(c, f)?;
+                    self.store_integer(c, f, (64 - a.leading_zeros()) as u64)?
+                }
+                FIND_SET_RIGHT_BIT_OP => {
+                    let a = self.eval_integer(c, f)?;
+                    let bit = if a == 0 { 0 } else { a.trailing_zeros() + 1 };
+                    self.store_integer(c, f, bit as u64)?
+                }
+                INCREMENT_OP | DECREMENT_OP => {
+                    let target = self.parse_target(c, f)?;
+                    let value = self.read_target(f, target)?;
+                    let n = self.integer_of(value)?;
+                    let n = if op == INCREMENT_OP {
+                        n.wrapping_add(1)
+                    } else {
+                        n.wrapping_sub(1)
+                    };
+                    let value = AMLValue::Integer(self.truncate(n));
+                    self.write_target(f, target, value)?;
+                    value
+                }
+                LAND_OP | LOR_OP => {
+                    let a = self.eval_integer(c, f)? != 0;
+                    let b = self.eval_integer(c, f)? != 0;
+                    self.logical(if op == LAND_OP { a && b } else { a || b })
+                }
+                LNOT_OP => {
+                    let a = self.eval_integer(c, f)?;
+                    self.logical(a == 0)
+                }
+                LEQUAL_OP | LGREATER_OP | LLESS_OP => {
+                    let a = self.eval_term_arg(c, f)?;
+                    let b = self.eval_term_arg(c, f)?;
+                    let ordering = self.compare(a, b)?;
+                    self.logical(
+                        ordering
+                            == match op {
+                                LEQUAL_OP => Ordering::Equal,
+                                LGREATER_OP => Ordering::Greater,
+                                _ => Ordering::Less,
+                            },
+                    )
+                }
+                SIZE_OF_OP => {
+                    let target = self.parse_target(c, f)?;
+                    let value = self.read_target(f, target)?;
+                    let size = match self.deref(value)? {
+                        AMLValue::String(s) => s.len(),
+                        AMLValue::Buffer(b) => b.len(),
+                        AMLValue::Package(p) => p.len(),
+                        _ => return Err(AMLError::TypeMismatch),
+                    };
+                    AMLValue::Integer(size as u64)
+                }
+                INDEX_OP => {
+                    let source = self.eval_term_arg(c, f)?;
+                    let index = self.eval_integer(c, f)? as usize;
+                    let reference = match self.deref(source)? {
+                        AMLValue::Package(p) => IndexRef::Package(p, index),
+                        AMLValue::Buffer(b) => IndexRef::Buffer(b, index),
+                        _ => return Err(AMLError::TypeMismatch),
+                    };
+                    let target = self.parse_target(c, f)?;
+                    self.write_target(f, target, AMLValue::Index(reference))?;
+                    AMLValue::Index(reference)
+                }
+                DEREF_OF_OP => {
+                    let value = self.eval_term_arg(c, f)?;
+                    self.deref(value)?
+                }
+                REF_OF_OP => match self.parse_target(c, f)? {
+                    Target::Node(node) => AMLValue::Reference(node),
+                    Target::Index(reference) => AMLValue::Index(reference),
+                    _ => return Err(AMLError::Unsupported(op)),
+                },
+                TO_INTEGER_OP => {
+                    let n = self.eval_integer(c, f)?;
+                    self.store_integer(c, f, n)?
+                }
+                EXT_OP_PREFIX => self.eval_ext_term_arg(c, f)?,
+                _ if is_name_start(op) => {
+                    c.pos -= 1;
+                    let name = c.name_string()?;
+                    let node = self.resolve(f.scope, &name).ok_or(AMLError::NotFound)?;
+                    self.eval_name(node, c, f)?
+                }
+                _ => return Err(AMLError::Unsupported(op)),
+            })
+        }
+    }
+
+    unsafe fn eval_ext_term_arg(
+        &mut self,
+        c: &mut Cursor,
+        f: &mut Frame,
+    ) -> Result<AMLValue, AMLError> {
+        unsafe {
+            let ext = c.byte()?;
+            Ok(match ext {
+                COND_REF_OF_OP => {
+                    let node = if is_name_start(c.peek()?) {
+                        let name = c.name_string()?;
+                        self.resolve(f.scope, &name)
+                    } else {
+                        match self.parse_target(c, f)? {
+                            Target::Node(node) => Some(node),
+                            _ => None,
+                        }
+                    };
+                    let target = self.parse_target(c, f)?;
+                    if let Some(node) = node {
+                        self.write_target(f, target, AMLValue::Reference(node))?;
+                    }
+                    self.logical(node.is_some())
+                }
+                ACQUIRE_OP => {
+                    // There is only one thread of AML execution, so acquiring
+                    // a mutex always succeeds.
+                    self.parse_target(c, f)?;
+                    c.le(2)?;
+                    AMLValue::Integer(0)
+                }
+                RELEASE_OP | SIGNAL_OP | RESET_OP => {
+                    self.parse_target(c, f)?;
+                    AMLValue::Uninitialized
+                }
+                WAIT_OP => {
+                    self.parse_target(c, f)?;
+                    self.eval_integer(c, f)?;
+                    AMLValue::Integer(0)
+                }
+                SLEEP_OP => {
+                    let ms = self.eval_integer(c, f)?;
+                    io_delay_us(ms.min(1000) as u32 * 1000);
+                    AMLValue::Uninitialized
+                }
+                STALL_OP => {
+                    let us = self.eval_integer(c, f)?;
+                    io_delay_us(us.min(100) as u32);
+                    AMLValue::Uninitialized
+                }
+                REVISION_OP => AMLValue::Integer(INTERPRETER_REVISION),
+                DEBUG_OP => AMLValue::Uninitialized,
+                TIMER_OP => AMLValue::Integer(0),
+                _ => return Err(AMLError::Unsupported(ext)),
+            })
+        }
+    }
+
+    /// Evaluates a name in a term argument. Methods are called with the
+    /// arguments that follow the name.
+    unsafe fn eval_name(
+        &mut self,
+        node: NodeId,
+        c: &mut Cursor,
+        f: &mut Frame,
+    ) -> Result<AMLValue, AMLError> {
+        unsafe {
+            match self.node(node).map(|n| n.kind) {
+                Some(NodeKind::Method { flags, .. }) => {
+                    let mut args = [AMLValue::Uninitialized; NUM_ARGS];
+                    for arg in args.iter_mut().take((flags & 0x07) as usize) {
+                        *arg = self.eval_term_arg(c, f)?;
+                    }
+                    self.invoke(node, args)
+                }
+                Some(NodeKind::Alias(target)) => self.eval_name(target, c, f),
+                _ => self.read_node(node),
+            }
+        }
+    }
+
+    /// Stores an integer result in the target that follows it.
+    unsafe fn store_integer(
+        &mut self,
+        c: &mut Cursor,
+        f: &mut Frame,
+        n: u64,
+    ) -> Result<AMLValue, AMLError> {
+        unsafe {
+            let value = AMLValue::Integer(self.truncate(n));
+            let target = self.parse_target(c, f)?;
+            self.write_target(f, target, value)?;
+            Ok(value)
+        }
+    }
+
+    unsafe fn parse_target(&mut self, c: &mut Cursor, f: &mut Frame) -> Result<Target, AMLError> {
+        unsafe {
+            let op = c.peek()?;
+            Ok(match op {
+                ZERO_OP => {
+                    c.byte()?;
+                    Target::None
+                }
+                LOCAL0_OP..=LOCAL7_OP => {
+                    c.byte()?;
+                    Target::Local((op - LOCAL0_OP) as usize)
+                }
+                ARG0_OP..=ARG6_OP => {
+                    c.byte()?;
+                    Target::Arg((op - ARG0_OP) as usize)
+                }
+                EXT_OP_PREFIX if c.peek_at(1) == Some(DEBUG_OP) => {
+                    c.bytes(2)?;
+                    Target::Debug
+                }
+                INDEX_OP | REF_OF_OP => match self.eval_term_arg(c, f)? {
+                    AMLValue::Index(reference) => Target::Index(reference),
+                    AMLValue::Reference(node) => Target::Node(node),
+                    _ => return Err(AMLError::TypeMismatch),
+                },
+                DEREF_OF_OP => {
+                    c.byte()?;
+                    match self.eval_term_arg(c, f)? {
+                        AMLValue::Index(reference) => Target::Index(reference),
+                        AMLValue::Reference(node) => Target::Node(node),
+                        _ => return Err(AMLError::TypeMismatch),
+                    }
+                }
+                _ if is_name_start(op) => {
+                    let name = c.name_string()?;
+                    Target::Node(self.resolve(f.scope, &name).ok_or(AMLError::NotFound)?)
+                }
+                _ => return Err(AMLError::Unsupported(op)),
+            })
+        }
+    }
+
+    unsafe fn read_target(&mut self, f: &Frame, target: Target) -> Result<AMLValue, AMLError> {
+        unsafe {
+            match target {
+                Target::None | Target::Debug => Ok(AMLValue::Uninitialized),
+                Target::Local(i) => f.locals.get(i).copied().ok_or(AMLError::OutOfBounds),
+                Target::Arg(i) => f.args.get(i).copied().ok_or(AMLError::OutOfBounds),
+                Target::Node(node) => self.read_node(node),
+                Target::Index(reference) => self.deref(AMLValue::Index(reference)),
+            }
+        }
+    }
+
+    unsafe fn write_target(
+        &mut self,
+        f: &mut Frame,
+        target: Target,
+        value: AMLValue,
+    ) -> Result<(), AMLError> {
+        unsafe {
+            let slot = match target {
+                Target::None | Target::Debug => return Ok(()),
+                Target::Local(i) => f.locals.get_mut(i),
+                Target::Arg(i) => f.args.get_mut(i),
+                Target::Node(node) => return self.write_node(node, value),
+                Target::Index(IndexRef::Package(package, i)) => {
+                    return if package.set(i, value) {
+                        Ok(())
+                    } else {
+                        Err(AMLError::OutOfBounds)
+                    };
+                }
+                Target::Index(IndexRef::Buffer(buffer, i)) => {
+                    let b = self.integer_of(value)? as u8;
+                    return if buffer.set(i, b) {
+                        Ok(())
+                    } else {
+                        Err(AMLError::OutOfBounds)
+                    };
+                }
+            };
+            *slot.ok_or(AMLError::OutOfBounds)? = value;
+            Ok(())
+        }
+    }
+
+    unsafe fn read_node(&mut self, node: NodeId) -> Result<AMLValue, AMLError> {
+        unsafe {
+            match self.node(node).map(|n| n.kind) {
+                Some(NodeKind::Name(value)) => Ok(value),
+                Some(NodeKind::Method { .. }) => {
+                    self.invoke(node, [AMLValue::Uninitialized; NUM_ARGS])
+                }
+                Some(NodeKind::Field(unit)) => Ok(AMLValue::Integer(self.read_field(&unit)?)),
+                Some(NodeKind::BufferField {
+                    buffer,
+                    bit_offset,
+                    bit_len,
+                }) => Ok(AMLValue::Integer(read_bits(buffer, bit_offset, bit_len)?)),
+                Some(NodeKind::Alias(target)) => self.read_node(target),
+                Some(_) => Ok(AMLValue::Reference(node)),
+                None => Err(AMLError::NotFound),
+            }
+        }
+    }
+
+    unsafe fn write_node(&mut self, node: NodeId, value: AMLValue) -> Result<(), AMLError> {
+        unsafe {
+            match self.node(node).map(|n| n.kind) {
+                Some(NodeKind::Name(_)) => {
+                    if let Some(n) = self.node_mut(node) {
+                        n.kind = NodeKind::Name(value);
+                    }
+                    Ok(())
+                }
+                Some(NodeKind::Field(unit)) => {
+                    let n = self.integer_of(value)?;
+                    self.write_field(&unit, n)
+                }
+                Some(NodeKind::BufferField {
+                    buffer,
+                    bit_offset,
+                    bit_len,
+                }) => write_bits(buffer, bit_offset, bit_len, self.integer_of(value)?),
+                Some(NodeKind::Alias(target)) => self.write_node(target, value),
+                Some(_) => Err(AMLError::TypeMismatch),
+                None => Err(AMLError::NotFound),
+            }
+        }
+    }
+
+    /// Follows a reference to a package element, buffer byte or named object.
+    unsafe fn deref(&mut self, value: AMLValue) -> Result<AMLValue, AMLError> {
+        unsafe {
+            match value {
+                AMLValue::Index(IndexRef::Package(package, i)) => {
+                    package.get(i).ok_or(AMLError::OutOfBounds)
+                }
+                AMLValue::Index(IndexRef::Buffer(buffer, i)) => buffer
+                    .get(i)
+                    .map(|b| AMLValue::Integer(b as u64))
+                    .ok_or(AMLError::OutOfBounds),
+                AMLValue::Reference(node) => self.read_node(node),
+                _ => Ok(value),
+            }
+        }
+    }
+
+    pub(super) unsafe fn integer_of(&mut self, value: AMLValue) -> Result<u64, AMLError> {
+        unsafe {
+            match self.deref(value)? {
+                AMLValue::Integer(n) => Ok(n),
+                AMLValue::Buffer(buffer) => Ok(buffer
+                    .bytes()
+                    .iter()
+                    .take(8)
+                    .rev()
+                    .fold(0, |acc, b| (acc << 8) | *b as u64)),
+                _ => Err(AMLError::TypeMismatch),
+            }
+        }
+    }
+
+    unsafe fn compare(&mut self, a: AMLValue, b: AMLValue) -> Result<Ordering, AMLError> {
+        unsafe {
+            match (self.deref(a)?, self.deref(b)?) {
+                (AMLValue::String(a), AMLValue::String(b)) => Ok(a.cmp(b)),
+                (AMLValue::Buffer(a), AMLValue::Buffer(b)) => Ok(a.bytes().cmp(b.bytes())),
+                (a, b) => Ok(self.integer_of(a)?.cmp(&self.integer_of(b)?)),
+            }
+        }
+    }
+
+    fn truncate(&self, n: u64) -> u64 {
+        if self.wide_integers {
+            n
+        } else {
+            n & 0xFFFF_FFFF
+        }
+    }
+
+    fn logical(&self, b: bool) -> AMLValue {
+        AMLValue::Integer(if b { self.truncate(u64::MAX) } else { 0 })
+    }
+
+    unsafe fn alloc_buffer(&mut self, len: usize) -> Result<Buffer, AMLError> {
+        unsafe {
+            let ptr = self
+                .mem
+                .lock()
+                .malloc(len, false)
+                .ok_or(AMLError::OutOfMemory)?;
+            ptr.write_bytes(0, len);
+            Ok(Buffer::new(ptr, len))
+        }
+    }
+
+    unsafe fn alloc_package(&mut self, len: usize) -> Result<Package, AMLError> {
+        unsafe {
+            let align = core::mem::align_of::<AMLValue>();
+            let size = len.saturating_mul(core::mem::size_of::<AMLValue>());
+            let addr = self
+                .mem
+                .lock()
+                .malloc(size.saturating_add(align - 1), false)
+                .ok_or(AMLError::OutOfMemory)? as usize;
+            let ptr = ((addr + align - 1) & !(align - 1)) as *mut AMLValue;
+            for i in 0..len {
+                ptr.add(i).write(AMLValue::Uninitialized);
+            }
+            Ok(Package::new(ptr, len))
+        }
+    }
+
+    unsafe fn read_field(&mut self, unit: &FieldUnit) -> Result<u64, AMLError> {
+        unsafe {
+            if unit.bit_len > 64 {
+                return Err(AMLError::Unsupported(FIELD_OP));
+            }
+            let width = access_width(unit.flags);
+            let unit_bits = (width * 8) as u32;
+            let mut value = 0;
+            let mut done = 0;
+            while done < unit.bit_len {
+                let bit = unit.bit_offset + done;
+                let shift = bit % unit_bits;
+                let n = (unit_bits - shift).min(unit.bit_len - done);
+                let raw = self.read_field_unit(unit, (bit / unit_bits) as usize * width, width)?;
+                value |= ((raw >> shift) & mask(n)) << done;
+                done += n;
+            }
+            Ok(value)
+        }
+    }
+
+    unsafe fn write_field(&mut self, unit: &FieldUnit, value: u64) -> Result<(), AMLError> {
+        unsafe {
+            if unit.bit_len > 64 {
+                return Err(AMLError::Unsupported(FIELD_OP));
+            }
+            let width = access_width(unit.flags);
+            let unit_bits = (width * 8) as u32;
+            let mut done = 0;
+            while done < unit.bit_len {
+                let bit = unit.bit_offset + done;
+                let shift = bit % unit_bits;
+                let n = (unit_bits - shift).min(unit.bit_len - done);
+                let offset = (bit / unit_bits) as usize * width;
+                // Bits of the access unit outside the field follow the update rule.
+                let base = if n == unit_bits {
+                    0
+                } else {
+                    match (unit.flags >> FIELD_UPDATE_RULE_SHIFT) & 0b11 {
+                        UPDATE_WRITE_AS_ONES => u64::MAX,
+                        UPDATE_WRITE_AS_ZEROS => 0,
+                        _ => self.read_field_unit(unit, offset, width)?,
+                    }
+                };
+                let bits = ((value >> done) & mask(n)) << shift;
+                let raw = (base & !(mask(n) << shift)) | bits;
+                self.write_field_unit(unit, offset, width, raw)?;
+                done += n;
+            }
+            Ok(())
+        }
+    }
+
+    unsafe fn read_field_unit(
+        &mut self,
+        unit: &FieldUnit,
+        offset: usize,
+        width: usize,
+    ) -> Result<u64, AMLError> {
+        unsafe {
+            match unit.kind {
+                FieldKind::Region(region) => self.access_region(region, offset, width, None),
+                FieldKind::Index { index, data } => {
+                    self.write_node(index, AMLValue::Integer(offset as u64))?;
+                    let value = self.read_node(data)?;
+                    self.integer_of(value)
+                }
+                FieldKind::Bank {
+                    region,
+                    bank,
+                    value,
+                } => {
+                    self.write_node(bank, AMLValue::Integer(value))?;
+                    self.access_region(region, offset, width, None)
+                }
+            }
+        }
+    }
+
+    unsafe fn write_field_unit(
+        &mut self,
+        unit: &FieldUnit,
+        offset: usize,
+        width: usize,
+        raw: u64,
+    ) -> Result<(), AMLError> {
+        unsafe {
+            match unit.kind {
+                FieldKind::Region(region) => {
+                    self.access_region(region, offset, width, Some(raw))?;
+                }
+                FieldKind::Index { index, data } => {
+                    self.write_node(index, AMLValue::Integer(offset as u64))?;
+                    self.write_node(data, AMLValue::Integer(raw))?;
+                }
+                FieldKind::Bank {
+                    region,
+                    bank,
+                    value,
+                } => {
+                    self.write_node(bank, AMLValue::Integer(value))?;
+                    self.access_region(region, offset, width, Some(raw))?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Reads `width` bytes at `offset` in an operation region, or writes them
+    /// when `write` is given.
+    unsafe fn access_region(
+        &mut self,
+        region: NodeId,
+        offset: usize,
+        width: usize,
+        write: Option<u64>,
+    ) -> Result<u64, AMLError> {
+        unsafe {
+            let Some(NodeKind::OpRegion {
+                space,
+                offset: base,
+                ..
+            }) = self.node(region).map(|n| n.kind)
+            else {
+                return Err(AMLError::TypeMismatch);
+            };
+            let addr = base.wrapping_add(offset as u64);
+            match space {
+                GenericAddress::SYSTEM_MEMORY if addr <= u32::MAX as u64 => {
+                    let ptr = addr as usize;
+                    Ok(match (width, write) {
+                        (1, None) => (ptr as *const u8).read_volatile() as u64,
+                        (2, None) => (ptr as *const u16).read_volatile() as u64,
+                        (_, None) => (ptr as *const u32).read_volatile() as u64,
+                        (1, Some(v)) => {
+                            (ptr as *mut u8).write_volatile(v as u8);
+                            0
+                        }
+                        (2, Some(v)) => {
+                            (ptr as *mut u16).write_volatile(v as u16);
+                            0
+                        }
+                        (_, Some(v)) => {
+                            (ptr as *mut u32).write_volatile(v as u32);
+                            0
+                        }
+                    })
+                }
+                GenericAddress::SYSTEM_IO => {
+                    let port = addr as u16;
+                    Ok(match (width, write) {
+                        (1, None) => read_port_byte(port) as u64,
+                        (2, None) => read_port_word(port) as u64,
+                        (_, None) => read_port_dword(port) as u64,
+                        (1, Some(v)) => {
+                            write_port_byte(port, v as u8);
+                            0
+                        }
+                        (2, Some(v)) => {
+                            write_port_word(port, v as u16);
+                            0
+                        }
+                        (_, Some(v)) => {
+                            write_port_dword(port, v as u32);
+                            0
+                        }
+                    })
+                }
+                GenericAddress::PCI_CONFIG => {
+                    let device = self.pci_address(region)?;
+                    let reg = addr as u16;
+                    let shift = (addr & 0x03) as u32 * 8;
+                    let width_mask = mask((width * 8) as u32) << shift;
+                    let dword = ConfigSpace::Ports.read_dword(device, reg) as u64;
+                    match write {
+                        None => Ok((dword & width_mask) >> shift),
+                        Some(v) => {
+                            let dword = (dword & !width_mask) | ((v << shift) & width_mask);
+                            ConfigSpace::Ports.write_dword(device, reg, dword as u32);
+                            Ok(0)
+                        }
+                    }
+                }
+                _ => Err(AMLError::Unsupported(OP_REGION_OP)),
+            }
+        }
+    }
+
+    /// The PCI function of a PCI_Config region. The device is given by the `_ADR` of the device
+    /// declaring the region, the bus by the `_BBN` of its host bridge.
+    unsafe fn pci_address(&mut self, region: NodeId) -> Result<PCIAddress, AMLError> {
+        unsafe {
+            let mut device = self.parent(region);
+            let adr = loop {
+                if let Some(adr) = self.evaluate_child(device, ADR_NAME)? {
+                    break self.integer_of(adr)?;
+                }
+                if device == ROOT {
+                    return Err(AMLError::NotFound);
+                }
+                device = self.parent(device);
+            };
+            let mut bus = 0;
+            let mut scope = device;
+            while scope != ROOT {
+                if let Some(bbn) = self.evaluate_child(scope, BBN_NAME)? {
+                    bus = self.integer_of(bbn)?;
+                    break;
+                }
+                scope = self.parent(scope);
+            }
+            Ok(PCIAddress::new(bus as u8, (adr >> 16) as u8, adr as u8))
+        }
+    }
+}
+
+/// The number of bytes a field is accessed with, from the access type in its
+/// flags. QWord accesses are split, since the CPU can't do 64 bit port IO.
+fn access_width(flags: u8) -> usize {
+    match flags & FIELD_ACCESS_TYPE_MASK {
+        2 => 2,
+        3 | 4 => 4,
+        _ => 1,
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn read_bits(buffer: Buffer, bit_offset: usize, bit_len: usize) -> Result<u64, AMLError> {
+    let mut value = 0;
+    for i in 0..bit_len.min(64) {
+        let bit = bit_offset + i;
+        let byte = buffer.get(bit / 8).ok_or(AMLError::OutOfBounds)?;
+        value |= (((byte >> (bit % 8)) & 1) as u64) << i;
+    }
+    Ok(value)
+}
+
+fn write_bits(
+    buffer: Buffer,
+    bit_offset: usize,
+    bit_len: usize,
+    value: u64,
+) -> Result<(), AMLError> {
+    for i in 0..bit_len.min(64) {
+        let bit = bit_offset + i;
+        let byte = buffer.get(bit / 8).ok_or(AMLError::OutOfBounds)?;
+        let byte = if (value >> i) & 1 != 0 {
+            byte | 1 << (bit % 8)
+        } else {
+            byte & !(1 << (bit % 8))
+        };
+        buffer.set(bit / 8, byte);
+    }
+    Ok(())
+}
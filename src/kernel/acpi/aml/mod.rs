@@ -0,0 +1,41 @@
+// ACPI Machine Language
+//
+// The DSDT and SSDTs contain AML byte code describing the devices of the
+// system. Loading a table executes its top level, which declares the objects
+// of the ACPI namespace. Control methods in the namespace can then be
+// evaluated, e.g. `_STA` for the status of a device, `_CRS` for the resources
+// it uses and `_PRT` for the interrupt routing of a PCI bus.
+//
+// Only the subset of AML that firmware commonly uses is supported. Strings
+// and buffers can't be concatenated or converted, and objects can't be
+// unloaded. The namespace and the packages and buffers evaluation creates
+// aren't freed one by one: callers mark the heap before loading the
+// namespace and release everything allocated since once they are done.
+
+pub mod interpreter;
+pub mod namespace;
+pub mod opcodes;
+pub mod prt;
+pub mod value;
+
+#[derive(Clone, Copy)]
+pub enum AMLError {
+    /// The byte code ended in the middle of an object.
+    UnexpectedEnd,
+    /// An opcode the interpreter doesn't know or support.
+    Unsupported(u8),
+    /// A name that doesn't resolve to an object.
+    NotFound,
+    /// A value of the wrong type, e.g. a package where an integer is needed.
+    TypeMismatch,
+    /// A package or buffer index out of range.
+    OutOfBounds,
+    DivideByZero,
+    /// The namespace can't hold any more objects.
+    NamespaceFull,
+    /// Methods called each other too deeply, or a loop ran too long.
+    LimitExceeded,
+    /// The kernel heap is full.
+    OutOfMemory,
+    NoDSDT,
+}
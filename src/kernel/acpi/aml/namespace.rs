@@ -0,0 +1,343 @@
+// The ACPI namespace
+//
+// Objects form a tree of 4 character names rooted at `\`. Nodes are stored in
+// a fixed size array in creation order and refer to their parent by index.
+
+use crate::{
+    dyn_array::DynArray,
+    kernel::{
+        acpi::{
+            acpi::{ACPI, ACPISDTHeader},
+            aml::{
+                AMLError,
+                value::{AMLValue, Buffer},
+            },
+            fadt::FADT,
+        },
+        mem::MemoryManager,
+    },
+};
+
+pub type NodeId = usize;
+
+pub const ROOT: NodeId = 0;
+
+const MAX_NODES: usize = 1024;
+const DSDT_SIGNATURE: [u8; 4] = *b"DSDT";
+const SSDT_SIGNATURE: [u8; 4] = *b"SSDT";
+const ROOT_NAME: [u8; 4] = *b"\\___";
+/// Scopes that exist before any table is loaded.
+const PREDEFINED_SCOPES: [[u8; 4]; 5] = [*b"_GPE", *b"_PR_", *b"_SB_", *b"_SI_", *b"_TZ_"];
+
+/// A name as encoded in AML: an optional root or parent prefixes, followed by
+/// any number of 4 byte name segments.
+#[derive(Clone, Copy)]
+pub struct NameString {
+    pub root: bool,
+    pub parents: u8,
+    pub segs: &'static [u8],
+}
+
+impl NameString {
+    pub fn seg_count(&self) -> usize {
+        self.segs.len() / 4
+    }
+
+    pub fn seg(&self, index: usize) -> Option<[u8; 4]> {
+        let seg = self.segs.get(index * 4..index * 4 + 4)?;
+        <[u8; 4]>::try_from(seg).ok()
+    }
+}
+
+/// How a field unit accesses its bits.
+#[derive(Clone, Copy)]
+pub enum FieldKind {
+    /// A field in an operation region.
+    Region(NodeId),
+    /// A field accessed by writing its offset to the `index` field and then
+    /// accessing the `data` field.
+    Index { index: NodeId, data: NodeId },
+    /// A field in an operation region that is selected by writing `value` to
+    /// the `bank` field first.
+    Bank {
+        region: NodeId,
+        bank: NodeId,
+        value: u64,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub struct FieldUnit {
+    pub kind: FieldKind,
+    pub bit_offset: u32,
+    pub bit_len: u32,
+    /// Bits 3-0: access type, bit 4: lock rule, bits 6-5: update rule
+    pub flags: u8,
+}
+
+#[derive(Clone, Copy)]
+pub enum NodeKind {
+    Scope,
+    Device,
+    Processor,
+    PowerResource,
+    ThermalZone,
+    Name(AMLValue),
+    Method {
+        code: &'static [u8],
+        flags: u8,
+    },
+    OpRegion {
+        space: u8,
+        offset: u64,
+        len: u64,
+    },
+    Field(FieldUnit),
+    BufferField {
+        buffer: Buffer,
+        bit_offset: usize,
+        bit_len: usize,
+    },
+    Mutex,
+    Event,
+    Alias(NodeId),
+}
+
+impl NodeKind {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            NodeKind::Scope => "scope",
+            NodeKind::Device => "device",
+            NodeKind::Processor => "processor",
+            NodeKind::PowerResource => "power resource",
+            NodeKind::ThermalZone => "thermal zone",
+            NodeKind::Name(_) => "name",
+            NodeKind::Method { .. } => "method",
+            NodeKind::OpRegion { .. } => "region",
+            NodeKind::Field(_) => "field",
+            NodeKind::BufferField { .. } => "buffer field",
+            NodeKind::Mutex => "mutex",
+            NodeKind::Event => "event",
+            NodeKind::Alias(_) => "alias",
+        }
+    }
+
+    /// Whether nodes of this kind can contain other named objects.
+    pub fn is_container(&self) -> bool {
+        matches!(
+            self,
+            NodeKind::Scope
+                | NodeKind::Device
+                | NodeKind::Processor
+                | NodeKind::PowerResource
+                | NodeKind::ThermalZone
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Node {
+    pub name: [u8; 4],
+    pub parent: NodeId,
+    pub kind: NodeKind,
+}
+
+pub struct Namespace<'a> {
+    nodes: DynArray<'a, Node>,
+    count: usize,
+    pub(super) mem: &'a spin::Mutex<MemoryManager>,
+    /// Integers are 64 bits wide from DSDT revision 2 onwards, 32 bits before.
+    pub(super) wide_integers: bool,
+    /// The depth of nested method calls.
+    pub(super) depth: usize,
+    /// The number of objects that failed to load. Loading continues after
+    /// the object, so the rest of the namespace is still usable.
+    pub load_errors: usize,
+}
+
+impl<'a> Namespace<'a> {
+    /// Builds the namespace by loading the DSDT and all SSDTs.
+    pub unsafe fn load(acpi: &ACPI, mem: &'a spin::Mutex<MemoryManager>) -> Result<Self, AMLError> {
+        unsafe {
+            let fadt = FADT::from_acpi(acpi).ok_or(AMLError::NoDSDT)?;
+            let dsdt = &*(fadt.dsdt_addr() as *const ACPISDTHeader);
+            if dsdt.signature != DSDT_SIGNATURE || !dsdt.is_valid() {
+                return Err(AMLError::NoDSDT);
+            }
+
+            let mut ns = Self {
+                nodes: DynArray::new(MAX_NODES, true, mem).map_err(|_| AMLError::OutOfMemory)?,
+                count: 0,
+                mem,
+                wide_integers: dsdt.revision() >= 2,
+                depth: 0,
+                load_errors: 0,
+            };
+            ns.push(Node {
+                name: ROOT_NAME,
+                parent: ROOT,
+                kind: NodeKind::Scope,
+            })?;
+            for name in PREDEFINED_SCOPES {
+                ns.add(ROOT, name, NodeKind::Scope)?;
+            }
+
+            ns.load_table(dsdt);
+            let mut iter = acpi.iter();
+            while let Some(header) = iter.next() {
+                if header.signature == SSDT_SIGNATURE && header.is_valid() {
+                    ns.load_table(header);
+                }
+            }
+            Ok(ns)
+        }
+    }
+
+    unsafe fn load_table(&mut self, header: &ACPISDTHeader) {
+        unsafe {
+            // A corrupt length shorter than the header would wrap around.
+            let Some(aml_len) =
+                (header.length() as usize).checked_sub(core::mem::size_of::<ACPISDTHeader>())
+            else {
+                self.load_errors += 1;
+                return;
+            };
+            let aml = core::slice::from_raw_parts(
+                (header as *const ACPISDTHeader).add(1) as *const u8,
+                aml_len,
+            );
+            if self.execute_table(aml).is_err() {
+                self.load_errors += 1;
+            }
+        }
+    }
+
+    /// The number of objects in the namespace.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        if id >= self.count {
+            return None;
+        }
+        unsafe { self.nodes.get(id).ok() }
+    }
+
+    pub(super) fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        if id >= self.count {
+            return None;
+        }
+        unsafe { self.nodes.get_mut(id).ok() }
+    }
+
+    pub fn parent(&self, id: NodeId) -> NodeId {
+        self.node(id).map_or(ROOT, |node| node.parent)
+    }
+
+    pub fn child(&self, parent: NodeId, name: [u8; 4]) -> Option<NodeId> {
+        self.children(parent)
+            .find(|id| self.node(*id).is_some_and(|node| node.name == name))
+    }
+
+    /// The children of a node, in the order they were created.
+    pub fn children(&self, parent: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        (1..self.count).filter(move |id| self.node(*id).is_some_and(|node| node.parent == parent))
+    }
+
+    fn push(&mut self, node: Node) -> Result<NodeId, AMLError> {
+        if self.count >= MAX_NODES {
+            return Err(AMLError::NamespaceFull);
+        }
+        unsafe {
+            self.nodes
+                .set(self.count, node)
+                .map_err(|_| AMLError::NamespaceFull)?
+        };
+        self.count += 1;
+        Ok(self.count - 1)
+    }
+
+    /// Adds a named object, or replaces an existing object with the same name.
+    /// `Scope` never replaces an object, since it only opens an existing one.
+    pub fn add(
+        &mut self,
+        parent: NodeId,
+        name: [u8; 4],
+        kind: NodeKind,
+    ) -> Result<NodeId, AMLError> {
+        if let Some(id) = self.child(parent, name) {
+            if !matches!(kind, NodeKind::Scope)
+                && let Some(node) = self.node_mut(id)
+            {
+                node.kind = kind;
+            }
+            return Ok(id);
+        }
+        self.push(Node { name, parent, kind })
+    }
+
+    /// Resolves a name relative to `scope`. A single name segment without
+    /// prefixes is also searched for in the parent scopes.
+    pub fn resolve(&self, scope: NodeId, name: &NameString) -> Option<NodeId> {
+        if !name.root && name.parents == 0 && name.seg_count() == 1 {
+            let seg = name.seg(0)?;
+            let mut scope = scope;
+            loop {
+                if let Some(id) = self.child(scope, seg) {
+                    return Some(id);
+                }
+                if scope == ROOT {
+                    return None;
+                }
+                scope = self.parent(scope);
+            }
+        }
+        let mut node = self.start_of(scope, name);
+        for i in 0..name.seg_count() {
+            node = self.child(node, name.seg(i)?)?;
+        }
+        Some(node)
+    }
+
+    /// Resolves all but the last segment of a name, for creating the object
+    /// it names. Returns the parent and the last segment.
+    pub fn resolve_parent(&self, scope: NodeId, name: &NameString) -> Option<(NodeId, [u8; 4])> {
+        let count = name.seg_count();
+        let last = name.seg(count.checked_sub(1)?)?;
+        let mut node = self.start_of(scope, name);
+        for i in 0..count - 1 {
+            node = self.child(node, name.seg(i)?)?;
+        }
+        Some((node, last))
+    }
+
+    fn start_of(&self, scope: NodeId, name: &NameString) -> NodeId {
+        if name.root {
+            return ROOT;
+        }
+        let mut node = scope;
+        for _ in 0..name.parents {
+            node = self.parent(node);
+        }
+        node
+    }
+
+    /// Finds an object by an absolute path like `\_SB.PCI0`. Segments shorter
+    /// than 4 characters are padded with underscores.
+    pub fn find(&self, path: &[u8]) -> Option<NodeId> {
+        let path = path.strip_prefix(b"\\").unwrap_or(path);
+        let mut node = ROOT;
+        if path.is_empty() {
+            return Some(node);
+        }
+        for part in path.split(|c| *c == b'.') {
+            let mut seg = [b'_'; 4];
+            for (dst, src) in seg.iter_mut().zip(part) {
+                *dst = *src;
+            }
+            node = self.child(node, seg)?;
+        }
+        Some(node)
+    }
+}
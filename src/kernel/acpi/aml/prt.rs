@@ -0,0 +1,76 @@
+// PCI interrupt routing
+//
+// The `_PRT` of a PCI bus is a package of entries, one per device and
+// interrupt pin. Each maps the pin either to a global system interrupt, or to
+// an index into the resources of a link device.
+
+use crate::kernel::acpi::aml::{
+    AMLError,
+    namespace::{Namespace, NodeId},
+    value::{AMLValue, Package},
+};
+
+const PRT_NAME: [u8; 4] = *b"_PRT";
+
+#[derive(Clone, Copy)]
+pub struct PRTEntry {
+    /// The device in bits 31-16. The function in bits 15-0 is always 0xFFFF,
+    /// since entries apply to all functions of a device.
+    pub address: u32,
+    /// 0 to 3 for INTA# to INTD#.
+    pub pin: u8,
+    /// The link device the pin is connected to, if any.
+    pub source: Option<NodeId>,
+    /// The global system interrupt if there is no link device, otherwise the
+    /// index of the interrupt in the resources of the link device.
+    pub source_index: u32,
+}
+
+impl PRTEntry {
+    pub fn device(&self) -> u8 {
+        (self.address >> 16) as u8
+    }
+}
+
+impl<'a> Namespace<'a> {
+    /// Evaluates the `_PRT` of a PCI bus. Returns `None` if the bus has no
+    /// routing table.
+    pub unsafe fn routing_table(&mut self, bus: NodeId) -> Result<Option<Package>, AMLError> {
+        unsafe {
+            match self.evaluate_child(bus, PRT_NAME)? {
+                Some(value) => Ok(Some(value.as_package().ok_or(AMLError::TypeMismatch)?)),
+                None => Ok(None),
+            }
+        }
+    }
+
+    /// Decodes entry `index` of a routing table.
+    pub unsafe fn routing_entry(
+        &mut self,
+        table: Package,
+        index: usize,
+    ) -> Result<PRTEntry, AMLError> {
+        unsafe {
+            let entry = table
+                .get(index)
+                .and_then(|entry| entry.as_package())
+                .ok_or(AMLError::TypeMismatch)?;
+            let field = |i| entry.get(i).ok_or(AMLError::OutOfBounds);
+            let address = field(0)?;
+            let pin = field(1)?;
+            let source = field(2)?;
+            let source_index = field(3)?;
+            let source = match self.resolve_value(source) {
+                AMLValue::Reference(node) => Some(node),
+                AMLValue::Integer(_) => None,
+                _ => return Err(AMLError::NotFound),
+            };
+            Ok(PRTEntry {
+                address: self.integer_of(address)? as u32,
+                pin: self.integer_of(pin)? as u8,
+                source,
+                source_index: self.integer_of(source_index)? as u32,
+            })
+        }
+    }
+}
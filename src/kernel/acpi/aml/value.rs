@@ -0,0 +1,121 @@
+// AML data objects
+//
+// Strings point into the AML byte code. Buffers and packages are allocated
+// from the kernel heap when they are created, so they can be modified, and are
+// shared rather than copied when stored.
+
+use crate::kernel::acpi::aml::namespace::{NameString, NodeId};
+
+#[derive(Clone, Copy)]
+pub enum AMLValue {
+    Uninitialized,
+    Integer(u64),
+    /// A string, without the null terminator.
+    String(&'static [u8]),
+    Buffer(Buffer),
+    Package(Package),
+    /// A reference to a named object, e.g. a link device in a `_PRT` entry.
+    Reference(NodeId),
+    /// A name in a package that couldn't be resolved when the package was
+    /// created, because the object is declared further on.
+    Unresolved {
+        scope: NodeId,
+        name: NameString,
+    },
+    /// A reference to an element of a package or a byte of a buffer.
+    Index(IndexRef),
+}
+
+#[derive(Clone, Copy)]
+pub enum IndexRef {
+    Package(Package, usize),
+    Buffer(Buffer, usize),
+}
+
+#[derive(Clone, Copy)]
+pub struct Buffer {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Buffer {
+    /// Wraps `len` bytes of heap memory at `ptr`.
+    pub unsafe fn new(ptr: *mut u8, len: usize) -> Self {
+        Self { ptr, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn bytes(&self) -> &'static [u8] {
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn get(&self, index: usize) -> Option<u8> {
+        self.bytes().get(index).copied()
+    }
+
+    pub fn set(&self, index: usize, value: u8) -> bool {
+        if index >= self.len {
+            return false;
+        }
+        unsafe { self.ptr.add(index).write(value) };
+        true
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Package {
+    ptr: *mut AMLValue,
+    len: usize,
+}
+
+impl Package {
+    /// Wraps `len` initialised elements of heap memory at `ptr`.
+    pub unsafe fn new(ptr: *mut AMLValue, len: usize) -> Self {
+        Self { ptr, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get(&self, index: usize) -> Option<AMLValue> {
+        if index >= self.len {
+            return None;
+        }
+        Some(unsafe { self.ptr.add(index).read() })
+    }
+
+    pub fn set(&self, index: usize, value: AMLValue) -> bool {
+        if index >= self.len {
+            return false;
+        }
+        unsafe { self.ptr.add(index).write(value) };
+        true
+    }
+}
+
+impl AMLValue {
+    pub fn as_integer(&self) -> Option<u64> {
+        match self {
+            AMLValue::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_package(&self) -> Option<Package> {
+        match self {
+            AMLValue::Package(p) => Some(*p),
+            _ => None,
+        }
+    }
+
+    pub fn as_buffer(&self) -> Option<Buffer> {
+        match self {
+            AMLValue::Buffer(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
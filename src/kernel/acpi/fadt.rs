@@ -0,0 +1,166 @@
+// Fixed ACPI Description Table
+//
+// The FADT (signature "FACP") holds the fixed hardware registers used for
+// power management, the address of the DSDT and a few boot flags. The table
+// grew with every ACPI revision, so fields past the ACPI 1.0 layout are only
+// read when the table is long enough to contain them.
+
+use core::mem::{offset_of, size_of};
+
+use crate::kernel::acpi::acpi::{ACPI, ACPISDTHeader, GenericAddress};
+
+const SIGNATURE: [u8; 4] = [b'F', b'A', b'C', b'P'];
+
+/// The length of an ACPI 1.0 FADT, up to and including `flags`.
+const FADT_V1_LENGTH: usize = 116;
+
+/// IA-PC boot architecture flag of a motherboard with an 8042 controller.
+const BOOT_ARCH_8042: u16 = 1 << 1;
+
+/// Fixed feature flags.
+pub const FLAG_RESET_REG_SUP: u32 = 1 << 10;
+
+#[repr(C, packed)]
+pub struct FADT {
+    header: ACPISDTHeader,
+    firmware_ctrl: u32,
+    dsdt: u32,
+    reserved: u8,
+    preferred_pm_profile: u8,
+    sci_int: u16,
+    smi_cmd: u32,
+    acpi_enable: u8,
+    acpi_disable: u8,
+    s4bios_req: u8,
+    pstate_cnt: u8,
+    pm1a_evt_blk: u32,
+    pm1b_evt_blk: u32,
+    pm1a_cnt_blk: u32,
+    pm1b_cnt_blk: u32,
+    pm2_cnt_blk: u32,
+    pm_tmr_blk: u32,
+    gpe0_blk: u32,
+    gpe1_blk: u32,
+    pm1_evt_len: u8,
+    pm1_cnt_len: u8,
+    pm2_cnt_len: u8,
+    pm_tmr_len: u8,
+    gpe0_blk_len: u8,
+    gpe1_blk_len: u8,
+    gpe1_base: u8,
+    cst_cnt: u8,
+    p_lvl2_lat: u16,
+    p_lvl3_lat: u16,
+    flush_size: u16,
+    flush_stride: u16,
+    duty_offset: u8,
+    duty_width: u8,
+    day_alrm: u8,
+    mon_alrm: u8,
+    century: u8,
+    iapc_boot_arch: u16,
+    reserved2: u8,
+    flags: u32,
+    // ACPI 2.0 and later
+    reset_reg: GenericAddress,
+    reset_value: u8,
+    arm_boot_arch: u16,
+    minor_version: u8,
+    x_firmware_ctrl: u64,
+    x_dsdt: u64,
+    x_pm1a_evt_blk: GenericAddress,
+    x_pm1b_evt_blk: GenericAddress,
+    x_pm1a_cnt_blk: GenericAddress,
+    x_pm1b_cnt_blk: GenericAddress,
+    x_pm2_cnt_blk: GenericAddress,
+    x_pm_tmr_blk: GenericAddress,
+    x_gpe0_blk: GenericAddress,
+    x_gpe1_blk: GenericAddress,
+}
+
+impl FADT {
+    pub unsafe fn from_acpi(acpi: &ACPI) -> Option<&FADT> {
+        unsafe {
+            let header = acpi.find(&SIGNATURE)?;
+            if (header.length() as usize) < FADT_V1_LENGTH {
+                return None;
+            }
+            Some(&*(header as *const ACPISDTHeader).cast())
+        }
+    }
+
+    /// Whether the table is long enough to contain a field of type `T` at
+    /// `offset`.
+    fn has<T>(&self, offset: usize) -> bool {
+        self.header.length() as usize >= offset + size_of::<T>()
+    }
+
+    /// The physical address of the DSDT. The 64 bit address is used when
+    /// present and addressable.
+    pub fn dsdt_addr(&self) -> u32 {
+        if self.has::<u64>(offset_of!(FADT, x_dsdt))
+            && self.x_dsdt != 0
+            && self.x_dsdt <= u32::MAX as u64
+        {
+            return self.x_dsdt as u32;
+        }
+        self.dsdt
+    }
+
+    /// The ISA IRQ the SCI interrupt is wired to.
+    pub fn sci_irq(&self) -> u16 {
+        self.sci_int
+    }
+
+    /// The port and value to write to in order to switch to ACPI mode. The
+    /// port is zero when the system is always in ACPI mode.
+    pub fn smi_cmd(&self) -> (u32, u8) {
+        (self.smi_cmd, self.acpi_enable)
+    }
+
+    /// IO ports of the PM1a and PM1b control blocks. PM1b is optional and zero
+    /// when not present.
+    pub fn pm1_cnt_blk(&self) -> (u32, u32) {
+        (self.pm1a_cnt_blk, self.pm1b_cnt_blk)
+    }
+
+    /// The IO port of the 24 or 32 bit ACPI power management timer.
+    pub fn pm_tmr_blk(&self) -> u32 {
+        self.pm_tmr_blk
+    }
+
+    /// The index of the RTC CMOS register holding the century, zero when the
+    /// RTC has none.
+    pub fn century_reg(&self) -> u8 {
+        self.century
+    }
+
+    /// IA-PC boot architecture flags. Only valid from ACPI 2.0 onwards,
+    /// earlier tables report zero.
+    pub fn boot_arch(&self) -> u16 {
+        if self.header.revision() < 2 {
+            return 0;
+        }
+        self.iapc_boot_arch
+    }
+
+    /// Whether the machine has an 8042 PS/2 controller. The IA-PC boot
+    /// architecture flags only say so from ACPI 2.0 onwards, earlier tables
+    /// are assumed to have one.
+    pub fn has_8042(&self) -> bool {
+        self.header.revision() < 2 || self.boot_arch() & BOOT_ARCH_8042 != 0
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// The register and value to write to it to reset the system, if
+    /// supported.
+    pub fn reset_reg(&self) -> Option<(GenericAddress, u8)> {
+        if self.flags & FLAG_RESET_REG_SUP == 0 || !self.has::<u8>(offset_of!(FADT, reset_value)) {
+            return None;
+        }
+        Some((self.reset_reg, self.reset_value))
+    }
+}
@@ -0,0 +1,45 @@
+// High Precision Event Timer Description Table
+//
+// The HPET table (signature "HPET") gives the address of the HPET registers
+// and some capabilities of the timer block.
+
+use crate::kernel::acpi::acpi::{ACPI, ACPISDTHeader, GenericAddress};
+
+const SIGNATURE: [u8; 4] = [b'H', b'P', b'E', b'T'];
+
+#[repr(C, packed)]
+pub struct HPET {
+    header: ACPISDTHeader,
+    event_timer_block_id: u32,
+    base_address: GenericAddress,
+    hpet_number: u8,
+    minimum_tick: u16,
+    page_protection: u8,
+}
+
+impl HPET {
+    pub unsafe fn from_acpi(acpi: &ACPI) -> Option<&HPET> {
+        unsafe { acpi.find_table(&SIGNATURE) }
+    }
+
+    /// The address of the memory mapped timer registers.
+    pub fn base_address(&self) -> GenericAddress {
+        self.base_address
+    }
+
+    /// The number of comparators is stored in bits 12-8 of the block id,
+    /// minus one.
+    pub fn comparators(&self) -> u8 {
+        ((self.event_timer_block_id >> 8) & 0x1F) as u8 + 1
+    }
+
+    pub fn hpet_number(&self) -> u8 {
+        self.hpet_number
+    }
+
+    /// The minimum number of ticks a periodic timer can be set to without
+    /// losing interrupts.
+    pub fn minimum_tick(&self) -> u16 {
+        self.minimum_tick
+    }
+}
@@ -1,6 +1,5 @@
 use crate::kernel::acpi::acpi::{ACPI, ACPISDTHeader};
 
-
 pub struct SDTIterator<'a> {
     idx: usize,
     acpi: &'a ACPI,
@@ -20,10 +19,20 @@ impl<'a> SDTIterator<'a> {
             return None;
         }
         unsafe {
-            let addr = self.acpi.sdt_ptrs.add(self.idx);
-            let header = &**addr;
+            let entry = self.acpi.sdt_ptrs.add(self.idx * self.acpi.sdt_entry_size);
             self.idx += 1;
-            Some(header)
+            // Root table entries aren't necessarily aligned.
+            let addr = if self.acpi.sdt_entry_size == 8 {
+                let addr = (entry as *const u64).read_unaligned();
+                if addr > u32::MAX as u64 {
+                    // Not addressable without paging.
+                    return self.next();
+                }
+                addr as usize
+            } else {
+                (entry as *const u32).read_unaligned() as usize
+            };
+            Some(&*(addr as *const ACPISDTHeader))
         }
     }
 }
@@ -0,0 +1,192 @@
+// Multiple APIC Description Table
+//
+// The MADT (signature "APIC") describes the interrupt controllers of the
+// system: one local APIC per processor, the IO-APICs and how legacy ISA IRQs
+// map onto the IO-APIC inputs (global system interrupts).
+
+use crate::kernel::acpi::acpi::{ACPI, ACPISDTHeader};
+
+const SIGNATURE: [u8; 4] = [b'A', b'P', b'I', b'C'];
+
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+const ENTRY_NMI_SOURCE: u8 = 3;
+const ENTRY_LOCAL_APIC_NMI: u8 = 4;
+const ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+
+/// Set in the local APIC flags when the processor can be used.
+pub const LOCAL_APIC_ENABLED: u32 = 1 << 0;
+/// Set in the local APIC flags when the processor can be enabled at runtime.
+pub const LOCAL_APIC_ONLINE_CAPABLE: u32 = 1 << 1;
+
+#[repr(C, packed)]
+pub struct MADT {
+    header: ACPISDTHeader,
+    local_apic_addr: u32,
+    flags: u32,
+}
+
+#[repr(C, packed)]
+struct EntryHeader {
+    typ: u8,
+    length: u8,
+}
+
+#[derive(Clone, Copy)]
+pub enum MADTEntry {
+    LocalAPIC {
+        processor_id: u8,
+        apic_id: u8,
+        flags: u32,
+    },
+    IOAPIC {
+        id: u8,
+        addr: u32,
+        gsi_base: u32,
+    },
+    InterruptSourceOverride {
+        bus: u8,
+        source: u8,
+        gsi: u32,
+        flags: u16,
+    },
+    NMISource {
+        flags: u16,
+        gsi: u32,
+    },
+    LocalAPICNMI {
+        processor_id: u8,
+        flags: u16,
+        lint: u8,
+    },
+    LocalAPICAddressOverride {
+        addr: u64,
+    },
+    Unknown {
+        typ: u8,
+    },
+}
+
+impl MADT {
+    pub unsafe fn from_acpi(acpi: &ACPI) -> Option<&MADT> {
+        unsafe { acpi.find_table(&SIGNATURE) }
+    }
+
+    /// The physical address of the local APIC, taking a 64 bit address
+    /// override into account when it fits in 32 bits.
+    pub unsafe fn local_apic_addr(&self) -> u32 {
+        unsafe {
+            let mut iter = self.iter();
+            while let Some(entry) = iter.next() {
+                if let MADTEntry::LocalAPICAddressOverride { addr } = entry
+                    && addr <= u32::MAX as u64
+                {
+                    return addr as u32;
+                }
+            }
+        }
+        self.local_apic_addr
+    }
+
+    /// Whether the system also has dual 8259 PICs that need to be masked
+    /// before the IO-APIC is used.
+    pub fn has_legacy_pics(&self) -> bool {
+        self.flags & 0x1 != 0
+    }
+
+    pub unsafe fn iter(&'_ self) -> MADTIterator<'_> {
+        unsafe { MADTIterator::new(self) }
+    }
+
+    /// Finds the global system interrupt and flags an ISA IRQ is connected to.
+    /// Without an override, ISA IRQs are identity mapped and use the bus'
+    /// default polarity and trigger mode.
+    pub unsafe fn isa_irq_to_gsi(&self, irq: u8) -> (u32, u16) {
+        unsafe {
+            let mut iter = self.iter();
+            while let Some(entry) = iter.next() {
+                if let MADTEntry::InterruptSourceOverride {
+                    bus: 0,
+                    source,
+                    gsi,
+                    flags,
+                } = entry
+                    && source == irq
+                {
+                    return (gsi, flags);
+                }
+            }
+        }
+        (irq as u32, 0)
+    }
+}
+
+pub struct MADTIterator<'a> {
+    offset: usize,
+    madt: &'a MADT,
+}
+
+impl<'a> MADTIterator<'a> {
+    pub unsafe fn new(madt: &'a MADT) -> Self {
+        Self {
+            offset: core::mem::size_of::<MADT>(),
+            madt,
+        }
+    }
+
+    pub unsafe fn next(&mut self) -> Option<MADTEntry> {
+        let length = self.madt.header.length() as usize;
+        if self.offset + core::mem::size_of::<EntryHeader>() > length {
+            return None;
+        }
+        unsafe {
+            let entry = (self.madt as *const MADT as *const u8).add(self.offset);
+            let header = &*(entry as *const EntryHeader);
+            if header.length < 2 || self.offset + header.length as usize > length {
+                return None;
+            }
+            self.offset += header.length as usize;
+            let body = entry.add(core::mem::size_of::<EntryHeader>());
+            Some(match header.typ {
+                ENTRY_LOCAL_APIC => MADTEntry::LocalAPIC {
+                    processor_id: *body,
+                    apic_id: *body.add(1),
+                    flags: read_u32(body.add(2)),
+                },
+                ENTRY_IO_APIC => MADTEntry::IOAPIC {
+                    id: *body,
+                    addr: read_u32(body.add(2)),
+                    gsi_base: read_u32(body.add(6)),
+                },
+                ENTRY_INTERRUPT_SOURCE_OVERRIDE => MADTEntry::InterruptSourceOverride {
+                    bus: *body,
+                    source: *body.add(1),
+                    gsi: read_u32(body.add(2)),
+                    flags: read_u16(body.add(6)),
+                },
+                ENTRY_NMI_SOURCE => MADTEntry::NMISource {
+                    flags: read_u16(body),
+                    gsi: read_u32(body.add(2)),
+                },
+                ENTRY_LOCAL_APIC_NMI => MADTEntry::LocalAPICNMI {
+                    processor_id: *body,
+                    flags: read_u16(body.add(1)),
+                    lint: *body.add(3),
+                },
+                ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE => MADTEntry::LocalAPICAddressOverride {
+                    addr: (body.add(2) as *const u64).read_unaligned(),
+                },
+                typ => MADTEntry::Unknown { typ },
+            })
+        }
+    }
+}
+
+unsafe fn read_u16(addr: *const u8) -> u16 {
+    unsafe { (addr as *const u16).read_unaligned() }
+}
+
+unsafe fn read_u32(addr: *const u8) -> u32 {
+    unsafe { (addr as *const u32).read_unaligned() }
+}
@@ -0,0 +1,57 @@
+// PCI Express Memory Mapped Configuration Table
+//
+// The MCFG (signature "MCFG") lists the memory regions through which the
+// configuration space of PCI segment groups can be accessed (ECAM).
+
+use core::mem::size_of;
+
+use crate::kernel::acpi::acpi::{ACPI, ACPISDTHeader};
+
+const SIGNATURE: [u8; 4] = [b'M', b'C', b'F', b'G'];
+
+#[repr(C, packed)]
+pub struct MCFG {
+    header: ACPISDTHeader,
+    reserved: [u8; 8],
+}
+
+/// A configuration space base address allocation, covering the buses
+/// `start_bus..=end_bus` of a PCI segment group.
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct MCFGEntry {
+    pub base_addr: u64,
+    pub segment: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+    reserved: u32,
+}
+
+impl MCFG {
+    pub unsafe fn from_acpi(acpi: &ACPI) -> Option<&MCFG> {
+        unsafe { acpi.find_table(&SIGNATURE) }
+    }
+
+    pub fn len(&self) -> usize {
+        (self.header.length() as usize).saturating_sub(size_of::<MCFG>()) / size_of::<MCFGEntry>()
+    }
+
+    pub fn entry(&self, index: usize) -> Option<MCFGEntry> {
+        if index >= self.len() {
+            return None;
+        }
+        unsafe {
+            let entries = (self as *const MCFG).add(1) as *const MCFGEntry;
+            Some(entries.add(index).read_unaligned())
+        }
+    }
+
+    /// Finds the allocation covering a bus of a segment group.
+    pub fn entry_for(&self, segment: u16, bus: u8) -> Option<MCFGEntry> {
+        (0..self.len())
+            .filter_map(|index| self.entry(index))
+            .find(|entry| {
+                entry.segment == segment && entry.start_bus <= bus && bus <= entry.end_bus
+            })
+    }
+}
@@ -1,2 +1,8 @@
 pub mod acpi;
-pub mod iter;
\ No newline at end of file
+pub mod aml;
+pub mod fadt;
+pub mod hpet;
+pub mod iter;
+pub mod madt;
+pub mod mcfg;
+pub mod power;
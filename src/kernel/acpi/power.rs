@@ -0,0 +1,231 @@
+// ACPI power management
+//
+// Soft power-off puts the system in sleep state S5: the SLP_TYP values for S5
+// are found in the `\_S5` package of the DSDT and written to the PM1 control
+// registers together with SLP_EN. Reboot uses the FADT reset register, falling
+// back to the keyboard controller and finally a triple fault.
+
+use core::arch::asm;
+
+use crate::kernel::{
+    acpi::{
+        acpi::{ACPI, ACPISDTHeader, GenericAddress},
+        fadt::FADT,
+    },
+    ports::{Port, io_delay_us, read_port_byte, read_port_word, write_port_byte, write_port_word},
+};
+
+const DSDT_SIGNATURE: [u8; 4] = [b'D', b'S', b'D', b'T'];
+const S5_NAME: [u8; 4] = [b'_', b'S', b'5', b'_'];
+
+// AML opcodes needed to decode the `\_S5` package.
+const AML_ZERO_OP: u8 = 0x00;
+const AML_ONE_OP: u8 = 0x01;
+const AML_NAME_OP: u8 = 0x08;
+const AML_BYTE_PREFIX: u8 = 0x0A;
+const AML_PACKAGE_OP: u8 = 0x12;
+const AML_ROOT_CHAR: u8 = b'\\';
+
+/* PM1 control register
+ * Bit 0: SCI_EN, set when the system is in ACPI mode
+ * Bits 12-10: SLP_TYP
+ * Bit 13: SLP_EN
+ */
+const PM1_SCI_EN: u16 = 1 << 0;
+const PM1_SLP_TYP_SHIFT: u16 = 10;
+const PM1_SLP_EN: u16 = 1 << 13;
+
+const ACPI_ENABLE_TIMEOUT_US: u32 = 300_000;
+const SHUTDOWN_TIMEOUT_US: u32 = 1_000_000;
+const POLL_INTERVAL_US: u32 = 1_000;
+
+// The keyboard controller pulses the CPU reset line on this command.
+const KBC_INPUT_BUFFER_FULL: u8 = 1 << 1;
+const KBC_PULSE_RESET: u8 = 0xFE;
+const KBC_TIMEOUT_US: u32 = 100_000;
+
+pub enum PowerError {
+    NoACPI,
+    NoFADT,
+    NoDSDT,
+    NoS5,
+    /// The system didn't switch to ACPI mode.
+    ACPIModeTimeout,
+    /// The sleep registers were written, but the system is still running.
+    NoResponse,
+}
+
+/// Powers the machine off. Only returns when that failed, with the reason.
+pub unsafe fn shutdown() -> PowerError {
+    unsafe {
+        let Some(acpi) = ACPI::load() else {
+            return PowerError::NoACPI;
+        };
+        let Some(fadt) = FADT::from_acpi(&acpi) else {
+            return PowerError::NoFADT;
+        };
+        let (slp_typ_a, slp_typ_b) = match find_s5(fadt) {
+            Ok(values) => values,
+            Err(err) => return err,
+        };
+        if let Err(err) = enable_acpi_mode(fadt) {
+            return err;
+        }
+
+        let (pm1a_cnt, pm1b_cnt) = fadt.pm1_cnt_blk();
+        asm!("cli");
+        write_sleep_type(pm1a_cnt, slp_typ_a);
+        if pm1b_cnt != 0 {
+            write_sleep_type(pm1b_cnt, slp_typ_b);
+        }
+
+        // Some hardware takes a moment to cut the power.
+        io_delay_us(SHUTDOWN_TIMEOUT_US);
+        asm!("sti");
+        PowerError::NoResponse
+    }
+}
+
+/// Resets the machine, trying the FADT reset register, then the keyboard
+/// controller and finally a triple fault.
+pub unsafe fn reboot() -> ! {
+    unsafe {
+        asm!("cli");
+        if let Some(acpi) = ACPI::load()
+            && let Some(fadt) = FADT::from_acpi(&acpi)
+            && let Some((reg, value)) = fadt.reset_reg()
+        {
+            write_reset_reg(reg, value);
+            io_delay_us(POLL_INTERVAL_US);
+        }
+
+        let mut waited = 0;
+        while read_port_byte(Port::PS2StatusCmdReg.into()) & KBC_INPUT_BUFFER_FULL != 0
+            && waited < KBC_TIMEOUT_US
+        {
+            io_delay_us(POLL_INTERVAL_US);
+            waited += POLL_INTERVAL_US;
+        }
+        write_port_byte(Port::PS2StatusCmdReg.into(), KBC_PULSE_RESET);
+        io_delay_us(POLL_INTERVAL_US);
+
+        triple_fault()
+    }
+}
+
+/// Loads an empty IDT and raises an interrupt. The CPU can't deliver it, nor
+/// the resulting double fault, and resets.
+unsafe fn triple_fault() -> ! {
+    let idt_reg: [u16; 3] = [0; 3];
+    unsafe {
+        asm!("lidt [{}]", "int3", in(reg) &raw const idt_reg);
+    }
+    loop {}
+}
+
+unsafe fn write_reset_reg(reg: GenericAddress, value: u8) {
+    match reg.address_space {
+        GenericAddress::SYSTEM_IO => write_port_byte(reg.address as u16, value),
+        GenericAddress::SYSTEM_MEMORY if reg.address <= u32::MAX as u64 => unsafe {
+            (reg.address as usize as *mut u8).write_volatile(value)
+        },
+        _ => {}
+    }
+}
+
+fn write_sleep_type(pm1_cnt: u32, slp_typ: u8) {
+    let port = pm1_cnt as u16;
+    let value = read_port_word(port) & !(0b111 << PM1_SLP_TYP_SHIFT);
+    write_port_word(
+        port,
+        value | ((slp_typ as u16) << PM1_SLP_TYP_SHIFT) | PM1_SLP_EN,
+    );
+}
+
+/// Switches from legacy to ACPI mode through the SMI command port, unless the
+/// system is already in ACPI mode.
+unsafe fn enable_acpi_mode(fadt: &FADT) -> Result<(), PowerError> {
+    let (pm1a_cnt, _) = fadt.pm1_cnt_blk();
+    let (smi_cmd, acpi_enable) = fadt.smi_cmd();
+    if read_port_word(pm1a_cnt as u16) & PM1_SCI_EN != 0 || smi_cmd == 0 || acpi_enable == 0 {
+        return Ok(());
+    }
+    write_port_byte(smi_cmd as u16, acpi_enable);
+    let mut waited = 0;
+    while read_port_word(pm1a_cnt as u16) & PM1_SCI_EN == 0 {
+        if waited >= ACPI_ENABLE_TIMEOUT_US {
+            return Err(PowerError::ACPIModeTimeout);
+        }
+        io_delay_us(POLL_INTERVAL_US);
+        waited += POLL_INTERVAL_US;
+    }
+    Ok(())
+}
+
+/// Finds the SLP_TYPa and SLP_TYPb values of sleep state S5. Rather than
+/// interpreting the DSDT, this looks for the encoding of
+/// `Name (\_S5, Package () { a, b, ... })`.
+unsafe fn find_s5(fadt: &FADT) -> Result<(u8, u8), PowerError> {
+    unsafe {
+        let dsdt = &*(fadt.dsdt_addr() as *const ACPISDTHeader);
+        if dsdt.signature != DSDT_SIGNATURE || !dsdt.is_valid() {
+            return Err(PowerError::NoDSDT);
+        }
+        // A corrupt length shorter than the header would wrap around.
+        let Some(aml_len) =
+            (dsdt.length() as usize).checked_sub(core::mem::size_of::<ACPISDTHeader>())
+        else {
+            return Err(PowerError::NoDSDT);
+        };
+        let aml = core::slice::from_raw_parts(
+            (dsdt as *const ACPISDTHeader).add(1) as *const u8,
+            aml_len,
+        );
+
+        // Indexing is avoided, so no panic paths are pulled in.
+        for i in 1..aml.len() {
+            if aml.get(i..i + S5_NAME.len()) != Some(&S5_NAME[..]) {
+                continue;
+            }
+            let is_name = matches!(
+                (aml.get(i - 1), i.checked_sub(2).and_then(|j| aml.get(j))),
+                (Some(&AML_NAME_OP), _) | (Some(&AML_ROOT_CHAR), Some(&AML_NAME_OP))
+            );
+            if is_name
+                && let Some(package) = aml.get(i + S5_NAME.len()..)
+                && let Some(values) = parse_s5_package(package)
+            {
+                return Ok(values);
+            }
+        }
+        Err(PowerError::NoS5)
+    }
+}
+
+/// Reads the first two integers of the package following the `\_S5` name.
+fn parse_s5_package(aml: &[u8]) -> Option<(u8, u8)> {
+    if *aml.first()? != AML_PACKAGE_OP {
+        return None;
+    }
+    // Bits 7-6 of the lead byte are the number of bytes following it.
+    let pkg_length_len = 1 + (*aml.get(1)? >> 6) as usize;
+    // Skip the opcode, the package length and the number of elements.
+    let mut pos = 1 + pkg_length_len + 1;
+    let slp_typ_a = parse_integer(aml, &mut pos)?;
+    let slp_typ_b = parse_integer(aml, &mut pos)?;
+    Some((slp_typ_a, slp_typ_b))
+}
+
+fn parse_integer(aml: &[u8], pos: &mut usize) -> Option<u8> {
+    let value = match *aml.get(*pos)? {
+        AML_ZERO_OP => 0,
+        AML_ONE_OP => 1,
+        AML_BYTE_PREFIX => {
+            *pos += 1;
+            *aml.get(*pos)?
+        }
+        _ => return None,
+    };
+    *pos += 1;
+    Some(value)
+}
@@ -0,0 +1,386 @@
+// Advanced Programmable Interrupt Controller
+//
+// Each processor has a local APIC that receives interrupts and has its own
+// timer. IO-APICs route device interrupts (global system interrupts, GSIs) to
+// local APICs. Both are memory mapped at the addresses given by the MADT.
+//
+// The local APIC timer counts down at the bus clock divided by a configurable
+// factor. The bus clock is unknown, so the timer is calibrated against PIT
+// channel 2 before it's started.
+
+use core::{
+    arch::x86::__cpuid,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+
+use crate::kernel::{
+    acpi::madt::{MADT, MADTEntry},
+    cpu,
+    interrupt_handlers::{IRQ_BASE_VECTOR, apic_timer::apic_timer_ticks},
+    pic::PIC,
+    time::pit,
+};
+
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+/// The IRQ line the local APIC timer is delivered on.
+pub const LAPIC_TIMER_IRQ: u8 = 24;
+
+const CPUID_FEATURE_APIC: u32 = 1 << 9;
+const SVR_APIC_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+/// Calibration measures over this fraction of a second, i.e. 10 ms.
+const TIMER_CALIBRATION_DIVISOR: u32 = 100;
+/// The timer is slowed down to count at most this often, a resolution of
+/// 100 ns, which lets a 32 bit count span about 7 minutes.
+const TIMER_MAX_FREQUENCY: u32 = 10_000_000;
+
+/* Interrupt command register
+ * Bits 7-0: vector
+ * Bits 10-8: delivery mode
+ * Bit 12: delivery status (1 = pending)
+ * Bit 14: level (1 = assert)
+ * Bits 19-18: destination shorthand
+ */
+pub const ICR_INIT: u32 = 0b101 << 8;
+pub const ICR_STARTUP: u32 = 0b110 << 8;
+pub const ICR_LEVEL_ASSERT: u32 = 1 << 14;
+pub const ICR_ALL_EXCLUDING_SELF: u32 = 0b11 << 18;
+const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+
+const ISA_CASCADE_IRQ: u8 = 2;
+const NUM_ISA_IRQS: u8 = 16;
+
+const IOAPIC_REG_VERSION: u32 = 0x01;
+const IOAPIC_REG_REDIRECTION: u32 = 0x10;
+const REDIRECTION_ACTIVE_LOW: u32 = 1 << 13;
+const REDIRECTION_LEVEL_TRIGGERED: u32 = 1 << 15;
+
+/* MPS INTI flags of an interrupt source override
+ * Bits 1-0: polarity (00 bus default, 01 active high, 11 active low)
+ * Bits 3-2: trigger mode (00 bus default, 01 edge, 11 level)
+ */
+const INTI_POLARITY_MASK: u16 = 0b0011;
+const INTI_POLARITY_ACTIVE_LOW: u16 = 0b0011;
+const INTI_TRIGGER_MASK: u16 = 0b1100;
+const INTI_TRIGGER_LEVEL: u16 = 0b1100;
+
+/// Base address of the local APIC. Zero while the legacy PIC is in use.
+static LAPIC_BASE: AtomicUsize = AtomicUsize::new(0);
+/// The rate the bootstrap processor's timer counts at, 0 until it's started.
+static TIMER_FREQUENCY: AtomicU32 = AtomicU32::new(0);
+
+pub enum APICError {
+    NotSupported,
+    NoIOAPIC,
+}
+
+/// Offsets of local APIC registers.
+#[derive(Clone, Copy)]
+pub enum LocalAPICReg {
+    Id = 0x020,
+    EndOfInterrupt = 0x0B0,
+    SpuriousInterruptVector = 0x0F0,
+    InterruptCommandLo = 0x300,
+    InterruptCommandHi = 0x310,
+    LvtTimer = 0x320,
+    TimerInitialCount = 0x380,
+    TimerCurrentCount = 0x390,
+    TimerDivideConfig = 0x3E0,
+}
+
+/// Values of the timer divide configuration register.
+#[derive(Clone, Copy)]
+pub enum TimerDivide {
+    By1 = 0b1011,
+    By2 = 0b0000,
+    By4 = 0b0001,
+    By8 = 0b0010,
+    By16 = 0b0011,
+    By32 = 0b1000,
+    By64 = 0b1001,
+    By128 = 0b1010,
+}
+
+impl TimerDivide {
+    const ALL: [Self; 8] = [
+        Self::By1,
+        Self::By2,
+        Self::By4,
+        Self::By8,
+        Self::By16,
+        Self::By32,
+        Self::By64,
+        Self::By128,
+    ];
+
+    /// The smallest divisor that brings `frequency` down to `max`, or the
+    /// largest one if none does.
+    pub fn slowing_to(frequency: u32, max: u32) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|divide| divide.apply(frequency) <= max)
+            .unwrap_or(Self::By128)
+    }
+
+    /// The rate of a clock running at `frequency` divided by this.
+    pub fn apply(self, frequency: u32) -> u32 {
+        let shift = match self {
+            Self::By1 => 0,
+            Self::By2 => 1,
+            Self::By4 => 2,
+            Self::By8 => 3,
+            Self::By16 => 4,
+            Self::By32 => 5,
+            Self::By64 => 6,
+            Self::By128 => 7,
+        };
+        frequency >> shift
+    }
+}
+
+pub struct LocalAPIC {
+    base: usize,
+}
+
+impl LocalAPIC {
+    /// Returns the local APIC, if the kernel switched to APIC mode.
+    pub fn get() -> Option<Self> {
+        match LAPIC_BASE.load(Ordering::Relaxed) {
+            0 => None,
+            base => Some(Self { base }),
+        }
+    }
+
+    pub fn read(&self, reg: LocalAPICReg) -> u32 {
+        unsafe { ((self.base + reg as usize) as *const u32).read_volatile() }
+    }
+
+    pub fn write(&self, reg: LocalAPICReg, value: u32) {
+        unsafe { ((self.base + reg as usize) as *mut u32).write_volatile(value) }
+    }
+
+    pub fn id(&self) -> u8 {
+        (self.read(LocalAPICReg::Id) >> 24) as u8
+    }
+
+    pub fn send_eoi(&self) {
+        self.write(LocalAPICReg::EndOfInterrupt, 0);
+    }
+
+    /// Sends an inter-processor interrupt to the local APIC with id `dest` and
+    /// waits for it to be delivered. `dest` is ignored when `command` contains
+    /// a destination shorthand.
+    pub fn send_ipi(&self, dest: u8, command: u32) {
+        self.write(LocalAPICReg::InterruptCommandHi, (dest as u32) << 24);
+        self.write(LocalAPICReg::InterruptCommandLo, command);
+        while self.read(LocalAPICReg::InterruptCommandLo) & ICR_DELIVERY_PENDING != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Starts the timer, which fires on `LAPIC_TIMER_IRQ` after counting down
+    /// `initial_count` ticks of the bus clock divided by `divide`.
+    pub fn start_timer(&self, initial_count: u32, divide: TimerDivide, periodic: bool) {
+        let mut lvt = (IRQ_BASE_VECTOR + LAPIC_TIMER_IRQ) as u32;
+        if periodic {
+            lvt |= LVT_TIMER_PERIODIC;
+        }
+        self.write(LocalAPICReg::TimerDivideConfig, divide as u32);
+        self.write(LocalAPICReg::LvtTimer, lvt);
+        self.write(LocalAPICReg::TimerInitialCount, initial_count);
+    }
+
+    pub fn stop_timer(&self) {
+        self.write(LocalAPICReg::LvtTimer, LVT_MASKED);
+        self.write(LocalAPICReg::TimerInitialCount, 0);
+    }
+
+    pub fn timer_current_count(&self) -> u32 {
+        self.read(LocalAPICReg::TimerCurrentCount)
+    }
+
+    /// Measures how many times per second the timer counts down with
+    /// `divide`, against PIT channel 2. The timer is masked meanwhile and
+    /// stopped afterwards. Returns `None` if the PIT doesn't count.
+    pub fn calibrate_timer(&self, divide: TimerDivide) -> Option<u32> {
+        self.write(LocalAPICReg::TimerDivideConfig, divide as u32);
+        self.write(LocalAPICReg::LvtTimer, LVT_MASKED);
+        self.write(LocalAPICReg::TimerInitialCount, u32::MAX);
+        let waited = pit::wait_cycles((pit::BASE_FREQUENCY / TIMER_CALIBRATION_DIVISOR) as u16);
+        let elapsed = u32::MAX - self.timer_current_count();
+        self.stop_timer();
+        waited.then_some(elapsed.saturating_mul(TIMER_CALIBRATION_DIVISOR))
+    }
+
+    fn enable(&self) {
+        self.write(
+            LocalAPICReg::SpuriousInterruptVector,
+            SVR_APIC_ENABLE | SPURIOUS_VECTOR as u32,
+        );
+    }
+}
+
+pub struct IOAPIC {
+    base: usize,
+    gsi_base: u32,
+}
+
+impl IOAPIC {
+    pub fn new(addr: u32, gsi_base: u32) -> Self {
+        Self {
+            base: addr as usize,
+            gsi_base,
+        }
+    }
+
+    /// Registers are accessed indirectly: the register index is written to
+    /// IOREGSEL, after which the register is available in IOWIN.
+    pub fn read(&self, reg: u32) -> u32 {
+        unsafe {
+            (self.base as *mut u32).write_volatile(reg);
+            ((self.base + 0x10) as *const u32).read_volatile()
+        }
+    }
+
+    pub fn write(&self, reg: u32, value: u32) {
+        unsafe {
+            (self.base as *mut u32).write_volatile(reg);
+            ((self.base + 0x10) as *mut u32).write_volatile(value);
+        }
+    }
+
+    /// The number of interrupt inputs of this IO-APIC.
+    pub fn redirection_entries(&self) -> u32 {
+        ((self.read(IOAPIC_REG_VERSION) >> 16) & 0xFF) + 1
+    }
+
+    pub fn handles(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi < self.gsi_base + self.redirection_entries()
+    }
+
+    /// Routes a GSI to `vector` on the local APIC with id `dest`, using the
+    /// polarity and trigger mode in the MPS INTI `flags`.
+    pub fn route(&self, gsi: u32, vector: u8, flags: u16, dest: u8) {
+        let mut lo = vector as u32;
+        if flags & INTI_POLARITY_MASK == INTI_POLARITY_ACTIVE_LOW {
+            lo |= REDIRECTION_ACTIVE_LOW;
+        }
+        if flags & INTI_TRIGGER_MASK == INTI_TRIGGER_LEVEL {
+            lo |= REDIRECTION_LEVEL_TRIGGERED;
+        }
+        let reg = IOAPIC_REG_REDIRECTION + 2 * (gsi - self.gsi_base);
+        self.write(reg + 1, (dest as u32) << 24);
+        self.write(reg, lo);
+    }
+}
+
+/// Whether interrupts are delivered through the APIC instead of the 8259 PIC.
+pub fn enabled() -> bool {
+    LAPIC_BASE.load(Ordering::Relaxed) != 0
+}
+
+/// Acknowledges the interrupt currently being handled at the local APIC.
+pub fn send_eoi() {
+    if let Some(lapic) = LocalAPIC::get() {
+        lapic.send_eoi();
+    }
+}
+
+/// Enables the local APIC of the CPU this runs on. The bootstrap processor's is
+/// enabled by `init`, application processors call this themselves.
+pub fn enable_local_apic() {
+    if let Some(lapic) = LocalAPIC::get() {
+        lapic.enable();
+    }
+}
+
+/// Calibrates the local APIC timer of the CPU this runs on and starts it,
+/// interrupting `hz` times per second on `LAPIC_TIMER_IRQ`. Returns the rate
+/// it counts at, or `None` if the APIC isn't in use or calibration failed.
+pub fn start_periodic_timer(hz: u32) -> Option<u32> {
+    let lapic = LocalAPIC::get()?;
+    let bus_frequency = lapic
+        .calibrate_timer(TimerDivide::By1)
+        .filter(|frequency| *frequency > 0)?;
+    let divide = TimerDivide::slowing_to(bus_frequency, TIMER_MAX_FREQUENCY);
+    let frequency = divide.apply(bus_frequency).max(1);
+    lapic.start_timer((frequency / hz.max(1)).max(1), divide, true);
+    TIMER_FREQUENCY.store(frequency, Ordering::Relaxed);
+    Some(frequency)
+}
+
+/// The rate the local APIC timer counts at, 0 if it isn't running.
+pub fn timer_frequency() -> u32 {
+    TIMER_FREQUENCY.load(Ordering::Relaxed)
+}
+
+/// The number of local APIC timer interrupts so far.
+pub fn timer_ticks() -> u32 {
+    apic_timer_ticks()
+}
+
+fn cpu_has_apic() -> bool {
+    __cpuid(1).edx & CPUID_FEATURE_APIC != 0
+}
+
+/// Finds the IO-APIC that handles a GSI.
+pub unsafe fn io_apic_for(madt: &MADT, gsi: u32) -> Option<IOAPIC> {
+    unsafe {
+        let mut iter = madt.iter();
+        while let Some(entry) = iter.next() {
+            if let MADTEntry::IOAPIC { addr, gsi_base, .. } = entry {
+                let io_apic = IOAPIC::new(addr, gsi_base);
+                if io_apic.handles(gsi) {
+                    return Some(io_apic);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Routes a GSI to IRQ line `irq` of this processor.
+pub unsafe fn route_irq(madt: &MADT, gsi: u32, irq: u8, flags: u16) -> Result<(), APICError> {
+    unsafe {
+        let lapic = LocalAPIC::get().ok_or(APICError::NotSupported)?;
+        let io_apic = io_apic_for(madt, gsi).ok_or(APICError::NoIOAPIC)?;
+        io_apic.route(gsi, IRQ_BASE_VECTOR + irq, flags, lapic.id());
+        Ok(())
+    }
+}
+
+/// Switches interrupt delivery from the 8259 PIC to the APIC. The PIC is
+/// masked, the local APIC enabled and the ISA IRQs routed through the IO-APIC
+/// to the same vectors they had on the PIC, so handlers are unaffected.
+pub unsafe fn init(madt: &MADT) -> Result<(), APICError> {
+    unsafe {
+        if !cpu_has_apic() {
+            return Err(APICError::NotSupported);
+        }
+        if io_apic_for(madt, 0).is_none() {
+            return Err(APICError::NoIOAPIC);
+        }
+
+        if madt.has_legacy_pics() {
+            PIC::disable();
+        }
+
+        let lapic = LocalAPIC {
+            base: madt.local_apic_addr() as usize,
+        };
+        lapic.enable();
+        lapic.stop_timer();
+        LAPIC_BASE.store(lapic.base, Ordering::Relaxed);
+        cpu::get_mut(cpu::BSP_INDEX).apic_id = lapic.id();
+
+        for irq in 0..NUM_ISA_IRQS {
+            if irq == ISA_CASCADE_IRQ {
+                continue;
+            }
+            let (gsi, flags) = madt.isa_irq_to_gsi(irq);
+            route_irq(madt, gsi, irq, flags)?;
+        }
+        Ok(())
+    }
+}
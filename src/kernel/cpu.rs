@@ -0,0 +1,125 @@
+// Per-CPU data
+//
+// Every processor gets a slot in `CPUS`, holding its descriptor tables and
+// bookkeeping. The bootstrap processor always uses slot 0, application
+// processors get the following slots in the order they are started.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use crate::kernel::{
+    apic::LocalAPIC,
+    gdt::GDT,
+    tss::{TaskStateSegment, init_tss},
+};
+
+pub const MAX_CPUS: usize = 8;
+pub const BSP_INDEX: usize = 0;
+
+static mut CPUS: [Cpu; MAX_CPUS] = [const { Cpu::new() }; MAX_CPUS];
+/// The number of slots in `CPUS` that are assigned to a processor.
+static CPU_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+pub struct Cpu {
+    pub apic_id: u8,
+    pub online: AtomicBool,
+    /// The number of tasks this CPU has run.
+    pub tasks_run: AtomicU32,
+    /// The lowest address of the CPU's stack, see `kernel::gdt`.
+    stack_bottom: u32,
+    gdt: GDT,
+    tss: TaskStateSegment,
+    double_fault_tss: TaskStateSegment,
+}
+
+impl Cpu {
+    const fn new() -> Self {
+        Self {
+            apic_id: 0,
+            online: AtomicBool::new(false),
+            tasks_run: AtomicU32::new(0),
+            stack_bottom: 0,
+            gdt: GDT::new(),
+            tss: TaskStateSegment::new(),
+            double_fault_tss: TaskStateSegment::new(),
+        }
+    }
+
+    /// Sets up this CPU's TSSs and GDT and loads them, limiting the stack to
+    /// `stack_bottom`. Must be called on the CPU itself.
+    pub unsafe fn load_descriptors(&mut self, double_fault_stack_top: u32, stack_bottom: u32) {
+        self.stack_bottom = stack_bottom;
+        unsafe {
+            init_tss(
+                &mut self.tss,
+                &mut self.double_fault_tss,
+                double_fault_stack_top,
+            );
+            self.gdt.init(
+                &raw const self.tss,
+                &raw const self.double_fault_tss,
+                stack_bottom,
+            );
+            self.gdt.load();
+        }
+    }
+
+    /// The TSS the CPU saves its state in when switching to the double fault task.
+    pub fn kernel_tss(&self) -> &TaskStateSegment {
+        &self.tss
+    }
+
+    pub fn stack_bottom(&self) -> u32 {
+        self.stack_bottom
+    }
+
+    pub fn gdt(&self) -> &GDT {
+        &self.gdt
+    }
+}
+
+/// The number of processors known to the kernel, online or not.
+pub fn count() -> usize {
+    CPU_COUNT.load(Ordering::Acquire)
+}
+
+pub fn get(index: usize) -> Option<&'static Cpu> {
+    if index < count() {
+        Some(unsafe { &*(&raw const CPUS).cast::<Cpu>().add(index) })
+    } else {
+        None
+    }
+}
+
+/// Mutable access to a CPU's data. Only the CPU itself, or the bootstrap
+/// processor before starting it, may do so.
+pub unsafe fn get_mut(index: usize) -> &'static mut Cpu {
+    unsafe { &mut *(&raw mut CPUS).cast::<Cpu>().add(index) }
+}
+
+/// Assigns the next free slot to the processor with the given local APIC id.
+/// Must only be called by the bootstrap processor.
+pub unsafe fn add(apic_id: u8) -> Option<usize> {
+    let index = count();
+    if index >= MAX_CPUS {
+        return None;
+    }
+    unsafe { get_mut(index).apic_id = apic_id };
+    CPU_COUNT.store(index + 1, Ordering::Release);
+    Some(index)
+}
+
+/// The index of the CPU this code runs on. Before the APIC is enabled, only
+/// the bootstrap processor runs.
+pub fn current_index() -> usize {
+    let Some(lapic) = LocalAPIC::get() else {
+        return BSP_INDEX;
+    };
+    let apic_id = lapic.id();
+    (0..count())
+        .find(|index| get(*index).is_some_and(|cpu| cpu.apic_id == apic_id))
+        .unwrap_or(BSP_INDEX)
+}
+
+pub fn current() -> &'static Cpu {
+    unsafe { &*(&raw const CPUS).cast::<Cpu>().add(current_index()) }
+}
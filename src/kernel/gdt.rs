@@ -0,0 +1,188 @@
+// Global Descriptor Table
+//
+// The boot sector sets up a minimal flat GDT to get into protected mode.
+// The kernel replaces it with its own, so that it can add TSS descriptors.
+// Every CPU has its own GDT, since a TSS descriptor is marked busy once it is
+// loaded and can therefore not be shared between CPUs.
+//
+// The stack segment is expand-down and ends at the bottom of the CPU's stack,
+// so a stack overflow raises a stack fault. Delivering it needs the stack as
+// well, which turns it into a double fault that the double fault task
+// reports. Code must therefore only address the stack through ESP and EBP,
+// which the target spec ensures by always keeping frame pointers.
+
+use core::{arch::asm, sync::atomic::Ordering};
+
+use crate::kernel::{
+    cpu,
+    mem::BSP_STACK_BOTTOM,
+    tss::{TaskStateSegment, bsp_double_fault_stack_top},
+};
+
+pub const KERNEL_CS: u16 = 0x08;
+pub const KERNEL_DS: u16 = 0x10;
+pub const KERNEL_TSS_SEL: u16 = 0x18;
+pub const DOUBLE_FAULT_TSS_SEL: u16 = 0x20;
+pub const KERNEL_SS: u16 = 0x28;
+
+const NUM_GDT_ENTRIES: usize = 6;
+type GDTEntries = [GDTEntry; NUM_GDT_ENTRIES];
+
+/* Access byte
+ * Bit 7: present
+ * Bits 6-5: privilege level (0=kernel..3=user)
+ * Bit 4: 1 for code/data segments, 0 for system segments (TSS)
+ * Bits 3-0: segment type
+ */
+const ACCESS_KERNEL_CODE: u8 = 0b1001_1010;
+const ACCESS_KERNEL_DATA: u8 = 0b1001_0010;
+const ACCESS_KERNEL_STACK: u8 = 0b1001_0110; // Expand-down data
+const ACCESS_TSS: u8 = 0b1000_1001; // 32 bit available TSS
+
+/* Flags nibble
+ * Bit 3: granularity (limit in 4 KiB pages)
+ * Bit 2: 32 bit segment
+ */
+const FLAGS_FLAT: u8 = 0b1100;
+const FLAGS_BYTE_GRANULARITY: u8 = 0b0000;
+/// Byte granular, which keeps the stacks below 1 MiB. An expand-down segment
+/// marked 32 bit reaches up to 4 GiB.
+const FLAGS_STACK: u8 = 0b0100;
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct GDTEntry {
+    lo_limit: u16,
+    lo_base: u16,
+    mid_base: u8,
+    access: u8,
+    flags_hi_limit: u8, // Flags in the high nibble, limit bits 19-16 in the low nibble.
+    hi_base: u8,
+}
+
+impl GDTEntry {
+    pub const fn null() -> Self {
+        Self::new(0, 0, 0, 0)
+    }
+
+    pub const fn new(base: u32, limit: u32, access: u8, flags: u8) -> Self {
+        Self {
+            lo_limit: (limit & 0xFFFF) as u16,
+            lo_base: (base & 0xFFFF) as u16,
+            mid_base: ((base >> 16) & 0xFF) as u8,
+            access,
+            flags_hi_limit: (flags << 4) | ((limit >> 16) & 0x0F) as u8,
+            hi_base: (base >> 24) as u8,
+        }
+    }
+
+    fn tss(tss: *const TaskStateSegment) -> Self {
+        Self::new(
+            tss as u32,
+            (core::mem::size_of::<TaskStateSegment>() - 1) as u32,
+            ACCESS_TSS,
+            FLAGS_BYTE_GRANULARITY,
+        )
+    }
+
+    /// A stack segment in which offsets below `bottom` are out of bounds.
+    fn stack(bottom: u32) -> Self {
+        Self::new(
+            0,
+            bottom.saturating_sub(1),
+            ACCESS_KERNEL_STACK,
+            FLAGS_STACK,
+        )
+    }
+}
+
+// The GDT register must be 6 bytes in length.
+#[repr(C, packed)]
+pub struct GDTReg {
+    pub limit: u16,
+    pub base: *const GDTEntry,
+}
+
+impl GDTReg {
+    pub const fn null() -> Self {
+        Self {
+            limit: 0,
+            base: core::ptr::null(),
+        }
+    }
+}
+
+pub struct GDT {
+    entries: GDTEntries,
+    reg: GDTReg,
+}
+
+impl GDT {
+    pub const fn new() -> Self {
+        Self {
+            entries: [GDTEntry::null(); NUM_GDT_ENTRIES],
+            reg: GDTReg::null(),
+        }
+    }
+
+    /// Fills in the flat kernel segments, the TSS descriptors and the stack
+    /// segment. The code and data segments are identical to the ones set up by
+    /// the boot sector.
+    pub fn init(
+        &mut self,
+        tss: *const TaskStateSegment,
+        double_fault_tss: *const TaskStateSegment,
+        stack_bottom: u32,
+    ) {
+        self.entries[1] = GDTEntry::new(0, 0xFFFFF, ACCESS_KERNEL_CODE, FLAGS_FLAT);
+        self.entries[2] = GDTEntry::new(0, 0xFFFFF, ACCESS_KERNEL_DATA, FLAGS_FLAT);
+        self.entries[3] = GDTEntry::tss(tss);
+        self.entries[4] = GDTEntry::tss(double_fault_tss);
+        self.entries[5] = GDTEntry::stack(stack_bottom);
+        self.reg.base = &raw const self.entries[0];
+        self.reg.limit = (core::mem::size_of::<GDTEntries>() - 1) as u16;
+    }
+
+    /// Loads this GDT, reloads all segment registers and loads the task
+    /// register with the kernel TSS.
+    pub unsafe fn load(&self) {
+        let gdt_reg_ptr: *const u16 = &raw const self.reg.limit;
+        unsafe {
+            asm!(
+                "lgdt [{reg}]",
+                // A far return is the simplest way to reload CS.
+                "push {cs}",
+                "lea {tmp}, [2f]",
+                "push {tmp}",
+                "retf",
+                "2:",
+                "mov ds, {ds:x}",
+                "mov es, {ds:x}",
+                "mov fs, {ds:x}",
+                "mov gs, {ds:x}",
+                "mov ss, {ss:x}",
+                "ltr {tss:x}",
+                reg = in(reg) gdt_reg_ptr,
+                cs = const KERNEL_CS as u32,
+                ds = in(reg) KERNEL_DS as u32,
+                ss = in(reg) KERNEL_SS as u32,
+                tss = in(reg) KERNEL_TSS_SEL as u32,
+                tmp = out(reg) _,
+            );
+        }
+    }
+
+    /// The address of the GDT register contents, to be used with `lgdt`.
+    pub fn reg(&self) -> &GDTReg {
+        &self.reg
+    }
+}
+
+/// Sets up and loads the descriptor tables of the bootstrap processor.
+pub unsafe fn set_gdt() {
+    unsafe {
+        let bsp = cpu::get_mut(cpu::BSP_INDEX);
+        bsp.load_descriptors(bsp_double_fault_stack_top(), BSP_STACK_BOTTOM);
+        bsp.online.store(true, Ordering::Release);
+    }
+}
@@ -1,8 +1,11 @@
 // Interrupt Discriptor Table
 
-use crate::util::{address_hi_16_bytes, address_lo_16_bytes};
+use crate::{
+    kernel::gdt::KERNEL_CS,
+    util::{address_hi_16_bytes, address_lo_16_bytes},
+};
 
-const KERNEL_CS: u16 = 0x08;
+const TASK_GATE_FLAGS: u8 = 0x85;
 
 #[derive(Clone, Copy)]
 #[repr(C, packed)]
@@ -15,6 +18,7 @@ pub struct IDTGate {
      * Bits 6-5: Privilege level of caller (0=kernel..3=user)
      * Bit 4: Set to 0 for interrupt gates
      * Bits 3-0: bits 1110 = decimal 14 = "32 bit interrupt gate"
+     *           bits 0101 = decimal 5 = "task gate"
      */
     flags: u8,
     hi_offset: u16, // Hi bits of handler function
@@ -36,6 +40,15 @@ impl IDTGate {
         self.hi_offset = address_hi_16_bytes(handler_addr);
         self.lo_offset = address_lo_16_bytes(handler_addr);
     }
+
+    /// Turns this gate into a task gate, which switches to the task described
+    /// by the TSS behind `tss_sel` instead of calling a handler.
+    pub fn set_task(&mut self, tss_sel: u16) {
+        self.sel = tss_sel;
+        self.flags = TASK_GATE_FLAGS;
+        self.hi_offset = 0;
+        self.lo_offset = 0;
+    }
 }
 
 // The IDT register must be 6 bytes in length.
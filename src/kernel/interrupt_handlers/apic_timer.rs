@@ -0,0 +1,20 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{kernel::isr::Registers, sys_event::SysEvent};
+
+/// The rate the kernel runs the local APIC timer at, in Hz.
+pub const APIC_TIMER_FREQUENCY: u32 = 100;
+
+static APIC_TIMER_TICKS: AtomicU32 = AtomicU32::new(0);
+
+/// Counts local APIC timer interrupts. The end of interrupt sent for every
+/// IRQ is all the timer needs to fire again.
+pub unsafe fn apic_timer_handler(_regs: Registers) -> Option<SysEvent> {
+    APIC_TIMER_TICKS.fetch_add(1, Ordering::Relaxed);
+    None
+}
+
+/// The number of local APIC timer interrupts since it was started.
+pub fn apic_timer_ticks() -> u32 {
+    APIC_TIMER_TICKS.load(Ordering::Relaxed)
+}
@@ -0,0 +1,224 @@
+use core::arch::asm;
+
+use crate::{
+    hex_printable::u32_to_hex_ascii,
+    kernel::{
+        cpu,
+        gdt::KERNEL_SS,
+        isr::{ISR_EXCEPTION_MSGS, Registers},
+        vga_driver::VGAText,
+    },
+    printer::VGATextWriter,
+};
+
+const DEBUG: u32 = 1;
+const BREAKPOINT: u32 = 3;
+const OVERFLOW: u32 = 4;
+const DOUBLE_FAULT: u32 = 8;
+const INVALID_TSS: u32 = 10;
+const SEGMENT_NOT_PRESENT: u32 = 11;
+const STACK_FAULT: u32 = 12;
+const GENERAL_PROTECTION_FAULT: u32 = 13;
+const PAGE_FAULT: u32 = 14;
+
+/// Size of what the CPU pushes for an exception without a privilege change:
+/// EIP, CS and EFLAGS.
+const CPU_FRAME_SIZE: u32 = 3 * 4;
+/// Size of the interrupt number and error code pushed by the stubs.
+const STUB_FRAME_SIZE: u32 = 2 * 4;
+/// Delivering a stack fault takes the CPU frame and an error code. With less
+/// room left on the stack, a double fault means the stack overflowed.
+const STACK_FAULT_FRAME_SIZE: u32 = CPU_FRAME_SIZE + 4;
+
+/// Handles CPU exceptions 0-31. Every exception is reported on screen with
+/// the full register dump. Traps (debug, breakpoint and overflow) resume
+/// execution afterwards, whereas faults and aborts halt the kernel: there
+/// is no task to kill yet, and returning would retry the faulting
+/// instruction forever.
+pub unsafe fn exception_handler(regs: &Registers) {
+    unsafe {
+        report(regs, faulting_esp(regs));
+        match regs.int_no {
+            DEBUG | BREAKPOINT | OVERFLOW => {}
+            _ => halt(),
+        }
+    }
+}
+
+/// Entry point of the double fault task. The CPU switches here through the
+/// task gate, having saved the state of the faulting code in the kernel TSS
+/// of the CPU it ran on. A kernel stack overflow ends up here, since the
+/// stack fault it raises can't be delivered on the full stack.
+/// The error code of a double fault is always zero, so it is left on the stack.
+pub extern "C" fn double_fault_task() -> ! {
+    unsafe {
+        let cpu = cpu::current();
+        let tss = *cpu.kernel_tss();
+        let regs = Registers {
+            ds: tss.ds,
+            edi: tss.edi,
+            esi: tss.esi,
+            ebp: tss.ebp,
+            esp: tss.esp,
+            ebx: tss.ebx,
+            edx: tss.edx,
+            ecx: tss.ecx,
+            eax: tss.eax,
+            int_no: DOUBLE_FAULT,
+            err_code: 0,
+            eip: tss.eip,
+            cs: tss.cs,
+            eflags: tss.eflags,
+            useresp: tss.esp,
+            ss: tss.ss,
+        };
+        report(&regs, tss.esp);
+        if tss.ss == KERNEL_SS as u32
+            && tss.esp < cpu.stack_bottom().saturating_add(STACK_FAULT_FRAME_SIZE)
+        {
+            let mut vga = VGAText {};
+            let mut tty = VGATextWriter::create(&mut vga);
+            tty.nl();
+            tty.println_ascii("Kernel stack overflow".as_bytes());
+        }
+    }
+    halt()
+}
+
+/// Stops the CPU for good. Interrupts are disabled first so that the
+/// `hlt` cannot be woken up by an IRQ.
+pub fn halt() -> ! {
+    loop {
+        unsafe { asm!("cli", "hlt", options(nomem, nostack)) };
+    }
+}
+
+/// Prints a report of an exception to the screen. A fresh writer is created
+/// instead of going through the kernel's VGA lock, since the lock may be
+/// held by the code that faulted. `esp` is the stack pointer of the faulting code.
+pub unsafe fn report(regs: &Registers, esp: u32) {
+    unsafe {
+        let mut vga = VGAText {};
+        let mut tty = VGATextWriter::create(&mut vga);
+        tty.clear();
+
+        let int_no = regs.int_no;
+        tty.print_ascii("EXCEPTION ".as_bytes());
+        print_hex(&mut tty, int_no);
+        tty.print_ascii(": ".as_bytes());
+        match ISR_EXCEPTION_MSGS.get(int_no as usize) {
+            Some(msg) => tty.println_ascii(msg.as_bytes()),
+            None => tty.println_ascii("Unknown".as_bytes()),
+        }
+        tty.nl();
+
+        print_reg(&mut tty, "EAX=", regs.eax);
+        print_reg(&mut tty, " EBX=", regs.ebx);
+        print_reg(&mut tty, " ECX=", regs.ecx);
+        print_reg(&mut tty, " EDX=", regs.edx);
+        tty.nl();
+        print_reg(&mut tty, "ESI=", regs.esi);
+        print_reg(&mut tty, " EDI=", regs.edi);
+        print_reg(&mut tty, " EBP=", regs.ebp);
+        print_reg(&mut tty, " ESP=", esp);
+        tty.nl();
+        print_reg(&mut tty, "EIP=", regs.eip);
+        print_reg(&mut tty, " CS=", regs.cs);
+        print_reg(&mut tty, " DS=", regs.ds);
+        print_reg(&mut tty, " EFLAGS=", regs.eflags);
+        tty.nl();
+        if regs.cs & 0x3 != 0 {
+            print_reg(&mut tty, "USERESP=", regs.useresp);
+            print_reg(&mut tty, " SS=", regs.ss);
+            tty.nl();
+        }
+        print_reg(&mut tty, "ERR=", regs.err_code);
+        tty.nl();
+
+        match int_no {
+            INVALID_TSS | SEGMENT_NOT_PRESENT | STACK_FAULT | GENERAL_PROTECTION_FAULT => {
+                print_selector_error(&mut tty, regs.err_code)
+            }
+            PAGE_FAULT => print_page_fault_error(&mut tty, regs.err_code),
+            _ => {}
+        }
+    }
+}
+
+/// The `esp` saved by `pusha` points at the interrupt number pushed by the stub.
+/// When no privilege change happened, the stack of the faulting code starts
+/// right above the frame pushed by the CPU.
+fn faulting_esp(regs: &Registers) -> u32 {
+    if regs.cs & 0x3 != 0 {
+        regs.useresp
+    } else {
+        regs.esp + STUB_FRAME_SIZE + CPU_FRAME_SIZE
+    }
+}
+
+/// Decodes a selector error code.
+/// Bit 0: the exception originated externally to the processor.
+/// Bits 2-1: the descriptor table (00 GDT, 01/11 IDT, 10 LDT).
+/// Bits 15-3: the index into that table.
+unsafe fn print_selector_error(tty: &mut VGATextWriter, err_code: u32) {
+    unsafe {
+        if err_code == 0 {
+            tty.println_ascii("Not selector related.".as_bytes());
+            return;
+        }
+        let table = match (err_code >> 1) & 0x3 {
+            0b00 => "GDT",
+            0b10 => "LDT",
+            _ => "IDT",
+        };
+        tty.print_ascii("Selector: ".as_bytes());
+        tty.print_ascii(table.as_bytes());
+        tty.print_ascii(" index ".as_bytes());
+        print_hex(tty, (err_code >> 3) & 0x1FFF);
+        if err_code & 0x1 != 0 {
+            tty.print_ascii(" (external)".as_bytes());
+        }
+        tty.nl();
+    }
+}
+
+/// Decodes a page fault error code, along with the faulting address from CR2.
+unsafe fn print_page_fault_error(tty: &mut VGATextWriter, err_code: u32) {
+    unsafe {
+        let cr2: u32;
+        asm!("mov {}, cr2", out(reg) cr2, options(nomem, nostack, preserves_flags));
+        print_reg(tty, "Address=", cr2);
+        tty.nl();
+        tty.print_ascii(if err_code & 0x1 != 0 {
+            "Protection violation".as_bytes()
+        } else {
+            "Page not present".as_bytes()
+        });
+        tty.print_ascii(if err_code & 0x2 != 0 {
+            " on write".as_bytes()
+        } else {
+            " on read".as_bytes()
+        });
+        if err_code & 0x4 != 0 {
+            tty.print_ascii(" from user mode".as_bytes());
+        }
+        if err_code & 0x8 != 0 {
+            tty.print_ascii(", reserved bit set".as_bytes());
+        }
+        if err_code & 0x10 != 0 {
+            tty.print_ascii(", instruction fetch".as_bytes());
+        }
+        tty.nl();
+    }
+}
+
+unsafe fn print_reg(tty: &mut VGATextWriter, name: &str, value: u32) {
+    unsafe {
+        tty.print_ascii(name.as_bytes());
+        print_hex(tty, value);
+    }
+}
+
+unsafe fn print_hex(tty: &mut VGATextWriter, value: u32) {
+    unsafe { tty.print_ascii(&u32_to_hex_ascii(value)) };
+}
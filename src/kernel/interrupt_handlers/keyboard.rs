@@ -1,5 +1,17 @@
-use crate::{kernel::isr::Registers, sys_event::SysEvent};
+use crate::{
+    kernel::{
+        isr::Registers,
+        ports::{Port, read_port_byte},
+    },
+    sys_event::SysEvent,
+};
 
+pub const KEYBOARD_IRQ: u8 = 1;
+
+/// Reads the scan code while still in interrupt context, so the controller can
+/// deliver the next one before the kernel gets to handle the event. Decoding
+/// it is left to the keyboard driver.
 pub unsafe fn keyboard_handler(_regs: Registers) -> Option<SysEvent> {
-    return Some(SysEvent::Keyboard);
+    let scancode = read_port_byte(Port::PS2DataPort.into());
+    Some(SysEvent::Scancode { scancode })
 }
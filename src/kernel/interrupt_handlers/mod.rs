@@ -1,269 +1,116 @@
-mod null_handler;
-mod keyboard;
+pub mod apic_timer;
+pub mod exception;
+pub mod keyboard;
+pub mod mouse;
+pub mod rtc;
+pub mod timer;
 
-use crate::sys_event::SysEvent;
 use crate::kernel::isr::Registers;
+use crate::sys_event::SysEvent;
+
+/// Handler for a hardware interrupt. A handler may produce an event, which is
+/// passed on to the kernel's main loop.
+pub type InterruptHandler = unsafe fn(Registers) -> Option<SysEvent>;
+
+/// The vector IRQ 0 is remapped to. IRQ `n` arrives at vector `IRQ_BASE_VECTOR + n`.
+pub const IRQ_BASE_VECTOR: u8 = 32;
+/// The 16 ISA IRQs, 8 more IO-APIC inputs, the local APIC timer and the
+/// wake-up IPI of application processors.
+pub const NUM_IRQS: usize = 26;
+/// The maximum number of handlers that can share a single IRQ line.
+const MAX_SHARED_HANDLERS: usize = 4;
+
+static mut INTERRUPT_HANDLERS: [[Option<InterruptHandler>; MAX_SHARED_HANDLERS]; NUM_IRQS] =
+    [[None; MAX_SHARED_HANDLERS]; NUM_IRQS];
+
+pub enum IrqError {
+    InvalidIrq,
+    AlreadyRegistered,
+    LineFull,
+    NotRegistered,
+}
+
+/// Registers a handler for an IRQ line. Lines can be shared; all handlers
+/// registered on a line are called, in order of registration, when it fires.
+///
+/// Handlers should be registered while interrupts are disabled, since the
+/// handler table is not synchronised with the interrupt handler.
+pub unsafe fn register_irq(irq: u8, handler: InterruptHandler) -> Result<(), IrqError> {
+    let line = line(irq)?;
+    unsafe {
+        let mut free = None;
+        for i in 0..MAX_SHARED_HANDLERS {
+            match INTERRUPT_HANDLERS[line][i] {
+                Some(h) if core::ptr::fn_addr_eq(h, handler) => {
+                    return Err(IrqError::AlreadyRegistered);
+                }
+                None if free.is_none() => free = Some(i),
+                _ => {}
+            }
+        }
+        let i = free.ok_or(IrqError::LineFull)?;
+        INTERRUPT_HANDLERS[line][i] = Some(handler);
+    }
+    Ok(())
+}
+
+/// Removes a handler from an IRQ line. The remaining handlers on the line keep
+/// their order.
+pub unsafe fn unregister_irq(irq: u8, handler: InterruptHandler) -> Result<(), IrqError> {
+    let line = line(irq)?;
+    unsafe {
+        for i in 0..MAX_SHARED_HANDLERS {
+            if let Some(h) = INTERRUPT_HANDLERS[line][i]
+                && core::ptr::fn_addr_eq(h, handler)
+            {
+                for j in i..MAX_SHARED_HANDLERS - 1 {
+                    INTERRUPT_HANDLERS[line][j] = INTERRUPT_HANDLERS[line][j + 1];
+                }
+                INTERRUPT_HANDLERS[line][MAX_SHARED_HANDLERS - 1] = None;
+                return Ok(());
+            }
+        }
+    }
+    Err(IrqError::NotRegistered)
+}
+
+/// Calls every handler registered on an IRQ line, passing each produced event
+/// to `on_event`.
+pub unsafe fn dispatch_irq(irq: u8, regs: Registers, mut on_event: impl FnMut(SysEvent)) {
+    let Ok(line) = line(irq) else {
+        return;
+    };
+    for i in 0..MAX_SHARED_HANDLERS {
+        match unsafe { INTERRUPT_HANDLERS[line][i] } {
+            Some(handler) => {
+                if let Some(event) = unsafe { handler(regs) } {
+                    on_event(event);
+                }
+            }
+            None => break,
+        }
+    }
+}
+
+/// Returns whether at least one handler is registered on an IRQ line.
+pub fn irq_in_use(irq: u8) -> bool {
+    match line(irq) {
+        Ok(line) => unsafe { INTERRUPT_HANDLERS[line][0].is_some() },
+        Err(_) => false,
+    }
+}
+
+/// Iterates over the interrupt vectors that have a handler attached to them.
+pub fn used_vectors() -> impl Iterator<Item = u8> {
+    (0..NUM_IRQS as u8)
+        .filter(|irq| irq_in_use(*irq))
+        .map(|irq| irq + IRQ_BASE_VECTOR)
+}
 
-pub static mut INTERRUPT_HANDLERS: [unsafe fn(Registers) -> Option<SysEvent>; 256] = [
-    // 0
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    // 16, 0x10
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    // 32
-    null_handler::null_handler,
-    keyboard::keyboard_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    // 48
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    // 64
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-    null_handler::null_handler,
-];
\ No newline at end of file
+fn line(irq: u8) -> Result<usize, IrqError> {
+    if (irq as usize) < NUM_IRQS {
+        Ok(irq as usize)
+    } else {
+        Err(IrqError::InvalidIrq)
+    }
+}
@@ -0,0 +1,31 @@
+use crate::{
+    kernel::{
+        isr::Registers,
+        mouse_driver::{MouseKind, PacketDecoder},
+        ports::{Port, read_port_byte},
+    },
+    sys_event::SysEvent,
+};
+
+pub const MOUSE_IRQ: u8 = 12;
+
+static DECODER: spin::Mutex<PacketDecoder> = spin::Mutex::new(PacketDecoder::new());
+
+/// Sets the packet format the handler decodes. Must be called before the
+/// second PS/2 port's interrupt is enabled.
+pub fn set_mouse_kind(kind: MouseKind) {
+    DECODER.lock().set_kind(kind);
+}
+
+/// Collects packet bytes, one per interrupt, and raises an event once a
+/// whole packet has arrived.
+pub unsafe fn mouse_handler(_regs: Registers) -> Option<SysEvent> {
+    let byte = read_port_byte(Port::PS2DataPort.into());
+    let packet = DECODER.lock().push(byte)?;
+    Some(SysEvent::Mouse {
+        dx: packet.dx,
+        dy: packet.dy,
+        wheel: packet.wheel,
+        buttons: packet.buttons,
+    })
+}
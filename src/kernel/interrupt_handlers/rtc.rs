@@ -0,0 +1,23 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    kernel::{isr::Registers, time::rtc},
+    sys_event::SysEvent,
+};
+
+pub const RTC_IRQ: u8 = 8;
+
+static RTC_TICKS: AtomicU32 = AtomicU32::new(0);
+
+/// Counts periodic RTC interrupts. Status register C must be read for the
+/// RTC to raise the next one.
+pub unsafe fn rtc_handler(_regs: Registers) -> Option<SysEvent> {
+    rtc::acknowledge_interrupt();
+    RTC_TICKS.fetch_add(1, Ordering::Relaxed);
+    None
+}
+
+/// The number of periodic RTC interrupts since they were enabled.
+pub fn rtc_ticks() -> u32 {
+    RTC_TICKS.load(Ordering::Relaxed)
+}
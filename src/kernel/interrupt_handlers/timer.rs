@@ -0,0 +1,16 @@
+use crate::{
+    kernel::{isr::Registers, time},
+    sys_event::SysEvent,
+};
+
+pub const TIMER_IRQ: u8 = 0;
+
+/// Advances the system tick. An event is only raised when a kernel timer is
+/// due, rather than on every tick, to keep the event queue free for others.
+pub unsafe fn timer_handler(_regs: Registers) -> Option<SysEvent> {
+    if time::tick() {
+        Some(SysEvent::Timer)
+    } else {
+        None
+    }
+}
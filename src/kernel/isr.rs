@@ -1,13 +1,24 @@
-use core::arch::asm;
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use crate::{
+    kernel::apic::{self, SPURIOUS_VECTOR},
+    kernel::gdt::DOUBLE_FAULT_TSS_SEL,
     kernel::idt::{IDTGate, IDTReg},
-    kernel::interrupt_handlers::INTERRUPT_HANDLERS,
+    kernel::interrupt_handlers::{
+        self, IRQ_BASE_VECTOR, dispatch_irq, exception::exception_handler,
+    },
     kernel::pic::PIC,
-    sys_event::SysEvent,
+    kernel::time,
+    ring_buffer::RingBuffer,
+    sys_event::{InterruptEvent, SysEvent},
 };
 
 const NUM_IDT_GATES: usize = 256;
+/// The interrupt enable flag in EFLAGS.
+const INTERRUPT_FLAG: u32 = 1 << 9;
 type IDTGates = [IDTGate; NUM_IDT_GATES];
 
 static mut IDT_REG: IDTReg = IDTReg::null();
@@ -17,18 +28,18 @@ static mut IDT: IDTGates = {
 };
 static mut LAST_INTERRUPT: u32 = 0;
 
-const EVENT_BUF_SIZE: usize = 8;
-struct EventBuf {
-    buf: [Option<SysEvent>; EVENT_BUF_SIZE],
-    len: usize,
-}
+/// Capacity of the queue between interrupt handlers and the kernel's main loop.
+/// Must be a power of two.
+pub const EVENT_QUEUE_CAPACITY: usize = 32;
 
-static mut EVENT_BUF: EventBuf = EventBuf {
-    buf: [None; EVENT_BUF_SIZE],
-    len: 0,
-};
+static EVENT_QUEUE: RingBuffer<InterruptEvent, EVENT_QUEUE_CAPACITY> = RingBuffer::new();
+/// Serializes the producers of the queue. Interrupts are disabled on the CPU
+/// holding it, so only producers on other CPUs ever wait for it.
+static EVENT_PRODUCER: spin::Mutex<()> = spin::Mutex::new(());
+/// The most ticks an event waited in the queue before it was taken.
+static MAX_EVENT_DELAY: AtomicU32 = AtomicU32::new(0);
 
-const ISR_EXCEPTION_MSGS: [&str; 32] = [
+pub const ISR_EXCEPTION_MSGS: [&str; 32] = [
     "Division By Zero",
     "Debug",
     "Non Maskable Interrupt",
@@ -73,7 +84,8 @@ pub unsafe fn set_isr() {
         IDT[5].set(isr5);
         IDT[6].set(isr6);
         IDT[7].set(isr7);
-        IDT[8].set(isr8);
+        // Double faults switch to a separate task, so a broken stack can be reported.
+        IDT[8].set_task(DOUBLE_FAULT_TSS_SEL);
         IDT[9].set(isr9);
         IDT[10].set(isr10);
         IDT[11].set(isr11);
@@ -117,8 +129,28 @@ pub unsafe fn set_isr() {
         IDT[46].set(irq14);
         IDT[47].set(irq15);
 
+        // Only reachable through the IO-APIC and local APIC.
+        IDT[48].set(irq16);
+        IDT[49].set(irq17);
+        IDT[50].set(irq18);
+        IDT[51].set(irq19);
+        IDT[52].set(irq20);
+        IDT[53].set(irq21);
+        IDT[54].set(irq22);
+        IDT[55].set(irq23);
+        IDT[56].set(irq24);
+        IDT[57].set(irq25);
+        IDT[SPURIOUS_VECTOR as usize].set(spurious_irq);
+
         IDT_REG.base = &IDT[0];
         IDT_REG.limit = (core::mem::size_of::<IDTGates>() - 1) as u16;
+        load_idt();
+    }
+}
+
+/// Loads the IDT on the CPU this runs on. All CPUs share the same IDT.
+pub unsafe fn load_idt() {
+    unsafe {
         let idt_reg_ptr: *const u16 = &raw const IDT_REG.limit;
         asm!(
             "lidt [{0}]",
@@ -128,6 +160,7 @@ pub unsafe fn set_isr() {
     }
 }
 
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
 pub struct Registers {
     pub ds: u32,
@@ -152,38 +185,85 @@ pub struct Registers {
 unsafe extern "C" fn isr_handler(regs: Registers) {
     unsafe {
         LAST_INTERRUPT = regs.int_no;
+        exception_handler(&regs);
     }
 }
 
 #[unsafe(no_mangle)]
-unsafe extern "C" fn irq_handler(mut regs: Registers) {
+unsafe extern "C" fn irq_handler(regs: Registers) {
     unsafe {
-        PIC::send_eoi(regs.int_no as u8);
+        if apic::enabled() {
+            apic::send_eoi();
+        } else {
+            PIC::send_eoi(regs.int_no as u8);
+        }
         if regs.int_no > 0 {
             LAST_INTERRUPT = regs.int_no;
-            if regs.int_no == 0x12 {
-                regs.int_no += 1;
-            }
-            if let Some(event) = INTERRUPT_HANDLERS[regs.int_no as usize](regs) {
-                // TODO BHV this leads to missed events if the buffer fills up, and gives no warnings.
-                EVENT_BUF.buf[EVENT_BUF.len % EVENT_BUF_SIZE] = Some(event);
-                EVENT_BUF.len += 1;
-            }
+            let irq = (regs.int_no as u8).wrapping_sub(IRQ_BASE_VECTOR);
+            // Interrupt gates clear the interrupt flag, as `queue_event` needs.
+            dispatch_irq(irq, regs, |event| queue_event(event, irq));
         }
     }
 }
 
-pub unsafe fn empty_event_buffer() -> [Option<SysEvent>; EVENT_BUF_SIZE] {
+/// Iterates over the interrupt vectors that have an IRQ handler attached.
+pub fn used_vectors() -> impl Iterator<Item = u8> {
+    interrupt_handlers::used_vectors()
+}
+
+/// Queues an event. The queue only allows one producer at a time, and
+/// interrupt handlers on any CPU and kernel code may all raise events, so
+/// producers take `EVENT_PRODUCER`. Interrupts must be disabled, otherwise an
+/// interrupt handler could spin on the lock held by the code it interrupted.
+/// When the queue is full, the event is dropped and accounted for by the queue.
+unsafe fn queue_event(event: SysEvent, device: u8) {
+    let _producer = EVENT_PRODUCER.lock();
+    unsafe {
+        EVENT_QUEUE.push(InterruptEvent {
+            event,
+            device,
+            tick: time::ticks() as u32,
+        });
+    }
+}
+
+/// Queues an event raised outside of interrupt context, like from a kernel
+/// timer. Interrupts are disabled meanwhile.
+pub fn raise_event(event: SysEvent, device: u8) {
+    let flags: u32;
     unsafe {
-        let cp = EVENT_BUF.buf;
-        while EVENT_BUF.len > 0 {
-            EVENT_BUF.len -= 1;
-            EVENT_BUF.buf[EVENT_BUF.len] = None;
+        asm!("pushfd", "pop {}", "cli", out(reg) flags);
+        queue_event(event, device);
+        if flags & INTERRUPT_FLAG != 0 {
+            asm!("sti");
         }
-        cp
     }
 }
 
+/// Takes the oldest event raised by an interrupt handler. The kernel's main
+/// loop must be the only caller.
+pub unsafe fn next_event() -> Option<InterruptEvent> {
+    let event = unsafe { EVENT_QUEUE.pop() }?;
+    let delay = (time::ticks() as u32).wrapping_sub(event.tick);
+    MAX_EVENT_DELAY.fetch_max(delay, Ordering::Relaxed);
+    Some(event)
+}
+
+/// The number of events waiting to be taken.
+pub fn queued_events() -> usize {
+    EVENT_QUEUE.len()
+}
+
+/// The number of events that were dropped because the queue was full.
+pub fn dropped_events() -> u32 {
+    EVENT_QUEUE.dropped()
+}
+
+/// The most ticks an event waited in the queue before the main loop took it.
+pub fn max_event_delay() -> u32 {
+    MAX_EVENT_DELAY.load(Ordering::Relaxed)
+}
+
 pub unsafe fn clear_last_interrupt() {
     unsafe {
         LAST_INTERRUPT = 0;
@@ -215,8 +295,6 @@ unsafe extern "C" {
 
     fn isr7();
 
-    fn isr8();
-
     fn isr9();
 
     fn isr10();
@@ -277,4 +355,15 @@ unsafe extern "C" {
     fn irq13();
     fn irq14();
     fn irq15();
+    fn irq16();
+    fn irq17();
+    fn irq18();
+    fn irq19();
+    fn irq20();
+    fn irq21();
+    fn irq22();
+    fn irq23();
+    fn irq24();
+    fn irq25();
+    fn spurious_irq();
 }
@@ -4,16 +4,37 @@ use once_cell_no_std::OnceCell;
 
 use crate::{
     kernel::{
-        isr::set_isr, keyboard_driver::KeyboardDriver, mem::MemoryManager,
-        process_manager::ProcessManager, vga_driver::VGAText,
+        acpi::{acpi::ACPI, madt::MADT},
+        apic::{self, LAPIC_TIMER_IRQ},
+        gdt::set_gdt,
+        interrupt_handlers::{
+            apic_timer::{APIC_TIMER_FREQUENCY, apic_timer_handler},
+            keyboard::{KEYBOARD_IRQ, keyboard_handler},
+            mouse::{MOUSE_IRQ, mouse_handler, set_mouse_kind},
+            register_irq,
+            timer::{TIMER_IRQ, timer_handler},
+            unregister_irq,
+        },
+        isr::set_isr,
+        keyboard_driver::KeyboardDriver,
+        mem::MemoryManager,
+        mouse_driver::{MouseDriver, MouseInitError},
+        pci::{bus::PCIBus, config::ConfigSpace},
+        process_manager::{ProcessManager, Task},
+        ps2::{PS2Controller, PS2Port, init_ps2},
+        smp::wake_application_processors,
+        time,
+        vga_driver::{self, VGAText},
     },
     printer::VGATextWriter,
+    tty::console,
 };
 
 pub enum KernelError {
     NotReady,
     OutOfBounds,
     Busy,
+    OutOfMemory,
 }
 
 pub struct KernelAcc {
@@ -48,17 +69,22 @@ pub struct Kernel {
     mem: spin::Mutex<MemoryManager>,
     pm: spin::Mutex<ProcessManager>,
     keyboard_driver: spin::Mutex<KeyboardDriver>,
+    mouse_driver: Option<MouseDriver>,
     vga_driver: spin::Mutex<VGAText>,
+    pci: spin::Mutex<PCIBus>,
+    ps2: PS2Controller,
 }
 
 impl Kernel {
     pub unsafe fn new() -> Result<Self, ()> {
         unsafe {
-            // Setup interrupt handling
+            // Setup segmentation and interrupt handling
+            set_gdt();
             set_isr();
 
             // Create kernel components
-            let mem = MemoryManager::init();
+            let mut mem = MemoryManager::init();
+            console::init(&mut mem);
 
             // Initialise drivers
             let mut vga_drv = VGAText {};
@@ -69,13 +95,72 @@ impl Kernel {
 
             tty.clear();
 
-            let keyboard_drv = match KeyboardDriver::initialise() {
+            let acpi = ACPI::load();
+            let ps2 = match init_ps2(acpi.as_ref()) {
+                Ok(ps2) => ps2,
+                Err(err) => {
+                    tty.print_ascii("Couldn't initialise the PS/2 controller: ".as_bytes());
+                    print_reason(&mut tty, err.describe());
+                    loop {}
+                }
+            };
+            let keyboard_drv = match KeyboardDriver::initialise(&ps2) {
                 Ok(drv) => drv,
-                Err(_) => {
-                    tty.println_ascii("Couldn't load keyboard driver.".as_bytes());
+                Err(err) => {
+                    tty.print_ascii("Couldn't load keyboard driver: ".as_bytes());
+                    print_reason(&mut tty, err.describe());
                     loop {}
                 }
             };
+            // The mouse is optional, its interrupt is enabled once the handler is registered.
+            let mouse_drv = match MouseDriver::initialise(&ps2) {
+                Ok(drv) => Some(drv),
+                Err(MouseInitError::NoMouse) => None,
+                Err(err) => {
+                    tty.print_ascii("Couldn't load mouse driver: ".as_bytes());
+                    print_reason(&mut tty, err.describe());
+                    None
+                }
+            };
+            // Prefer the APIC when ACPI describes one, the PIC stays in use otherwise.
+            if let Some(acpi) = &acpi
+                && let Some(madt) = MADT::from_acpi(acpi)
+                && apic::init(madt).is_err()
+            {
+                tty.println_ascii("Couldn't enable the APIC, using the PIC.".as_bytes());
+            }
+
+            let mut pci = PCIBus::scan(ConfigSpace::detect(acpi.as_ref()));
+            if pci.register_driver(&vga_driver::PCI_DRIVER).is_err() {
+                tty.println_ascii("Couldn't register the VGA PCI driver.".as_bytes());
+            }
+            time::rtc::init(acpi.as_ref());
+
+            if register_irq(TIMER_IRQ, timer_handler).is_err()
+                || register_irq(KEYBOARD_IRQ, keyboard_handler).is_err()
+            {
+                tty.println_ascii("Couldn't register IRQ handlers.".as_bytes());
+                loop {}
+            }
+            if let Some(mouse) = &mouse_drv {
+                set_mouse_kind(mouse.kind());
+                if register_irq(MOUSE_IRQ, mouse_handler).is_err()
+                    || ps2.enable_interrupt(PS2Port::Second).is_err()
+                {
+                    tty.println_ascii("Couldn't enable the PS/2 mouse.".as_bytes());
+                }
+            }
+            if apic::enabled()
+                && (register_irq(LAPIC_TIMER_IRQ, apic_timer_handler).is_err()
+                    || apic::start_periodic_timer(APIC_TIMER_FREQUENCY).is_none())
+            {
+                // The timer may have failed to calibrate after the handler
+                // was registered.
+                let _ = unregister_irq(LAPIC_TIMER_IRQ, apic_timer_handler);
+                tty.println_ascii("Couldn't start the local APIC timer.".as_bytes());
+            }
+            time::init(time::DEFAULT_FREQUENCY);
+            time::clock::init(acpi.as_ref());
             asm!("sti"); // Sets the enable interrupt flag.
 
             // Cleanup used references to drivers.
@@ -85,9 +170,12 @@ impl Kernel {
             // Done
             Ok(Self {
                 mem: spin::Mutex::new(mem),
-                pm: spin::Mutex::new(ProcessManager {}),
+                pm: spin::Mutex::new(ProcessManager::new()),
                 keyboard_driver: spin::Mutex::new(keyboard_drv),
+                mouse_driver: mouse_drv,
                 vga_driver: spin::Mutex::new(vga_drv),
+                pci: spin::Mutex::new(pci),
+                ps2,
             })
         }
     }
@@ -100,6 +188,13 @@ impl Kernel {
         &self.pm
     }
 
+    /// Queues a task and wakes up idle CPUs to run it.
+    pub fn spawn(&self, task: Task) -> Result<(), KernelError> {
+        self.pm.lock().spawn(task)?;
+        wake_application_processors();
+        Ok(())
+    }
+
     pub fn vga_driver(&self) -> &spin::Mutex<VGAText> {
         &self.vga_driver
     }
@@ -107,4 +202,29 @@ impl Kernel {
     pub fn keyboard_driver(&self) -> &spin::Mutex<KeyboardDriver> {
         &self.keyboard_driver
     }
+
+    pub fn mouse_driver(&self) -> Option<&MouseDriver> {
+        self.mouse_driver.as_ref()
+    }
+
+    pub fn ps2_controller(&self) -> &PS2Controller {
+        &self.ps2
+    }
+
+    pub fn pci_bus(&self) -> &spin::Mutex<PCIBus> {
+        &self.pci
+    }
+}
+
+/// Prints a reason returned by an error's `describe`, followed by the byte
+/// that caused it, if any.
+pub unsafe fn print_reason(tty: &mut VGATextWriter, (reason, byte): (&str, Option<u8>)) {
+    unsafe {
+        tty.print_ascii(reason.as_bytes());
+        if let Some(byte) = byte {
+            tty.print_ascii(" ".as_bytes());
+            tty.print_hex(byte);
+        }
+        tty.nl();
+    }
 }
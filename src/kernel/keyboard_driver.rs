@@ -1,120 +1,568 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
 use crate::{
-    kernel::ports::{Port, read_port_byte},
-    kernel::ps2::{KeyboardInitError, identity_devices},
+    KERNEL,
+    event_bus::EventSubscriber,
+    kernel::{
+        interrupt_handlers::keyboard::KEYBOARD_IRQ,
+        isr::raise_event,
+        keycode::{KeyCode, KeyEvent, Modifiers},
+        layouts::{Layout, Symbol, boot_layout, compose, spacing_accent},
+        ps2::{
+            KeyboardInitError, PS2Controller, PS2DeviceCommand, PS2Error, PS2Port, device_command,
+            identity_devices, read_data, write_device,
+        },
+        time::timers::{self, TimerId},
+    },
+    sys_event::{EventFilter, EventKind, InterruptEvent, SysEvent},
 };
 
-const LOWER_CASE_OFFSET: u8 = 0x20;
+const EXTENDED_PREFIX: u8 = 0xE0;
+/// Starts the 6 byte sequence Pause sends, E1 1D 45 E1 9D C5.
+const PAUSE_PREFIX: u8 = 0xE1;
+const PAUSE_SEQUENCE_LEN: u8 = 5;
+const RELEASE_BIT: u8 = 0x80;
+/// Print Screen and the navigation keys are wrapped in fake shift presses
+/// and releases, E0 2A and E0 36, which are dropped.
+const FAKE_LEFT_SHIFT: u8 = 0x2A;
+const FAKE_RIGHT_SHIFT: u8 = 0x36;
+
+const ACK: u8 = 0xFA;
+const RESEND: u8 = 0xFE;
+const SET_LEDS: u8 = 0xED;
+const LED_SCROLL_LOCK: u8 = 1 << 0;
+const LED_NUM_LOCK: u8 = 1 << 1;
+const LED_CAPS_LOCK: u8 = 1 << 2;
+
+/// Typematic delays, selected by bits 5 and 6 of the typematic byte.
+const TYPEMATIC_DELAYS_MS: [u32; 4] = [250, 500, 750, 1000];
+const TYPEMATIC_DELAY_SHIFT: u8 = 5;
+const TYPEMATIC_RATE_MASK: u8 = 0x1F;
+/// 500 ms and 10.9 repeats a second, what keyboards start with.
+const DEFAULT_TYPEMATIC: u8 = 0x2B;
+
+/// Answers to a scan code set query, by set. With translation on, the
+/// controller translates the answer too.
+const SCAN_CODE_SET_IDS: [(u8, u8); 3] = [(1, 0x43), (2, 0x41), (3, 0x3F)];
+
+const BACKSPACE: char = '\u{8}';
+const ESCAPE: char = '\u{1b}';
+/// Ctrl with a letter types the letter's control code.
+const CONTROL_MASK: u8 = 0x1F;
+
+enum Prefix {
+    None,
+    Extended,
+    /// Bytes of the Pause sequence still to come.
+    Pause(u8),
+}
+
+/// Where the driver is in sending the lock LEDs. The keyboard's answers
+/// arrive through IRQ 1 like scan codes do, so the command is advanced from
+/// `handle_scan_code` rather than by polling.
+#[derive(Clone, Copy)]
+enum LedUpdate {
+    Idle,
+    AwaitingCommandAck,
+    /// Carries the LED byte that was sent.
+    AwaitingDataAck(u8),
+}
+
+pub enum KeyboardError {
+    Device(PS2Error),
+    /// The driver only decodes set 1, which the keyboard sends either itself
+    /// or through the controller's translation of set 2.
+    UnsupportedSet(u8),
+    /// The keyboard answered a scan code set query with this.
+    UnknownSet(u8),
+}
+
+impl KeyboardError {
+    pub fn describe(&self) -> (&'static str, Option<u8>) {
+        match self {
+            KeyboardError::Device(err) => err.describe(),
+            KeyboardError::UnsupportedSet(set) => ("unsupported scan code set", Some(*set)),
+            KeyboardError::UnknownSet(reply) => ("unknown scan code set", Some(*reply)),
+        }
+    }
+}
+
+impl From<PS2Error> for KeyboardError {
+    fn from(err: PS2Error) -> Self {
+        Self::Device(err)
+    }
+}
+
+/// Who repeats held keys.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RepeatMode {
+    /// The keyboard sends the make code again, at its typematic rate.
+    Hardware,
+    /// The keyboard's repeats are dropped and a kernel timer repeats the key
+    /// with the same delay and rate instead.
+    Software,
+}
+
+/// The delay before a held key repeats and the rate it repeats at.
+#[derive(Clone, Copy)]
+pub struct Typematic {
+    pub delay_ms: u32,
+    /// In repeats per 10 seconds, as the rates aren't whole numbers.
+    pub rate_decihertz: u32,
+}
+
+impl Typematic {
+    fn from_byte(byte: u8) -> Self {
+        let delay = (byte >> TYPEMATIC_DELAY_SHIFT) as usize;
+        Self {
+            delay_ms: TYPEMATIC_DELAYS_MS.get(delay).copied().unwrap_or(0),
+            rate_decihertz: 10_000_000 / period_us(byte & TYPEMATIC_RATE_MASK),
+        }
+    }
+
+    pub fn period_ms(&self) -> u32 {
+        10_000 / self.rate_decihertz.max(1)
+    }
+}
+
+/// The repeat period of a typematic rate setting. Bits 0 to 2 and 3 to 4 hold
+/// A and B of (8 + A) * 2^B * 4.17 ms.
+fn period_us(rate: u8) -> u32 {
+    ((8 + (rate & 0x7) as u32) << ((rate >> 3) & 0x3)) * 4167
+}
+
+/// The typematic byte closest to a delay and a rate.
+fn typematic_byte(delay_ms: u32, rate_decihertz: u32) -> u8 {
+    let delay = (0..TYPEMATIC_DELAYS_MS.len())
+        .min_by_key(|i| {
+            TYPEMATIC_DELAYS_MS
+                .get(*i)
+                .map_or(u32::MAX, |d| d.abs_diff(delay_ms))
+        })
+        .unwrap_or(0) as u8;
+    let period = 10_000_000 / rate_decihertz.max(1);
+    let rate = (0..=TYPEMATIC_RATE_MASK)
+        .min_by_key(|rate| period_us(*rate).abs_diff(period))
+        .unwrap_or(0);
+    delay << TYPEMATIC_DELAY_SHIFT | rate
+}
 
 /// A driver for a generic PS/2 connected keyboard.
 pub struct KeyboardDriver {
     b1: u8,
     b2: u8,
-    shift_offset: u8,
+    prefix: Prefix,
+    modifiers: Modifiers,
+    /// Lock keys held down, so they toggle once per press and not on repeats.
+    locks_held: Modifiers,
+    leds: LedUpdate,
+    layout: &'static Layout,
+    /// A dead key waiting for the key it modifies.
+    dead_key: Option<char>,
+    controller: PS2Controller,
+    typematic: u8,
+    repeat_mode: RepeatMode,
+    /// The key repeated in software mode, while it is held.
+    held: Option<KeyCode>,
 }
 
 impl KeyboardDriver {
     /// Initialise the driver by reading the PS/2 connection and
     /// identifying the device for mapping inputs.
-    pub unsafe fn initialise() -> Result<Self, KeyboardInitError> {
-        unsafe {
-            let (b1, b2) = identity_devices()?;
-            Ok(Self {
-                b1,
-                b2,
-                shift_offset: LOWER_CASE_OFFSET,
-            })
+    pub fn initialise(controller: &PS2Controller) -> Result<Self, KeyboardInitError> {
+        if !controller.is_working(PS2Port::First) {
+            return Err(KeyboardInitError::NoKeyboard);
         }
+        let (b1, b2) = identity_devices()?;
+        // The locks start off, bring the LEDs in line in case the firmware
+        // left some on. Not every keyboard has LEDs, so failing is fine.
+        let _ = device_command(PS2Port::First, SET_LEDS)
+            .and_then(|_| device_command(PS2Port::First, 0));
+        Ok(Self {
+            b1,
+            b2,
+            prefix: Prefix::None,
+            modifiers: Modifiers::none(),
+            locks_held: Modifiers::none(),
+            leds: LedUpdate::Idle,
+            layout: boot_layout(),
+            dead_key: None,
+            controller: *controller,
+            typematic: DEFAULT_TYPEMATIC,
+            repeat_mode: RepeatMode::Hardware,
+            held: None,
+        })
     }
 
-    /// Handle a keyboard interrupt (IRQ1). This function will
-    /// read the input on the data port and parse it.
-    pub fn keyboard_interrupt_handler(&mut self) -> Option<u8> {
-        let scan_code = read_port_byte(Port::PS2DataPort.into());
-        self.letter_from_scan_code(scan_code)
-    }
-
-    fn letter_from_scan_code(&mut self, scan_code: u8) -> Option<u8> {
+    /// Handle a scan code read by the keyboard interrupt (IRQ1) and parse it.
+    /// Returns an event once a whole key sequence has arrived. Pause has no
+    /// release sequence, so it only produces a press.
+    pub fn handle_scan_code(&mut self, scan_code: u8) -> Option<KeyEvent> {
         match scan_code {
-            0x01 => None, // Escape
-            0x00..0x02 => Some(b'X'),
-            0x02..=0x0b => Some(
-                if self.shift_offset == LOWER_CASE_OFFSET {
-                    b'0' + (scan_code - 0x02 + 1) % 10
+            ACK => {
+                self.advance_leds();
+                return None;
+            }
+            RESEND => {
+                self.resend_leds();
+                return None;
+            }
+            _ => {}
+        }
+
+        let extended = match self.prefix {
+            Prefix::Pause(remaining) => {
+                if remaining > 1 {
+                    self.prefix = Prefix::Pause(remaining - 1);
+                    return None;
+                }
+                self.prefix = Prefix::None;
+                return Some(self.event(KeyCode::Pause, true));
+            }
+            Prefix::Extended => {
+                self.prefix = Prefix::None;
+                true
+            }
+            Prefix::None => match scan_code {
+                EXTENDED_PREFIX => {
+                    self.prefix = Prefix::Extended;
+                    return None;
+                }
+                PAUSE_PREFIX => {
+                    self.prefix = Prefix::Pause(PAUSE_SEQUENCE_LEN);
+                    return None;
+                }
+                _ => false,
+            },
+        };
+
+        let code = scan_code & !RELEASE_BIT;
+        if extended && (code == FAKE_LEFT_SHIFT || code == FAKE_RIGHT_SHIFT) {
+            return None;
+        }
+        let key = KeyCode::from_set1(code, extended)?;
+        let pressed = scan_code & RELEASE_BIT == 0;
+
+        if let Some(modifier) = key.modifier() {
+            self.modifiers = if pressed {
+                self.modifiers.with(modifier)
+            } else {
+                self.modifiers.without(modifier)
+            };
+        }
+        if let Some(lock) = key.lock() {
+            if pressed && !self.locks_held.contains(lock) {
+                self.locks_held = self.locks_held.with(lock);
+                self.modifiers = if self.modifiers.contains(lock) {
+                    self.modifiers.without(lock)
                 } else {
-                    match scan_code {
-                        0x02 => b'!',
-                        0x03 => b'@',
-                        0x04 => b'#',
-                        0x05 => b'$',
-                        0x06 => b'%',
-                        0x07 => b'^',
-                        0x08 => b'&',
-                        0x09 => b'*',
-                        0x0a => b'(',
-                        0x0b => b')',
-                        _ => b' '
-                    }
-                }                
-            ),
-            0x0c => Some(b'-'),
-            0x0d => Some(b'='),
-            0x0e => Some(0x08), // Backspace
-            0x0f => Some('\t' as u8),
-            0x10 => Some(b'Q' + self.shift_offset),
-            0x11 => Some(b'W' + self.shift_offset),
-            0x12 => Some(b'E' + self.shift_offset),
-            0x13 => Some(b'R' + self.shift_offset),
-            0x14 => Some(b'T' + self.shift_offset),
-            0x15 => Some(b'Y' + self.shift_offset),
-            0x16 => Some(b'U' + self.shift_offset),
-            0x17 => Some(b'I' + self.shift_offset),
-            0x18 => Some(b'O' + self.shift_offset),
-            0x19 => Some(b'P' + self.shift_offset),
-            0x1a => Some(b'['),
-            0x1b => Some(b']'),
-            0x1c => Some('\n' as u8),
-            0x1e => Some(b'A' + self.shift_offset),
-            0x1f => Some(b'S' + self.shift_offset),
-            0x20 => Some(b'D' + self.shift_offset),
-            0x21 => Some(b'F' + self.shift_offset),
-            0x22 => Some(b'G' + self.shift_offset),
-            0x23 => Some(b'H' + self.shift_offset),
-            0x24 => Some(b'J' + self.shift_offset),
-            0x25 => Some(b'K' + self.shift_offset),
-            0x26 => Some(b'L' + self.shift_offset),
-            0x27 => Some(b';'),
-            0x28 => Some('\'' as u8),
-            0x29 => Some(b'`'),
-            0x2a => {
-                self.shift_offset = 0;
-                None
-            } // Left shift
-            0x2b => Some('\\' as u8),
-            0x2c => Some(b'Z' + self.shift_offset),
-            0x2d => Some(b'X' + self.shift_offset),
-            0x2e => Some(b'C' + self.shift_offset),
-            0x2f => Some(b'V' + self.shift_offset),
-            0x30 => Some(b'B' + self.shift_offset),
-            0x31 => Some(b'N' + self.shift_offset),
-            0x32 => Some(b'M' + self.shift_offset),
-            0x33 => Some(b','),
-            0x34 => Some(b'.'),
-            0x35 => Some(b'/'),
-            0x36 => {
-                self.shift_offset = 0;
-                None // Right shift
+                    self.modifiers.with(lock)
+                };
+                self.send_leds();
+            } else if !pressed {
+                self.locks_held = self.locks_held.without(lock);
+            }
+        }
+
+        if self.repeat_mode == RepeatMode::Software
+            && key.modifier().is_none()
+            && key.lock().is_none()
+        {
+            match (pressed, self.held) {
+                // The keyboard repeating the key itself.
+                (true, Some(held)) if held == key => return None,
+                (true, _) => {
+                    self.held = Some(key);
+                    let typematic = self.typematic();
+                    start_repeat(typematic.delay_ms, typematic.period_ms());
+                }
+                (false, Some(held)) if held == key => {
+                    self.held = None;
+                    stop_repeat();
+                }
+                (false, _) => {}
+            }
+        }
+
+        Some(self.event(key, pressed))
+    }
+
+    fn event(&self, code: KeyCode, pressed: bool) -> KeyEvent {
+        KeyEvent {
+            code,
+            pressed,
+            modifiers: self.modifiers,
+        }
+    }
+
+    /// The event for a software repeat of the held key, if it is still held.
+    pub fn repeat(&self) -> Option<KeyEvent> {
+        self.held.map(|key| self.event(key, true))
+    }
+
+    pub fn typematic(&self) -> Typematic {
+        Typematic::from_byte(self.typematic)
+    }
+
+    /// Sets the typematic delay and rate to the closest the keyboard
+    /// supports, 250 to 1000 ms and 2 to 30 repeats a second. Software
+    /// repeats use them too.
+    pub fn set_typematic(
+        &mut self,
+        delay_ms: u32,
+        rate_decihertz: u32,
+    ) -> Result<(), KeyboardError> {
+        let typematic = typematic_byte(delay_ms, rate_decihertz);
+        self.polled(|| {
+            device_command(PS2Port::First, PS2DeviceCommand::SetRate as u8)?;
+            device_command(PS2Port::First, typematic)
+        })?;
+        self.typematic = typematic;
+        Ok(())
+    }
+
+    pub fn repeat_mode(&self) -> RepeatMode {
+        self.repeat_mode
+    }
+
+    pub fn set_repeat_mode(&mut self, mode: RepeatMode) {
+        self.repeat_mode = mode;
+        self.held = None;
+        stop_repeat();
+    }
+
+    /// Asks the keyboard which scan code set it sends.
+    pub fn scan_code_set(&mut self) -> Result<u8, KeyboardError> {
+        let id = self.polled(|| {
+            device_command(PS2Port::First, PS2DeviceCommand::ScanCodeSet as u8)?;
+            device_command(PS2Port::First, 0)?;
+            read_data()
+        })?;
+        SCAN_CODE_SET_IDS
+            .iter()
+            .find(|(set, translated)| id == *set || id == *translated)
+            .map(|(set, _)| *set)
+            .ok_or(KeyboardError::UnknownSet(id))
+    }
+
+    /// Switches the keyboard to scan code set 1 or 2. The controller
+    /// translates set 2 to set 1 and is told to stop doing so for set 1, so
+    /// the driver receives set 1 either way. Set 3 can't be translated.
+    pub fn set_scan_code_set(&mut self, set: u8) -> Result<(), KeyboardError> {
+        let translation = match set {
+            1 => false,
+            2 => true,
+            _ => return Err(KeyboardError::UnsupportedSet(set)),
+        };
+        let controller = self.controller;
+        self.polled(|| {
+            device_command(PS2Port::First, PS2DeviceCommand::ScanCodeSet as u8)?;
+            device_command(PS2Port::First, set)?;
+            controller.set_translation(translation)
+        })?;
+        self.prefix = Prefix::None;
+        Ok(())
+    }
+
+    /// Runs a command that polls the keyboard for its answers, with the
+    /// keyboard's interrupt disabled so the interrupt handler can't take them.
+    /// An LED update underway is abandoned, since its ACK would be lost.
+    fn polled<T>(&mut self, command: impl FnOnce() -> Result<T, PS2Error>) -> Result<T, PS2Error> {
+        self.leds = LedUpdate::Idle;
+        self.controller.disable_interrupt(PS2Port::First)?;
+        let result = command();
+        self.controller.enable_interrupt(PS2Port::First)?;
+        result
+    }
+
+    pub fn layout(&self) -> &'static Layout {
+        self.layout
+    }
+
+    pub fn set_layout(&mut self, layout: &'static Layout) {
+        self.layout = layout;
+        self.dead_key = None;
+    }
+
+    /// Translates a key press into the character it types on the current
+    /// layout. A dead key types nothing and is combined with the next key
+    /// instead; if the two don't combine, the accent is dropped. Followed by
+    /// space or by itself, a dead key types its accent.
+    pub fn translate(&mut self, event: &KeyEvent) -> Option<char> {
+        if !event.pressed {
+            return None;
+        }
+        let modifiers = event.modifiers;
+        if let Some(c) = fixed_char(event.code, modifiers.num_lock()) {
+            return Some(c);
+        }
+        let symbol = if event.code == KeyCode::Space {
+            Symbol::Char(' ')
+        } else {
+            self.layout.symbol(
+                event.code,
+                modifiers.shift(),
+                modifiers.alt_gr(),
+                modifiers.caps_lock(),
+            )?
+        };
+        let c = match (symbol, self.dead_key.take()) {
+            (Symbol::Dead(dead), Some(pending)) if dead == pending => {
+                return spacing_accent(dead);
+            }
+            (Symbol::Dead(dead), _) => {
+                self.dead_key = Some(dead);
+                return None;
+            }
+            (Symbol::Char(' '), Some(pending)) => spacing_accent(pending)?,
+            (Symbol::Char(c), Some(pending)) => compose(pending, c).unwrap_or(c),
+            (Symbol::Char(c), None) => c,
+        };
+        if modifiers.ctrl() && c.is_ascii_alphabetic() {
+            return Some((c as u8 & CONTROL_MASK) as char);
+        }
+        Some(c)
+    }
+
+    fn led_byte(&self) -> u8 {
+        let mut leds = 0;
+        if self.modifiers.scroll_lock() {
+            leds |= LED_SCROLL_LOCK;
+        }
+        if self.modifiers.num_lock() {
+            leds |= LED_NUM_LOCK;
+        }
+        if self.modifiers.caps_lock() {
+            leds |= LED_CAPS_LOCK;
+        }
+        leds
+    }
+
+    /// Starts sending the LEDs. If an update is already underway, another is
+    /// started once it finishes if the locks changed meanwhile.
+    fn send_leds(&mut self) {
+        if let LedUpdate::Idle = self.leds
+            && write_device(PS2Port::First, SET_LEDS).is_ok()
+        {
+            self.leds = LedUpdate::AwaitingCommandAck;
+        }
+    }
+
+    fn advance_leds(&mut self) {
+        match self.leds {
+            LedUpdate::AwaitingCommandAck => {
+                let leds = self.led_byte();
+                self.leds = match write_device(PS2Port::First, leds) {
+                    Ok(()) => LedUpdate::AwaitingDataAck(leds),
+                    Err(_) => LedUpdate::Idle,
+                };
             }
-            0x39 => Some(b' '),
-            0x3b..=0x44 => None, // F1 through F10
-            0x53 => None,        // Delete
-            0x57..=0x58 => None, // F11, F12
-            0x1c..0x80 => Some(b'X'),
-            // Release keys
-            0xAA | 0xB6 => {
-                self.shift_offset = LOWER_CASE_OFFSET;
-                None
+            LedUpdate::AwaitingDataAck(sent) => {
+                self.leds = LedUpdate::Idle;
+                if sent != self.led_byte() {
+                    self.send_leds();
+                }
             }
-            0x80..=0xFF => None, // Release
+            LedUpdate::Idle => {}
+        }
+    }
+
+    fn resend_leds(&mut self) {
+        let sent = match self.leds {
+            LedUpdate::AwaitingCommandAck => write_device(PS2Port::First, SET_LEDS),
+            LedUpdate::AwaitingDataAck(sent) => write_device(PS2Port::First, sent),
+            LedUpdate::Idle => return,
+        };
+        if sent.is_err() {
+            self.leds = LedUpdate::Idle;
+        }
+    }
+}
+
+/// The characters of keys that type the same on every layout.
+fn fixed_char(code: KeyCode, num_lock: bool) -> Option<char> {
+    use KeyCode::*;
+    let c = match code {
+        Escape => ESCAPE,
+        Backspace => BACKSPACE,
+        Tab => '\t',
+        Enter | KeypadEnter => '\n',
+        KeypadStar => '*',
+        KeypadMinus => '-',
+        KeypadPlus => '+',
+        KeypadSlash => '/',
+        _ if code.is_keypad_number() && !num_lock => return None,
+        Keypad0 => '0',
+        Keypad1 => '1',
+        Keypad2 => '2',
+        Keypad3 => '3',
+        Keypad4 => '4',
+        Keypad5 => '5',
+        Keypad6 => '6',
+        Keypad7 => '7',
+        Keypad8 => '8',
+        Keypad9 => '9',
+        KeypadDot => '.',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// The software repeat timer: a one-shot timer for the delay, then a periodic
+/// one for the rate.
+static REPEAT_TIMER: spin::Mutex<Option<TimerId>> = spin::Mutex::new(None);
+static REPEAT_PERIOD_MS: AtomicU32 = AtomicU32::new(0);
+
+fn start_repeat(delay_ms: u32, period_ms: u32) {
+    stop_repeat();
+    REPEAT_PERIOD_MS.store(period_ms, Ordering::Relaxed);
+    *REPEAT_TIMER.lock() = timers::add_oneshot(delay_ms, repeat_delay_elapsed).ok();
+}
+
+fn stop_repeat() {
+    if let Some(timer) = REPEAT_TIMER.lock().take() {
+        let _ = timers::cancel(timer);
+    }
+}
+
+fn repeat_delay_elapsed() {
+    raise_repeat();
+    let period = REPEAT_PERIOD_MS.load(Ordering::Relaxed);
+    *REPEAT_TIMER.lock() = timers::add_periodic(period, raise_repeat).ok();
+}
+
+/// Runs from the kernel's main loop like all timer callbacks, so the driver
+/// isn't locked already.
+fn raise_repeat() {
+    let Ok(kernel) = KERNEL.get() else {
+        return;
+    };
+    let key = kernel.keyboard_driver().lock().repeat();
+    if let Some(key) = key {
+        raise_event(SysEvent::Key(key), KEYBOARD_IRQ);
+    }
+}
+
+/// The events `KeyDecoder` needs to receive.
+pub const KEY_DECODER_EVENTS: EventFilter = EventFilter::none().with(EventKind::Scancode);
+
+/// Feeds the scan codes the keyboard interrupt puts on the event bus to the
+/// driver and raises a `SysEvent::Key` for every key sequence completed.
+/// The interrupt handler can't do this itself, as the driver may be locked
+/// by the code it interrupted.
+pub struct KeyDecoder;
+
+impl EventSubscriber for KeyDecoder {
+    unsafe fn on_event(&mut self, event: &InterruptEvent) {
+        let SysEvent::Scancode { scancode } = event.event else {
+            return;
+        };
+        let Ok(kernel) = KERNEL.get() else {
+            return;
+        };
+        let key = kernel.keyboard_driver().lock().handle_scan_code(scancode);
+        if let Some(key) = key {
+            raise_event(SysEvent::Key(key), event.device);
         }
     }
 }
@@ -0,0 +1,342 @@
+// Key codes and events
+//
+// A key code names a physical key, independent of the layout. Its value is the
+// key's scan code set 1 make code, with bit 7 set for the keys that are sent
+// behind an 0xE0 prefix; set 1 only uses bit 7 to mark releases, so the two
+// never clash.
+
+/// Set by `KeyCode` values of keys sent behind an 0xE0 prefix.
+pub const EXTENDED: u8 = 0x80;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum KeyCode {
+    Escape = 0x01,
+    Key1 = 0x02,
+    Key2 = 0x03,
+    Key3 = 0x04,
+    Key4 = 0x05,
+    Key5 = 0x06,
+    Key6 = 0x07,
+    Key7 = 0x08,
+    Key8 = 0x09,
+    Key9 = 0x0A,
+    Key0 = 0x0B,
+    Minus = 0x0C,
+    Equals = 0x0D,
+    Backspace = 0x0E,
+    Tab = 0x0F,
+    Q = 0x10,
+    W = 0x11,
+    E = 0x12,
+    R = 0x13,
+    T = 0x14,
+    Y = 0x15,
+    U = 0x16,
+    I = 0x17,
+    O = 0x18,
+    P = 0x19,
+    LeftBracket = 0x1A,
+    RightBracket = 0x1B,
+    Enter = 0x1C,
+    LeftCtrl = 0x1D,
+    A = 0x1E,
+    S = 0x1F,
+    D = 0x20,
+    F = 0x21,
+    G = 0x22,
+    H = 0x23,
+    J = 0x24,
+    K = 0x25,
+    L = 0x26,
+    Semicolon = 0x27,
+    Quote = 0x28,
+    Backtick = 0x29,
+    LeftShift = 0x2A,
+    Backslash = 0x2B,
+    Z = 0x2C,
+    X = 0x2D,
+    C = 0x2E,
+    V = 0x2F,
+    B = 0x30,
+    N = 0x31,
+    M = 0x32,
+    Comma = 0x33,
+    Period = 0x34,
+    Slash = 0x35,
+    RightShift = 0x36,
+    KeypadStar = 0x37,
+    LeftAlt = 0x38,
+    Space = 0x39,
+    CapsLock = 0x3A,
+    F1 = 0x3B,
+    F2 = 0x3C,
+    F3 = 0x3D,
+    F4 = 0x3E,
+    F5 = 0x3F,
+    F6 = 0x40,
+    F7 = 0x41,
+    F8 = 0x42,
+    F9 = 0x43,
+    F10 = 0x44,
+    NumLock = 0x45,
+    ScrollLock = 0x46,
+    Keypad7 = 0x47,
+    Keypad8 = 0x48,
+    Keypad9 = 0x49,
+    KeypadMinus = 0x4A,
+    Keypad4 = 0x4B,
+    Keypad5 = 0x4C,
+    Keypad6 = 0x4D,
+    KeypadPlus = 0x4E,
+    Keypad1 = 0x4F,
+    Keypad2 = 0x50,
+    Keypad3 = 0x51,
+    Keypad0 = 0x52,
+    KeypadDot = 0x53,
+    /// The key between left shift and Z on ISO keyboards.
+    NonUSBackslash = 0x56,
+    F11 = 0x57,
+    F12 = 0x58,
+    KeypadEnter = EXTENDED | 0x1C,
+    RightCtrl = EXTENDED | 0x1D,
+    KeypadSlash = EXTENDED | 0x35,
+    PrintScreen = EXTENDED | 0x37,
+    /// AltGr on most non-US layouts.
+    RightAlt = EXTENDED | 0x38,
+    /// Sent as 0xE0 0x46 when pressed with Ctrl, as Break.
+    Pause = EXTENDED | 0x46,
+    Home = EXTENDED | 0x47,
+    Up = EXTENDED | 0x48,
+    PageUp = EXTENDED | 0x49,
+    Left = EXTENDED | 0x4B,
+    Right = EXTENDED | 0x4D,
+    End = EXTENDED | 0x4F,
+    Down = EXTENDED | 0x50,
+    PageDown = EXTENDED | 0x51,
+    Insert = EXTENDED | 0x52,
+    Delete = EXTENDED | 0x53,
+    LeftGUI = EXTENDED | 0x5B,
+    RightGUI = EXTENDED | 0x5C,
+    Menu = EXTENDED | 0x5D,
+}
+
+impl KeyCode {
+    /// The key for a make code, without its release bit. `extended` is set
+    /// when the code followed an 0xE0 prefix.
+    pub fn from_set1(code: u8, extended: bool) -> Option<Self> {
+        use KeyCode::*;
+        let key = match (extended, code) {
+            (false, 0x01) => Escape,
+            (false, 0x02) => Key1,
+            (false, 0x03) => Key2,
+            (false, 0x04) => Key3,
+            (false, 0x05) => Key4,
+            (false, 0x06) => Key5,
+            (false, 0x07) => Key6,
+            (false, 0x08) => Key7,
+            (false, 0x09) => Key8,
+            (false, 0x0A) => Key9,
+            (false, 0x0B) => Key0,
+            (false, 0x0C) => Minus,
+            (false, 0x0D) => Equals,
+            (false, 0x0E) => Backspace,
+            (false, 0x0F) => Tab,
+            (false, 0x10) => Q,
+            (false, 0x11) => W,
+            (false, 0x12) => E,
+            (false, 0x13) => R,
+            (false, 0x14) => T,
+            (false, 0x15) => Y,
+            (false, 0x16) => U,
+            (false, 0x17) => I,
+            (false, 0x18) => O,
+            (false, 0x19) => P,
+            (false, 0x1A) => LeftBracket,
+            (false, 0x1B) => RightBracket,
+            (false, 0x1C) => Enter,
+            (false, 0x1D) => LeftCtrl,
+            (false, 0x1E) => A,
+            (false, 0x1F) => S,
+            (false, 0x20) => D,
+            (false, 0x21) => F,
+            (false, 0x22) => G,
+            (false, 0x23) => H,
+            (false, 0x24) => J,
+            (false, 0x25) => K,
+            (false, 0x26) => L,
+            (false, 0x27) => Semicolon,
+            (false, 0x28) => Quote,
+            (false, 0x29) => Backtick,
+            (false, 0x2A) => LeftShift,
+            (false, 0x2B) => Backslash,
+            (false, 0x2C) => Z,
+            (false, 0x2D) => X,
+            (false, 0x2E) => C,
+            (false, 0x2F) => V,
+            (false, 0x30) => B,
+            (false, 0x31) => N,
+            (false, 0x32) => M,
+            (false, 0x33) => Comma,
+            (false, 0x34) => Period,
+            (false, 0x35) => Slash,
+            (false, 0x36) => RightShift,
+            (false, 0x37) => KeypadStar,
+            (false, 0x38) => LeftAlt,
+            (false, 0x39) => Space,
+            (false, 0x3A) => CapsLock,
+            (false, 0x3B) => F1,
+            (false, 0x3C) => F2,
+            (false, 0x3D) => F3,
+            (false, 0x3E) => F4,
+            (false, 0x3F) => F5,
+            (false, 0x40) => F6,
+            (false, 0x41) => F7,
+            (false, 0x42) => F8,
+            (false, 0x43) => F9,
+            (false, 0x44) => F10,
+            (false, 0x45) => NumLock,
+            (false, 0x46) => ScrollLock,
+            (false, 0x47) => Keypad7,
+            (false, 0x48) => Keypad8,
+            (false, 0x49) => Keypad9,
+            (false, 0x4A) => KeypadMinus,
+            (false, 0x4B) => Keypad4,
+            (false, 0x4C) => Keypad5,
+            (false, 0x4D) => Keypad6,
+            (false, 0x4E) => KeypadPlus,
+            (false, 0x4F) => Keypad1,
+            (false, 0x50) => Keypad2,
+            (false, 0x51) => Keypad3,
+            (false, 0x52) => Keypad0,
+            (false, 0x53) => KeypadDot,
+            (false, 0x56) => NonUSBackslash,
+            (false, 0x57) => F11,
+            (false, 0x58) => F12,
+            (true, 0x1C) => KeypadEnter,
+            (true, 0x1D) => RightCtrl,
+            (true, 0x35) => KeypadSlash,
+            (true, 0x37) => PrintScreen,
+            (true, 0x38) => RightAlt,
+            (true, 0x46) => Pause,
+            (true, 0x47) => Home,
+            (true, 0x48) => Up,
+            (true, 0x49) => PageUp,
+            (true, 0x4B) => Left,
+            (true, 0x4D) => Right,
+            (true, 0x4F) => End,
+            (true, 0x50) => Down,
+            (true, 0x51) => PageDown,
+            (true, 0x52) => Insert,
+            (true, 0x53) => Delete,
+            (true, 0x5B) => LeftGUI,
+            (true, 0x5C) => RightGUI,
+            (true, 0x5D) => Menu,
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    /// The modifier a key holds down, if it is one.
+    pub fn modifier(&self) -> Option<Modifiers> {
+        match self {
+            KeyCode::LeftShift => Some(Modifiers::LEFT_SHIFT),
+            KeyCode::RightShift => Some(Modifiers::RIGHT_SHIFT),
+            KeyCode::LeftCtrl => Some(Modifiers::LEFT_CTRL),
+            KeyCode::RightCtrl => Some(Modifiers::RIGHT_CTRL),
+            KeyCode::LeftAlt => Some(Modifiers::LEFT_ALT),
+            KeyCode::RightAlt => Some(Modifiers::RIGHT_ALT),
+            KeyCode::LeftGUI => Some(Modifiers::LEFT_GUI),
+            KeyCode::RightGUI => Some(Modifiers::RIGHT_GUI),
+            _ => None,
+        }
+    }
+
+    /// The lock a key toggles, if it is one.
+    pub fn lock(&self) -> Option<Modifiers> {
+        match self {
+            KeyCode::CapsLock => Some(Modifiers::CAPS_LOCK),
+            KeyCode::NumLock => Some(Modifiers::NUM_LOCK),
+            KeyCode::ScrollLock => Some(Modifiers::SCROLL_LOCK),
+            _ => None,
+        }
+    }
+
+    /// Whether a key is on the keypad and types a digit or the decimal point
+    /// while Num Lock is on.
+    pub fn is_keypad_number(&self) -> bool {
+        (KeyCode::Keypad7 as u8..=KeyCode::KeypadDot as u8).contains(&(*self as u8))
+            && !matches!(self, KeyCode::KeypadMinus | KeyCode::KeypadPlus)
+    }
+}
+
+/// The modifiers held down and the locks turned on when a key event happened.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers(u16);
+
+impl Modifiers {
+    pub const LEFT_SHIFT: Self = Self(1 << 0);
+    pub const RIGHT_SHIFT: Self = Self(1 << 1);
+    pub const LEFT_CTRL: Self = Self(1 << 2);
+    pub const RIGHT_CTRL: Self = Self(1 << 3);
+    pub const LEFT_ALT: Self = Self(1 << 4);
+    pub const RIGHT_ALT: Self = Self(1 << 5);
+    pub const LEFT_GUI: Self = Self(1 << 6);
+    pub const RIGHT_GUI: Self = Self(1 << 7);
+    pub const CAPS_LOCK: Self = Self(1 << 8);
+    pub const NUM_LOCK: Self = Self(1 << 9);
+    pub const SCROLL_LOCK: Self = Self(1 << 10);
+
+    pub const fn none() -> Self {
+        Self(0)
+    }
+
+    pub const fn with(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn without(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Whether any of the modifiers in `other` is set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn shift(&self) -> bool {
+        self.contains(Self::LEFT_SHIFT.with(Self::RIGHT_SHIFT))
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.contains(Self::LEFT_CTRL.with(Self::RIGHT_CTRL))
+    }
+
+    pub fn alt(&self) -> bool {
+        self.contains(Self::LEFT_ALT)
+    }
+
+    pub fn alt_gr(&self) -> bool {
+        self.contains(Self::RIGHT_ALT)
+    }
+
+    pub fn caps_lock(&self) -> bool {
+        self.contains(Self::CAPS_LOCK)
+    }
+
+    pub fn num_lock(&self) -> bool {
+        self.contains(Self::NUM_LOCK)
+    }
+
+    pub fn scroll_lock(&self) -> bool {
+        self.contains(Self::SCROLL_LOCK)
+    }
+}
+
+/// A key going down or up, with the modifiers in effect after it.
+#[derive(Clone, Copy)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool,
+    pub modifiers: Modifiers,
+}
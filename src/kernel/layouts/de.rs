@@ -0,0 +1,28 @@
+use super::Layout;
+
+/// German QWERTZ. ^ and ´ are dead keys, as is ` on shift and ´.
+pub const DE: Layout = Layout {
+    name: "de",
+    description: "German QWERTZ",
+    levels: [
+        [
+            "\u{302}1234567890ß\u{301}",
+            "qwertzuiopü+",
+            "asdfghjklöä#",
+            "<yxcvbnm,.-",
+        ],
+        [
+            "°!\"§$%&/()=?\u{300}",
+            "QWERTZUIOPÜ*",
+            "ASDFGHJKLÖÄ'",
+            ">YXCVBNM;:_",
+        ],
+        [
+            "\0\0²³\0\0\0{[]}\\\0",
+            "@\0€\0\0\0\0\0\0\0\0~",
+            "",
+            "|\0\0\0\0\0\0µ\0\0\0",
+        ],
+        ["", "", "", ""],
+    ],
+};
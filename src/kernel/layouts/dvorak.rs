@@ -0,0 +1,22 @@
+use super::Layout;
+
+pub const DVORAK: Layout = Layout {
+    name: "dvorak",
+    description: "US Dvorak",
+    levels: [
+        [
+            "`1234567890[]",
+            "',.pyfgcrl/=",
+            "aoeuidhtns-\\",
+            "\\;qjkxbmwvz",
+        ],
+        [
+            "~!@#$%^&*(){}",
+            "\"<>PYFGCRL?+",
+            "AOEUIDHTNS_|",
+            "|:QJKXBMWVZ",
+        ],
+        ["", "", "", ""],
+        ["", "", "", ""],
+    ],
+};
@@ -0,0 +1,28 @@
+use super::Layout;
+
+/// French AZERTY. ^ and ¨ are dead keys, as are ~ and ` on AltGr.
+pub const FR: Layout = Layout {
+    name: "fr",
+    description: "French AZERTY",
+    levels: [
+        [
+            "²&é\"'(-è_çà)=",
+            "azertyuiop\u{302}$",
+            "qsdfghjklmù*",
+            "<wxcvbn,;:!",
+        ],
+        [
+            "\x001234567890°+",
+            "AZERTYUIOP\u{308}£",
+            "QSDFGHJKLM%µ",
+            ">WXCVBN?./§",
+        ],
+        [
+            "\0\0\u{303}#{[|\u{300}\\^@]}",
+            "\0\0€\0\0\0\0\0\0\0\0¤",
+            "",
+            "",
+        ],
+        ["", "", "", ""],
+    ],
+};
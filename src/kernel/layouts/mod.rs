@@ -0,0 +1,178 @@
+// Keyboard layouts
+//
+// A layout gives the symbols the character keys type, at four levels: plain,
+// with shift, with AltGr and with shift and AltGr. Each level is written as
+// four strings, one per row of keys, with a character per key in the order of
+// `ROWS`; `\0` marks a key that types nothing at that level and an empty
+// string a row that types nothing at all. Keys outside the rows, like Enter
+// or the keypad, type the same on every layout and are left to the driver.
+//
+// Dead keys are written as the combining form of their accent. They type
+// nothing themselves and change the next key typed instead.
+
+pub mod de;
+pub mod dvorak;
+pub mod fr;
+pub mod uk;
+pub mod us;
+
+use crate::kernel::keycode::KeyCode;
+
+/// The keys covered by layouts, by row.
+const ROWS: [&[KeyCode]; 4] = {
+    use KeyCode::*;
+    [
+        &[
+            Backtick, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, Minus, Equals,
+        ],
+        &[Q, W, E, R, T, Y, U, I, O, P, LeftBracket, RightBracket],
+        &[A, S, D, F, G, H, J, K, L, Semicolon, Quote, Backslash],
+        &[NonUSBackslash, Z, X, C, V, B, N, M, Comma, Period, Slash],
+    ]
+};
+
+const NO_SYMBOL: char = '\0';
+
+const DEAD_GRAVE: char = '\u{300}';
+const DEAD_ACUTE: char = '\u{301}';
+const DEAD_CIRCUMFLEX: char = '\u{302}';
+const DEAD_TILDE: char = '\u{303}';
+const DEAD_DIAERESIS: char = '\u{308}';
+
+/// The layouts that can be selected, the first one is the default.
+pub const LAYOUTS: [&Layout; 5] = [&us::US, &uk::UK, &de::DE, &fr::FR, &dvorak::DVORAK];
+
+pub struct Layout {
+    /// The short name layouts are selected by.
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Plain, shift, AltGr and shift with AltGr, by row.
+    levels: [[&'static str; 4]; 4],
+}
+
+/// What a key types.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+    Char(char),
+    /// A dead key, carrying the combining form of its accent.
+    Dead(char),
+}
+
+impl Layout {
+    /// The symbol a key types with the given modifiers. Caps Lock swaps the
+    /// plain and shift levels of keys that type a lower and upper case letter.
+    pub fn symbol(
+        &self,
+        key: KeyCode,
+        shift: bool,
+        alt_gr: bool,
+        caps_lock: bool,
+    ) -> Option<Symbol> {
+        let (row, column) = position(key)?;
+        let level = |shift: bool| {
+            let index = (alt_gr as usize) << 1 | shift as usize;
+            self.levels
+                .get(index)
+                .and_then(|rows| rows.get(row))
+                .and_then(|keys| keys.chars().nth(column))
+                .filter(|c| *c != NO_SYMBOL)
+        };
+
+        let mut c = level(shift)?;
+        if caps_lock
+            && let (Some(lower), Some(upper)) = (level(false), level(true))
+            && to_upper(lower) == Some(upper)
+        {
+            c = if shift { lower } else { upper };
+        }
+        if is_dead(c) {
+            Some(Symbol::Dead(c))
+        } else {
+            Some(Symbol::Char(c))
+        }
+    }
+}
+
+fn position(key: KeyCode) -> Option<(usize, usize)> {
+    ROWS.iter().enumerate().find_map(|(row, keys)| {
+        keys.iter()
+            .position(|k| *k == key)
+            .map(|column| (row, column))
+    })
+}
+
+/// Finds a layout by its name.
+pub fn find(name: &[u8]) -> Option<&'static Layout> {
+    LAYOUTS.iter().copied().find(|l| l.name.as_bytes() == name)
+}
+
+/// The layout chosen at build time through the `KEYBOARD_LAYOUT` environment
+/// variable, US if it isn't set or names no layout.
+pub fn boot_layout() -> &'static Layout {
+    option_env!("KEYBOARD_LAYOUT")
+        .and_then(|name| find(name.as_bytes()))
+        .unwrap_or(LAYOUTS[0])
+}
+
+fn is_dead(c: char) -> bool {
+    matches!(
+        c,
+        DEAD_GRAVE | DEAD_ACUTE | DEAD_CIRCUMFLEX | DEAD_TILDE | DEAD_DIAERESIS
+    )
+}
+
+/// The upper case form of an ASCII or Latin-1 lower case letter.
+fn to_upper(c: char) -> Option<char> {
+    match c {
+        'a'..='z' | 'à'..='þ' if c != '÷' => char::from_u32(c as u32 - 0x20),
+        _ => None,
+    }
+}
+
+/// Combines a dead key with the character typed after it. Only the accented
+/// letters of Latin-1 are known.
+pub fn compose(dead: char, c: char) -> Option<char> {
+    let accent = match dead {
+        DEAD_GRAVE => 0,
+        DEAD_ACUTE => 1,
+        DEAD_CIRCUMFLEX => 2,
+        DEAD_TILDE => 3,
+        DEAD_DIAERESIS => 4,
+        _ => return None,
+    };
+    let (upper, case_offset) = match c {
+        'A'..='Z' => (c, 0),
+        'a'..='z' => (char::from_u32(c as u32 - 0x20)?, 0x20),
+        _ => return None,
+    };
+    // Latin-1 orders the accented forms of each vowel as grave, acute,
+    // circumflex, tilde and diaeresis, leaving out the ones that don't exist.
+    let composed: u32 = match (upper, accent) {
+        ('A', _) => 0xC0 + accent,
+        ('O', _) => 0xD2 + accent,
+        ('E', 0..=2) => 0xC8 + accent,
+        ('E', 4) => 0xCB,
+        ('I', 0..=2) => 0xCC + accent,
+        ('I', 4) => 0xCF,
+        ('U', 0..=2) => 0xD9 + accent,
+        ('U', 4) => 0xDC,
+        ('N', 3) => 0xD1,
+        ('Y', 1) => 0xDD,
+        ('Y', 4) if case_offset != 0 => return Some('ÿ'),
+        _ => return None,
+    };
+    char::from_u32(composed + case_offset)
+}
+
+/// The spacing form of a dead key's accent, typed when the dead key is
+/// followed by space or by itself.
+pub fn spacing_accent(dead: char) -> Option<char> {
+    match dead {
+        DEAD_GRAVE => Some('`'),
+        DEAD_ACUTE => Some('´'),
+        DEAD_CIRCUMFLEX => Some('^'),
+        DEAD_TILDE => Some('~'),
+        DEAD_DIAERESIS => Some('¨'),
+        _ => None,
+    }
+}
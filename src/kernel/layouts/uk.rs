@@ -0,0 +1,27 @@
+use super::Layout;
+
+pub const UK: Layout = Layout {
+    name: "uk",
+    description: "UK QWERTY",
+    levels: [
+        [
+            "`1234567890-=",
+            "qwertyuiop[]",
+            "asdfghjkl;'#",
+            "\\zxcvbnm,./",
+        ],
+        [
+            "¬!\"£$%^&*()_+",
+            "QWERTYUIOP{}",
+            "ASDFGHJKL:@~",
+            "|ZXCVBNM<>?",
+        ],
+        [
+            "¦\0\0\0€\0\0\0\0\0\0\0\0",
+            "\0\0é\0\0\0úíó\0\0\0",
+            "á\0\0\0\0\0\0\0\0\0\0\0",
+            "",
+        ],
+        ["", "\0\0É\0\0\0ÚÍÓ\0\0\0", "Á\0\0\0\0\0\0\0\0\0\0\0", ""],
+    ],
+};
@@ -0,0 +1,22 @@
+use super::Layout;
+
+pub const US: Layout = Layout {
+    name: "us",
+    description: "US QWERTY",
+    levels: [
+        [
+            "`1234567890-=",
+            "qwertyuiop[]",
+            "asdfghjkl;'\\",
+            "\\zxcvbnm,./",
+        ],
+        [
+            "~!@#$%^&*()_+",
+            "QWERTYUIOP{}",
+            "ASDFGHJKL:\"|",
+            "|ZXCVBNM<>?",
+        ],
+        ["", "", "", ""],
+        ["", "", "", ""],
+    ],
+};
@@ -1,6 +1,11 @@
 use crate::kernel::pre_boot::{MemSpec, read_mem_spec};
 
-const FREE_MEM_START_ADDR: usize = 0x10000;
+const FREE_MEM_START_ADDR: usize = 0x28000;
+/// The kernel stack starts at 0x90000 and grows down, the heap stops 64 KiB
+/// short of it.
+const FREE_MEM_END_ADDR: usize = 0x80000;
+/// The lowest address of the bootstrap processor's stack, where the heap ends.
+pub const BSP_STACK_BOTTOM: u32 = FREE_MEM_END_ADDR as u32;
 const PAGE_SIZE: usize = 0x1000;
 const PAGE_SIZE_MASK: usize = !(PAGE_SIZE - 1);
 
@@ -17,18 +22,46 @@ impl MemoryManager {
         }
     }
 
-    pub unsafe fn malloc(&mut self, size: usize, align: bool) -> *mut u8 {
-        if align && (self.free_mem_addr & !PAGE_SIZE_MASK) > 0 {
-            self.free_mem_addr &= PAGE_SIZE_MASK;
-            self.free_mem_addr += PAGE_SIZE;
+    /// Allocates `size` bytes, page aligned if `align` is set. Returns `None`
+    /// when the heap would run into the stack.
+    pub unsafe fn malloc(&mut self, size: usize, align: bool) -> Option<*mut u8> {
+        let mut addr = self.free_mem_addr;
+        if align && (addr & !PAGE_SIZE_MASK) > 0 {
+            addr = (addr & PAGE_SIZE_MASK) + PAGE_SIZE;
         }
 
-        let phys_addr = self.free_mem_addr as *mut u8;
-        self.free_mem_addr += size;
-        phys_addr
+        let end = addr
+            .checked_add(size)
+            .filter(|end| *end <= FREE_MEM_END_ADDR)?;
+        self.free_mem_addr = end;
+        Some(addr as *mut u8)
     }
 
-    pub unsafe fn free(&mut self, addr: *mut u8) {}
+    /// Gives `size` bytes at `addr` back. Only the most recent allocation can
+    /// be taken back, anything else stays in use.
+    pub unsafe fn free(&mut self, addr: *mut u8, size: usize) {
+        if addr as usize + size == self.free_mem_addr {
+            self.free_mem_addr = addr as usize;
+        }
+    }
+
+    /// The bytes left before the heap runs into the stack.
+    pub fn available(&self) -> usize {
+        FREE_MEM_END_ADDR.saturating_sub(self.free_mem_addr)
+    }
+
+    /// The current end of the heap, for `release`.
+    pub fn mark(&self) -> usize {
+        self.free_mem_addr
+    }
+
+    /// Frees everything allocated since `mark` was taken. Nothing allocated
+    /// in between may still be in use.
+    pub unsafe fn release(&mut self, mark: usize) {
+        if mark >= FREE_MEM_START_ADDR && mark < self.free_mem_addr {
+            self.free_mem_addr = mark;
+        }
+    }
 
     pub unsafe fn get_memory(&mut self) -> MemSpec {
         self.mem_spec.clone()
@@ -1,14 +1,25 @@
 pub mod acpi;
+pub mod apic;
+pub mod cpu;
+mod gdt;
 mod idt;
 mod interrupt_handlers;
 pub mod isr;
 pub mod kernel;
 pub mod keyboard_driver; // TODO remove from kernel, make separate module
+pub mod keycode;
+pub mod layouts;
 pub mod mem;
+pub mod mouse_driver;
+pub mod pci;
 mod pic;
 pub mod platform;
 mod ports;
 pub mod pre_boot;
 mod process_manager;
-mod ps2;
+pub mod ps2;
+pub mod smp;
+pub mod time;
+mod tss;
 pub mod vga_driver;
+pub mod vga_modes;
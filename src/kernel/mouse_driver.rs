@@ -0,0 +1,248 @@
+// PS/2 mouse on the second port of the controller
+//
+// A standard mouse sends 3 byte packets. Setting the sample rate to 200, 100
+// and 80 makes an IntelliMouse switch to 4 byte packets with a scroll wheel
+// and report ID 3; following that with 200, 200 and 80 makes a 5 button
+// mouse report ID 4, and the fourth byte then also carries buttons 4 and 5.
+
+use crate::kernel::ps2::{
+    PS2Controller, PS2DeviceCommand, PS2Error, PS2Port, device_command, read_id,
+};
+
+const STANDARD_MOUSE_ID: u8 = 0x00;
+const WHEEL_MOUSE_ID: u8 = 0x03;
+const FIVE_BUTTON_MOUSE_ID: u8 = 0x04;
+
+const WHEEL_KNOCK: [u8; 3] = [200, 100, 80];
+const FIVE_BUTTON_KNOCK: [u8; 3] = [200, 200, 80];
+/// The sample rate once the extensions are detected, in reports per second.
+const SAMPLE_RATE: u8 = 100;
+
+const PACKET_LEFT: u8 = 1 << 0;
+const PACKET_RIGHT: u8 = 1 << 1;
+const PACKET_MIDDLE: u8 = 1 << 2;
+/// Always set in the first byte of a packet, used to find packet boundaries.
+const PACKET_ALWAYS_ONE: u8 = 1 << 3;
+const PACKET_X_SIGN: u8 = 1 << 4;
+const PACKET_Y_SIGN: u8 = 1 << 5;
+const PACKET_X_OVERFLOW: u8 = 1 << 6;
+const PACKET_Y_OVERFLOW: u8 = 1 << 7;
+const PACKET_BUTTON4: u8 = 1 << 4;
+const PACKET_BUTTON5: u8 = 1 << 5;
+
+pub const BUTTON_LEFT: u8 = 1 << 0;
+pub const BUTTON_RIGHT: u8 = 1 << 1;
+pub const BUTTON_MIDDLE: u8 = 1 << 2;
+pub const BUTTON_4: u8 = 1 << 3;
+pub const BUTTON_5: u8 = 1 << 4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MouseKind {
+    Standard,
+    Wheel,
+    FiveButton,
+}
+
+impl MouseKind {
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            STANDARD_MOUSE_ID => Some(Self::Standard),
+            WHEEL_MOUSE_ID => Some(Self::Wheel),
+            FIVE_BUTTON_MOUSE_ID => Some(Self::FiveButton),
+            _ => None,
+        }
+    }
+
+    pub fn packet_size(&self) -> usize {
+        match self {
+            Self::Standard => 3,
+            Self::Wheel | Self::FiveButton => 4,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Standard => "standard mouse",
+            Self::Wheel => "wheel mouse",
+            Self::FiveButton => "5 button wheel mouse",
+        }
+    }
+}
+
+pub enum MouseInitError {
+    /// The second port is missing or has no working device.
+    NoMouse,
+    /// The device on the second port identified as this, which isn't a mouse.
+    NotAMouse(u8),
+    Device(PS2Error),
+}
+
+impl MouseInitError {
+    pub fn describe(&self) -> (&'static str, Option<u8>) {
+        match self {
+            MouseInitError::NoMouse => ("no mouse", None),
+            MouseInitError::NotAMouse(id) => ("not a mouse, ID", Some(*id)),
+            MouseInitError::Device(err) => err.describe(),
+        }
+    }
+}
+
+impl From<PS2Error> for MouseInitError {
+    fn from(err: PS2Error) -> Self {
+        Self::Device(err)
+    }
+}
+
+/// A decoded mouse packet.
+#[derive(Clone, Copy)]
+pub struct MousePacket {
+    /// Movement to the right.
+    pub dx: i16,
+    /// Movement upwards, as the mouse reports it.
+    pub dy: i16,
+    /// Scroll wheel movement, positive towards the user.
+    pub wheel: i8,
+    /// A `BUTTON_*` bit per pressed button.
+    pub buttons: u8,
+}
+
+/// Collects the bytes of a packet as they arrive from IRQ 12.
+pub struct PacketDecoder {
+    kind: Option<MouseKind>,
+    bytes: [u8; 4],
+    len: usize,
+}
+
+impl PacketDecoder {
+    /// A decoder that drops everything until `set_kind` is called.
+    pub const fn new() -> Self {
+        Self {
+            kind: None,
+            bytes: [0; 4],
+            len: 0,
+        }
+    }
+
+    pub fn set_kind(&mut self, kind: MouseKind) {
+        self.kind = Some(kind);
+        self.len = 0;
+    }
+
+    /// Adds a byte, returning the packet once it is complete. A first byte
+    /// without its always-one bit is dropped, so the decoder gets back in
+    /// sync with the mouse after a lost byte.
+    pub fn push(&mut self, byte: u8) -> Option<MousePacket> {
+        let kind = self.kind?;
+        if self.len == 0 && byte & PACKET_ALWAYS_ONE == 0 {
+            return None;
+        }
+        if let Some(slot) = self.bytes.get_mut(self.len) {
+            *slot = byte;
+        }
+        self.len += 1;
+        if self.len < kind.packet_size() {
+            return None;
+        }
+        self.len = 0;
+        Some(self.decode(kind))
+    }
+
+    fn decode(&self, kind: MouseKind) -> MousePacket {
+        let [flags, x, y, extra] = self.bytes;
+        let mut buttons = 0;
+        for (packet_bit, button) in [
+            (PACKET_LEFT, BUTTON_LEFT),
+            (PACKET_RIGHT, BUTTON_RIGHT),
+            (PACKET_MIDDLE, BUTTON_MIDDLE),
+        ] {
+            if flags & packet_bit != 0 {
+                buttons |= button;
+            }
+        }
+
+        // The sign bits extend the movement bytes to 9 bit values. Movement
+        // that overflowed is meaningless and dropped.
+        let movement = |value: u8, sign: u8, overflow: u8| {
+            if flags & overflow != 0 {
+                0
+            } else if flags & sign != 0 {
+                value as i16 - 0x100
+            } else {
+                value as i16
+            }
+        };
+        let dx = movement(x, PACKET_X_SIGN, PACKET_X_OVERFLOW);
+        let dy = movement(y, PACKET_Y_SIGN, PACKET_Y_OVERFLOW);
+
+        let wheel = match kind {
+            MouseKind::Standard => 0,
+            MouseKind::Wheel => extra as i8,
+            MouseKind::FiveButton => {
+                if extra & PACKET_BUTTON4 != 0 {
+                    buttons |= BUTTON_4;
+                }
+                if extra & PACKET_BUTTON5 != 0 {
+                    buttons |= BUTTON_5;
+                }
+                // A 4 bit two's complement value.
+                ((extra << 4) as i8) >> 4
+            }
+        };
+
+        MousePacket {
+            dx,
+            dy,
+            wheel,
+            buttons,
+        }
+    }
+}
+
+/// A driver for a PS/2 mouse, with the IntelliMouse extensions when the
+/// mouse has them.
+pub struct MouseDriver {
+    kind: MouseKind,
+}
+
+impl MouseDriver {
+    /// Identifies the mouse on the second port and enables its extensions.
+    /// Data reporting is enabled on success, the caller still has to enable
+    /// the port's interrupt.
+    pub fn initialise(controller: &PS2Controller) -> Result<Self, MouseInitError> {
+        if !controller.is_working(PS2Port::Second) {
+            return Err(MouseInitError::NoMouse);
+        }
+        device_command(PS2Port::Second, PS2DeviceCommand::DisableScanning as u8)?;
+        let [id, _] = read_id(PS2Port::Second)?;
+        let mut kind = MouseKind::from_id(id).ok_or(MouseInitError::NotAMouse(id))?;
+
+        if kind == MouseKind::Standard && knock(&WHEEL_KNOCK)? == WHEEL_MOUSE_ID {
+            kind = MouseKind::Wheel;
+        }
+        if kind == MouseKind::Wheel && knock(&FIVE_BUTTON_KNOCK)? == FIVE_BUTTON_MOUSE_ID {
+            kind = MouseKind::FiveButton;
+        }
+
+        set_sample_rate(SAMPLE_RATE)?;
+        device_command(PS2Port::Second, PS2DeviceCommand::EnableScanning as u8)?;
+        Ok(Self { kind })
+    }
+
+    pub fn kind(&self) -> MouseKind {
+        self.kind
+    }
+}
+
+fn set_sample_rate(rate: u8) -> Result<(), PS2Error> {
+    device_command(PS2Port::Second, PS2DeviceCommand::SetRate as u8)?;
+    device_command(PS2Port::Second, rate)
+}
+
+/// Sends a sequence of sample rates and reads the ID the mouse answers with.
+fn knock(rates: &[u8]) -> Result<u8, PS2Error> {
+    for rate in rates {
+        set_sample_rate(*rate)?;
+    }
+    let [id, _] = read_id(PS2Port::Second)?;
+    Ok(id)
+}
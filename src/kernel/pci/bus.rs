@@ -0,0 +1,109 @@
+use crate::kernel::pci::{
+    PCIError,
+    config::{ConfigSpace, PCIAddress},
+    device::PCIDevice,
+    driver::PCIDriver,
+};
+
+const MAX_DEVICES: usize = 32;
+const MAX_DRIVERS: usize = 16;
+const DEVICES_PER_BUS: u8 = 32;
+const FUNCTIONS_PER_DEVICE: u8 = 8;
+
+/// The functions found on all PCI buses and the registered drivers.
+pub struct PCIBus {
+    config: ConfigSpace,
+    devices: [Option<PCIDevice>; MAX_DEVICES],
+    drivers: [Option<&'static PCIDriver>; MAX_DRIVERS],
+    /// The number of functions that didn't fit in the device table.
+    pub missed: usize,
+}
+
+impl PCIBus {
+    /// Probes every function of every bus. Buses are scanned by brute force,
+    /// so functions behind bridges are found without walking the hierarchy.
+    /// BARs are sized while probing, so this runs before interrupts are
+    /// enabled.
+    pub unsafe fn scan(config: ConfigSpace) -> Self {
+        let mut bus = Self {
+            config,
+            devices: [None; MAX_DEVICES],
+            drivers: [None; MAX_DRIVERS],
+            missed: 0,
+        };
+        for bus_number in config.buses() {
+            for device in 0..DEVICES_PER_BUS {
+                unsafe { bus.scan_device(bus_number, device) };
+            }
+        }
+        bus
+    }
+
+    unsafe fn scan_device(&mut self, bus: u8, device: u8) {
+        unsafe {
+            let Some(first) = PCIDevice::probe(&self.config, PCIAddress::new(bus, device, 0))
+            else {
+                return;
+            };
+            self.add(first);
+            if !first.is_multifunction() {
+                return;
+            }
+            for function in 1..FUNCTIONS_PER_DEVICE {
+                let address = PCIAddress::new(bus, device, function);
+                if let Some(found) = PCIDevice::probe(&self.config, address) {
+                    self.add(found);
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, device: PCIDevice) {
+        match self.devices.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(device),
+            None => self.missed += 1,
+        }
+    }
+
+    pub fn config(&self) -> &ConfigSpace {
+        &self.config
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &PCIDevice> {
+        self.devices.iter().flatten()
+    }
+
+    /// Registers a driver and offers it the matching functions that don't
+    /// have a driver yet. Returns the number of functions it took.
+    pub unsafe fn register_driver(
+        &mut self,
+        driver: &'static PCIDriver,
+    ) -> Result<usize, PCIError> {
+        if self
+            .drivers
+            .iter()
+            .flatten()
+            .any(|registered| core::ptr::eq(*registered, driver))
+        {
+            return Err(PCIError::AlreadyRegistered);
+        }
+        let slot = self
+            .drivers
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(PCIError::Full)?;
+        *slot = Some(driver);
+
+        let mut bound = 0;
+        for device in self.devices.iter_mut().flatten() {
+            if device.driver.is_none()
+                && driver.supports(device)
+                && unsafe { (driver.probe)(device, &self.config) }
+            {
+                device.driver = Some(driver.name);
+                bound += 1;
+            }
+        }
+        Ok(bound)
+    }
+}
@@ -0,0 +1,160 @@
+// PCI configuration space access
+//
+// Configuration mechanism #1 selects a register by writing its address to
+// port 0xCF8 and then accesses it through port 0xCFC. It only reaches the
+// first 256 bytes of each function. ECAM maps the full 4 KiB of every function
+// into memory, at `base + (bus << 20 | device << 15 | function << 12)`.
+
+use crate::kernel::{
+    acpi::{acpi::ACPI, mcfg::MCFG},
+    ports::{Port, read_port_dword, write_port_dword},
+};
+
+const CONFIG_ENABLE: u32 = 1 << 31;
+const LEGACY_SIZE: u16 = 0x100;
+const ECAM_SIZE: u16 = 0x1000;
+/// The size of the ECAM region of a single bus.
+const ECAM_BUS_SIZE: u64 = 1 << 20;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PCIAddress {
+    pub bus: u8,
+    /// 0 to 31
+    pub device: u8,
+    /// 0 to 7
+    pub function: u8,
+}
+
+impl PCIAddress {
+    pub fn new(bus: u8, device: u8, function: u8) -> Self {
+        Self {
+            bus,
+            device: device & 0x1F,
+            function: function & 0x07,
+        }
+    }
+
+    /// The bus, device and function bits shared by both access mechanisms.
+    fn bits(&self) -> u32 {
+        (self.bus as u32) << 16 | (self.device as u32) << 11 | (self.function as u32) << 8
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum ConfigSpace {
+    /// Configuration mechanism #1, through ports 0xCF8 and 0xCFC.
+    Ports,
+    /// Memory mapped configuration space of the buses `start_bus..=end_bus`.
+    ECAM {
+        base: u32,
+        start_bus: u8,
+        end_bus: u8,
+    },
+}
+
+impl ConfigSpace {
+    /// Uses ECAM for segment group 0 when the MCFG describes it and it's
+    /// addressable without paging, otherwise the IO ports.
+    pub unsafe fn detect(acpi: Option<&ACPI>) -> Self {
+        unsafe {
+            let Some(mcfg) = acpi.and_then(|acpi| MCFG::from_acpi(acpi)) else {
+                return ConfigSpace::Ports;
+            };
+            let entry = (0..mcfg.len())
+                .filter_map(|index| mcfg.entry(index))
+                .find(|entry| entry.segment == 0 && entry.start_bus <= entry.end_bus);
+            match entry {
+                Some(entry) => {
+                    // The base address corresponds to bus 0, even if the
+                    // allocation starts at a later bus.
+                    let end = entry.base_addr + (entry.end_bus as u64 + 1) * ECAM_BUS_SIZE;
+                    if end > u32::MAX as u64 {
+                        return ConfigSpace::Ports;
+                    }
+                    ConfigSpace::ECAM {
+                        base: entry.base_addr as u32,
+                        start_bus: entry.start_bus,
+                        end_bus: entry.end_bus,
+                    }
+                }
+                None => ConfigSpace::Ports,
+            }
+        }
+    }
+
+    /// The buses that can be accessed.
+    pub fn buses(&self) -> core::ops::RangeInclusive<u8> {
+        match self {
+            ConfigSpace::Ports => 0..=u8::MAX,
+            ConfigSpace::ECAM {
+                start_bus, end_bus, ..
+            } => *start_bus..=*end_bus,
+        }
+    }
+
+    /// The address of a dword register in an ECAM region.
+    fn ecam_ptr(&self, addr: PCIAddress, offset: u16) -> Option<*mut u32> {
+        match *self {
+            ConfigSpace::ECAM {
+                base,
+                start_bus,
+                end_bus,
+            } if start_bus <= addr.bus && addr.bus <= end_bus && offset < ECAM_SIZE => {
+                let ptr = base as usize + ((addr.bits() as usize) << 4) + (offset & !3) as usize;
+                Some(ptr as *mut u32)
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads the dword containing `offset`. Registers that can't be accessed
+    /// read as all ones, like a missing device.
+    pub unsafe fn read_dword(&self, addr: PCIAddress, offset: u16) -> u32 {
+        unsafe {
+            match self {
+                ConfigSpace::Ports if offset < LEGACY_SIZE => {
+                    write_port_dword(
+                        Port::PCIConfigAddress as u16,
+                        CONFIG_ENABLE | addr.bits() | (offset & 0xFC) as u32,
+                    );
+                    read_port_dword(Port::PCIConfigData as u16)
+                }
+                ConfigSpace::Ports => u32::MAX,
+                ConfigSpace::ECAM { .. } => match self.ecam_ptr(addr, offset) {
+                    Some(ptr) => ptr.read_volatile(),
+                    None => u32::MAX,
+                },
+            }
+        }
+    }
+
+    /// Writes the dword containing `offset`. Writes to registers that can't
+    /// be accessed are ignored.
+    pub unsafe fn write_dword(&self, addr: PCIAddress, offset: u16, value: u32) {
+        unsafe {
+            match self {
+                ConfigSpace::Ports if offset < LEGACY_SIZE => {
+                    write_port_dword(
+                        Port::PCIConfigAddress as u16,
+                        CONFIG_ENABLE | addr.bits() | (offset & 0xFC) as u32,
+                    );
+                    write_port_dword(Port::PCIConfigData as u16, value);
+                }
+                ConfigSpace::Ports => {}
+                ConfigSpace::ECAM { .. } => {
+                    if let Some(ptr) = self.ecam_ptr(addr, offset) {
+                        ptr.write_volatile(value);
+                    }
+                }
+            }
+        }
+    }
+
+    pub unsafe fn read_word(&self, addr: PCIAddress, offset: u16) -> u16 {
+        unsafe { (self.read_dword(addr, offset) >> ((offset & 2) * 8)) as u16 }
+    }
+
+    pub unsafe fn read_byte(&self, addr: PCIAddress, offset: u16) -> u8 {
+        unsafe { (self.read_dword(addr, offset) >> ((offset & 3) * 8)) as u8 }
+    }
+}
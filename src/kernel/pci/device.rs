@@ -0,0 +1,229 @@
+// PCI functions
+//
+// The header of every function starts with its IDs, class code and header
+// type. BARs and the interrupt line follow at offsets that depend on the
+// header type.
+
+use crate::kernel::pci::config::{ConfigSpace, PCIAddress};
+
+const VENDOR_ID: u16 = 0x00;
+const DEVICE_ID: u16 = 0x02;
+const COMMAND: u16 = 0x04;
+const CLASS_REVISION: u16 = 0x08;
+const HEADER_TYPE: u16 = 0x0E;
+const BAR0: u16 = 0x10;
+const INTERRUPT_LINE: u16 = 0x3C;
+const INTERRUPT_PIN: u16 = 0x3D;
+
+/// The vendor ID read for a function that doesn't exist.
+pub const NO_VENDOR: u16 = 0xFFFF;
+
+pub const COMMAND_IO_SPACE: u16 = 1 << 0;
+pub const COMMAND_MEMORY_SPACE: u16 = 1 << 1;
+
+const HEADER_TYPE_MASK: u8 = 0x7F;
+const HEADER_TYPE_MULTIFUNCTION: u8 = 1 << 7;
+const HEADER_TYPE_GENERAL: u8 = 0x00;
+const HEADER_TYPE_BRIDGE: u8 = 0x01;
+
+const BAR_IO: u32 = 1 << 0;
+const BAR_TYPE_MASK: u32 = 0b11 << 1;
+const BAR_TYPE_64: u32 = 0b10 << 1;
+const BAR_PREFETCHABLE: u32 = 1 << 3;
+const BAR_IO_MASK: u32 = !0b11;
+const BAR_MEMORY_MASK: u32 = !0b1111;
+/// The number of BARs in a general header, bridges have fewer.
+pub const MAX_BARS: usize = 6;
+
+/// A base address register, describing an address range the function decodes.
+#[derive(Clone, Copy)]
+pub enum BAR {
+    Unused,
+    IO {
+        port: u16,
+        size: u32,
+    },
+    Memory {
+        base: u64,
+        size: u64,
+        prefetchable: bool,
+        /// Whether this BAR takes up the next BAR as well, for the upper 32
+        /// bits of the address.
+        wide: bool,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub struct PCIDevice {
+    pub address: PCIAddress,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+    pub revision: u8,
+    pub header_type: u8,
+    /// The IRQ the firmware routed the interrupt pin to, 0xFF if none.
+    pub interrupt_line: u8,
+    /// 1 to 4 for INTA# to INTD#, 0 if the function doesn't use interrupts.
+    pub interrupt_pin: u8,
+    /// The BARs, sized when the function is probed. The upper half of a wide
+    /// BAR is `BAR::Unused`.
+    pub bars: [BAR; MAX_BARS],
+    /// The name of the driver bound to the function.
+    pub driver: Option<&'static str>,
+}
+
+impl PCIDevice {
+    /// Reads the header of a function and sizes its BARs, or returns `None`
+    /// if there is no function at `address`. Decoding is turned off while
+    /// the BARs are sized, so this must run before the function is in use and
+    /// with interrupts disabled.
+    pub unsafe fn probe(config: &ConfigSpace, address: PCIAddress) -> Option<Self> {
+        unsafe {
+            let vendor_id = config.read_word(address, VENDOR_ID);
+            if vendor_id == NO_VENDOR {
+                return None;
+            }
+            let class = config.read_dword(address, CLASS_REVISION);
+            let mut device = Self {
+                address,
+                vendor_id,
+                device_id: config.read_word(address, DEVICE_ID),
+                class: (class >> 24) as u8,
+                subclass: (class >> 16) as u8,
+                prog_if: (class >> 8) as u8,
+                revision: class as u8,
+                header_type: config.read_byte(address, HEADER_TYPE),
+                interrupt_line: config.read_byte(address, INTERRUPT_LINE),
+                interrupt_pin: config.read_byte(address, INTERRUPT_PIN),
+                bars: [BAR::Unused; MAX_BARS],
+                driver: None,
+            };
+            let mut index = 0;
+            while index < device.bar_count() {
+                let bar = device.size_bar(config, index);
+                if let Some(slot) = device.bars.get_mut(index) {
+                    *slot = bar;
+                }
+                index += match bar {
+                    BAR::Memory { wide: true, .. } => 2,
+                    _ => 1,
+                };
+            }
+            Some(device)
+        }
+    }
+
+    pub fn is_multifunction(&self) -> bool {
+        self.header_type & HEADER_TYPE_MULTIFUNCTION != 0
+    }
+
+    pub fn is_bridge(&self) -> bool {
+        self.header_type & HEADER_TYPE_MASK == HEADER_TYPE_BRIDGE
+    }
+
+    /// The number of BARs in the header.
+    pub fn bar_count(&self) -> usize {
+        if self.is_bridge() {
+            2
+        } else if self.header_type & HEADER_TYPE_MASK == HEADER_TYPE_GENERAL {
+            MAX_BARS
+        } else {
+            0
+        }
+    }
+
+    /// Decodes a BAR. The size is found by writing all ones to the BAR and
+    /// reading back which bits stuck, with decoding disabled meanwhile.
+    unsafe fn size_bar(&self, config: &ConfigSpace, index: usize) -> BAR {
+        if index >= self.bar_count() {
+            return BAR::Unused;
+        }
+        unsafe {
+            let offset = BAR0 + index as u16 * 4;
+            let command = config.read_word(self.address, COMMAND);
+            self.set_command(config, command & !(COMMAND_IO_SPACE | COMMAND_MEMORY_SPACE));
+
+            let low = config.read_dword(self.address, offset);
+            config.write_dword(self.address, offset, u32::MAX);
+            let low_mask = config.read_dword(self.address, offset);
+            config.write_dword(self.address, offset, low);
+
+            let wide =
+                low & (BAR_IO | BAR_TYPE_MASK) == BAR_TYPE_64 && index + 1 < self.bar_count();
+            let (high, high_mask) = if wide {
+                let offset = offset + 4;
+                let high = config.read_dword(self.address, offset);
+                config.write_dword(self.address, offset, u32::MAX);
+                let high_mask = config.read_dword(self.address, offset);
+                config.write_dword(self.address, offset, high);
+                (high, high_mask)
+            } else {
+                (0, u32::MAX)
+            };
+
+            self.set_command(config, command);
+
+            if low_mask == 0 {
+                return BAR::Unused;
+            }
+            if low & BAR_IO != 0 {
+                // The upper 16 bits may read back as zero.
+                let mask = (low_mask & BAR_IO_MASK) | 0xFFFF_0000;
+                return BAR::IO {
+                    port: (low & BAR_IO_MASK) as u16,
+                    size: (!mask).wrapping_add(1),
+                };
+            }
+            let mask = (high_mask as u64) << 32 | (low_mask & BAR_MEMORY_MASK) as u64;
+            BAR::Memory {
+                base: (high as u64) << 32 | (low & BAR_MEMORY_MASK) as u64,
+                size: (!mask).wrapping_add(1),
+                prefetchable: low & BAR_PREFETCHABLE != 0,
+                wide,
+            }
+        }
+    }
+
+    /// Writes the command register. Zeroes are written to the status register
+    /// sharing its dword, since writing ones would clear its error bits.
+    pub unsafe fn set_command(&self, config: &ConfigSpace, command: u16) {
+        unsafe { config.write_dword(self.address, COMMAND, command as u32) };
+    }
+
+    /// Enables the given command bits, e.g. `COMMAND_MEMORY_SPACE`.
+    pub unsafe fn enable(&self, config: &ConfigSpace, bits: u16) {
+        unsafe {
+            let command = config.read_word(self.address, COMMAND);
+            self.set_command(config, command | bits);
+        }
+    }
+
+    pub fn class_name(&self) -> &'static str {
+        match (self.class, self.subclass) {
+            (0x01, 0x01) => "IDE controller",
+            (0x01, 0x06) => "SATA controller",
+            (0x01, 0x08) => "NVM controller",
+            (0x01, _) => "Storage controller",
+            (0x02, 0x00) => "Ethernet controller",
+            (0x02, _) => "Network controller",
+            (0x03, 0x00) => "VGA controller",
+            (0x03, _) => "Display controller",
+            (0x04, 0x03) => "Audio device",
+            (0x04, _) => "Multimedia controller",
+            (0x05, _) => "Memory controller",
+            (0x06, 0x00) => "Host bridge",
+            (0x06, 0x01) => "ISA bridge",
+            (0x06, 0x04) => "PCI bridge",
+            (0x06, _) => "Bridge",
+            (0x07, _) => "Communication controller",
+            (0x08, _) => "System peripheral",
+            (0x09, _) => "Input device controller",
+            (0x0C, 0x03) => "USB controller",
+            (0x0C, 0x05) => "SMBus controller",
+            (0x0C, _) => "Serial bus controller",
+            _ => "Unknown device",
+        }
+    }
+}
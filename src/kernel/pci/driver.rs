@@ -0,0 +1,30 @@
+// PCI drivers
+//
+// A driver lists the devices it supports. When it's registered, it is offered
+// every matching function that has no driver yet, and keeps the functions it
+// accepts.
+
+use crate::kernel::pci::{config::ConfigSpace, device::PCIDevice};
+
+/// A function a driver supports.
+#[derive(Clone, Copy)]
+pub enum PCIMatch {
+    Class { class: u8, subclass: u8 },
+}
+
+pub struct PCIDriver {
+    pub name: &'static str,
+    pub matches: &'static [PCIMatch],
+    /// Sets up a matching function. Returns whether the driver takes it.
+    pub probe: unsafe fn(&PCIDevice, &ConfigSpace) -> bool,
+}
+
+impl PCIDriver {
+    pub fn supports(&self, device: &PCIDevice) -> bool {
+        self.matches.iter().any(|m| match *m {
+            PCIMatch::Class { class, subclass } => {
+                device.class == class && device.subclass == subclass
+            }
+        })
+    }
+}
@@ -0,0 +1,18 @@
+// Peripheral Component Interconnect
+//
+// Devices are found by reading the vendor ID of every bus, device and function
+// number in the configuration space, which is accessed either through the
+// legacy IO ports or memory mapped (ECAM) when the ACPI MCFG describes it.
+// Drivers register the devices they handle and are bound to matching devices.
+
+pub mod bus;
+pub mod config;
+pub mod device;
+pub mod driver;
+
+#[derive(Clone, Copy)]
+pub enum PCIError {
+    /// No more drivers can be registered.
+    Full,
+    AlreadyRegistered,
+}
@@ -31,6 +31,14 @@ impl PIC {
         write_port_byte(Port::MasterPICCommand.into(), EOI);
     }
 
+    /// Masks all IRQs on both PICs, for when interrupts are delivered through the APIC.
+    /// The PICs should be remapped first, so that spurious interrupts they may
+    /// still raise do not end up on exception vectors.
+    pub fn disable() {
+        write_port_byte(Port::MasterPICData.into(), 0xFF);
+        write_port_byte(Port::SlavePICData.into(), 0xFF);
+    }
+
     /// Initialises and remaps the PIC's IRQs. This function sends the initialisation command
     /// and the following 3 initialisation words.
     pub fn remap(offset1: u8, offset2: u8) {
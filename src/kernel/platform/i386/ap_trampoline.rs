@@ -0,0 +1,67 @@
+use core::arch::global_asm;
+
+// Startup code for application processors. An AP starts in real mode at
+// `vector << 12` after receiving a startup IPI, so the 16 bit part of this
+// code is copied to a page below 1 MiB by the bootstrap processor. It only
+// addresses memory relative to CS, so it runs wherever it is copied to.
+//
+// The GDT register contents at `ap_trampoline_gdtr` are filled in by the
+// bootstrap processor. Once in protected mode, the AP jumps to
+// `ap_protected_entry` in the kernel proper, which loads the stack pointer
+// from `AP_STACK_TOP` and calls `ap_main`.
+global_asm!(
+    ".section .text.ap_trampoline, \"ax\"",
+    ".global ap_trampoline_start",
+    ".global ap_trampoline_gdtr",
+    ".global ap_trampoline_end",
+    ".code16",
+    "ap_trampoline_start:",
+    "cli",
+    "cld",
+    "mov ax, cs",
+    "mov ds, ax",
+    // lgdt dword [ap_trampoline_gdtr - ap_trampoline_start]. The assembler
+    // doesn't accept a label difference as memory operand, so it's encoded
+    // by hand.
+    ".byte 0x66, 0x0F, 0x01, 0x16",
+    ".word ap_trampoline_gdtr - ap_trampoline_start",
+    "mov eax, cr0",
+    "or eax, 1",
+    "mov cr0, eax",
+    // Far jump with a 32 bit offset: jmp dword 0x08:ap_protected_entry
+    ".byte 0x66, 0xEA",
+    ".long ap_protected_entry",
+    ".word {cs}",
+    ".p2align 2",
+    "ap_trampoline_gdtr:",
+    ".word 0",
+    ".long 0",
+    "ap_trampoline_end:",
+    ".code32",
+    "",
+    ".section .text.ap_protected_entry, \"ax\"",
+    "ap_protected_entry:",
+    "mov ax, {ds}",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov fs, ax",
+    "mov gs, ax",
+    "mov ss, ax",
+    "mov esp, [{stack}]",
+    "xor ebp, ebp",
+    "call {main}",
+    "2:",
+    "cli",
+    "hlt",
+    "jmp 2b",
+    cs = const crate::kernel::gdt::KERNEL_CS,
+    ds = const crate::kernel::gdt::KERNEL_DS,
+    stack = sym crate::kernel::smp::AP_STACK_TOP,
+    main = sym crate::kernel::smp::ap_main,
+);
+
+unsafe extern "C" {
+    pub static ap_trampoline_start: u8;
+    pub static ap_trampoline_gdtr: u8;
+    pub static ap_trampoline_end: u8;
+}
@@ -1 +1 @@
-pub mod context_switch;
\ No newline at end of file
+pub mod ap_trampoline;
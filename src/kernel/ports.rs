@@ -11,9 +11,23 @@ pub enum Port {
     SlavePICCommand = 0x00A0,
     SlavePICData = 0x00A1,
 
+    // PIT
+    PITChannel0 = 0x0040,
+    PITChannel2 = 0x0042,
+    PITCommand = 0x0043,
+    SystemControlB = 0x0061, // PIT channel 2 gate and output, PC speaker
+
+    // CMOS and RTC
+    CMOSAddress = 0x0070,
+    CMOSData = 0x0071,
+
     // PS2
     PS2DataPort = 0x0060,
     PS2StatusCmdReg = 0x0064,
+
+    // PCI configuration mechanism #1
+    PCIConfigAddress = 0x0CF8,
+    PCIConfigData = 0x0CFC,
 }
 
 impl Into<u16> for Port {
@@ -70,10 +84,42 @@ pub fn read_port_word(port: u16) -> u16 {
     }
 }
 
+pub fn write_port_dword(port: u16, data: u32) {
+    unsafe {
+        asm!(
+            "out dx, eax",
+            in("dx") port,
+            in("eax") data,
+            options(nomem, nostack, preserves_flags),
+        )
+    }
+}
+
+pub fn read_port_dword(port: u16) -> u32 {
+    unsafe {
+        let mut eax: u32;
+        asm!(
+            "in eax, dx",
+            in("dx") port,
+            out("eax") eax,
+            options(nomem, nostack, preserves_flags),
+        );
+        eax
+    }
+}
+
 pub fn io_wait() {
     write_port_byte(Port::MBHexDisplay as u16, 0)
 }
 
+/// Busy waits for roughly `us` microseconds, assuming a port write takes about
+/// one microsecond. Only meant for coarse delays before a timer is available.
+pub fn io_delay_us(us: u32) {
+    for _ in 0..us {
+        io_wait();
+    }
+}
+
 pub unsafe fn kernel_write_port_byte(port: u16, data: u8) -> Result<(), u32> {
     unsafe {
         clear_last_interrupt();
@@ -1,13 +1,43 @@
-use crate::kernel::platform::i386::context_switch::{ProcessContext, switch_context};
-use core::hint::black_box;
+use crate::kernel::kernel::KernelError;
 
+const MAX_TASKS: usize = 16;
 
-pub struct ProcessManager {}
+/// A task runs to completion on whichever CPU takes it from the run queue.
+pub type Task = fn();
+
+pub struct ProcessManager {
+    queue: [Option<Task>; MAX_TASKS],
+    head: usize,
+    len: usize,
+}
 
 impl ProcessManager {
-    #[inline(never)]
-    pub unsafe fn start_process(&mut self, process: fn()) {
-        let ctx = ProcessContext { stack_base_ptr: 0x7FFFF as *const u8 };
-        unsafe { switch_context(black_box(process), &ctx) };
+    pub const fn new() -> Self {
+        Self {
+            queue: [None; MAX_TASKS],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Appends a task to the run queue.
+    pub fn spawn(&mut self, task: Task) -> Result<(), KernelError> {
+        if self.len == MAX_TASKS {
+            return Err(KernelError::Busy);
+        }
+        self.queue[(self.head + self.len) % MAX_TASKS] = Some(task);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Takes the oldest task from the run queue.
+    pub fn next_task(&mut self) -> Option<Task> {
+        if self.len == 0 {
+            return None;
+        }
+        let task = self.queue[self.head].take();
+        self.head = (self.head + 1) % MAX_TASKS;
+        self.len -= 1;
+        task
     }
 }
@@ -1,65 +1,184 @@
+// 8042 PS/2 controller
+//
+// The controller has two ports: the first is usually a keyboard and the
+// second, on dual channel controllers, a mouse. Commands for the controller go
+// to port 0x64 and data for it and its devices goes through port 0x60, gated
+// by the input and output buffer bits of the status register. Every wait has
+// a timeout, so a missing controller or device can't hang the kernel.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
 use crate::{
-    kernel::ports::{Port, read_port_byte, write_port_byte},
-    printer::VGATextWriter,
+    kernel::{
+        acpi::{acpi::ACPI, fadt::FADT},
+        ports::{Port, io_wait, read_port_byte, write_port_byte},
+    },
     util::read_bit_mask,
 };
 
 const OUTPUT_BUFFER_STATUS: u8 = 1 << 0;
-const INPUT_BUFFER_STATUS: u8 = 1 << 0;
-const SYSTEM_FLAG: u8 = 1 << 0;
-const COMMAND_OR_DATA: u8 = 1 << 0;
-const TIME_OUT_ERROR: u8 = 1 << 0;
-const PARITY_ERROR: u8 = 1 << 0;
+const INPUT_BUFFER_STATUS: u8 = 1 << 1;
+const SYSTEM_FLAG: u8 = 1 << 2;
+const COMMAND_OR_DATA: u8 = 1 << 3;
+const AUX_OUTPUT_BUFFER: u8 = 1 << 5;
+const TIME_OUT_ERROR: u8 = 1 << 6;
+const PARITY_ERROR: u8 = 1 << 7;
+
+const CONFIG_PORT1_INTERRUPT: u8 = 1 << 0;
+const CONFIG_PORT2_INTERRUPT: u8 = 1 << 1;
+const CONFIG_PORT1_CLOCK_DISABLED: u8 = 1 << 4;
+const CONFIG_PORT2_CLOCK_DISABLED: u8 = 1 << 5;
+/// Translates scan code set 2 to set 1, which the keyboard driver expects.
+const CONFIG_PORT1_TRANSLATION: u8 = 1 << 6;
+
+const SELF_TEST_PASSED: u8 = 0x55;
+const PORT_TEST_PASSED: u8 = 0x00;
+
+const DEVICE_ACK: u8 = 0xFA;
+const DEVICE_RESEND: u8 = 0xFE;
+const DEVICE_SELF_TEST_PASSED: u8 = 0xAA;
+/// Attempts at sending a byte to a device that asks for it to be resent.
+const MAX_RESENDS: usize = 3;
+
+/// How long to wait for the controller, in microseconds.
+const TIMEOUT_US: u32 = 50_000;
+/// Devices can take much longer to reset.
+const RESET_TIMEOUT_US: u32 = 1_000_000;
+/// Bytes read when flushing the output buffer, in case it never empties.
+const MAX_FLUSH: usize = 32;
 
-struct PS2Status {
-    output_buf_full: bool,
-    input_buf_full: bool,
-    sys_flag: bool,
-    data_for_device: bool,
+pub struct PS2Status {
+    pub output_buf_full: bool,
+    pub input_buf_full: bool,
+    pub sys_flag: bool,
+    pub data_for_device: bool,
+    /// The byte in the output buffer comes from the second port.
+    pub from_second_port: bool,
 }
 
-enum PS2Error {
+#[derive(Clone, Copy)]
+pub enum PS2Error {
     TimeOut,
     Parity,
+    /// Nothing answered on the controller's ports.
+    NoController,
+    /// The controller self test returned this instead of 0x55.
+    SelfTestFailed(u8),
+    /// No port passed its interface test.
+    NoWorkingPort,
+    /// A device answered a command with this instead of an ACK.
+    NoAck(u8),
+    /// A device failed its self test after a reset.
+    ResetFailed(u8),
+}
+
+impl PS2Error {
+    /// What went wrong, and the byte received instead of the expected one
+    /// if that is the reason.
+    pub fn describe(&self) -> (&'static str, Option<u8>) {
+        match *self {
+            PS2Error::TimeOut => ("timed out", None),
+            PS2Error::Parity => ("parity error", None),
+            PS2Error::NoController => ("no controller", None),
+            PS2Error::SelfTestFailed(reply) => ("self test failed", Some(reply)),
+            PS2Error::NoWorkingPort => ("no working port", None),
+            PS2Error::NoAck(reply) => ("no ACK", Some(reply)),
+            PS2Error::ResetFailed(reply) => ("reset failed", Some(reply)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PS2Port {
+    First,
+    Second,
 }
 
-enum PS2DeviceCommand {
+enum ControllerCommand {
+    ReadConfig = 0x20,
+    WriteConfig = 0x60,
+    DisablePort2 = 0xA7,
+    EnablePort2 = 0xA8,
+    TestPort2 = 0xA9,
+    SelfTest = 0xAA,
+    TestPort1 = 0xAB,
+    DisablePort1 = 0xAD,
+    EnablePort1 = 0xAE,
+    WritePort2 = 0xD4,
+}
+
+pub enum PS2DeviceCommand {
+    /// Gets or sets the scan code set of a keyboard; takes the set as an
+    /// argument, 0 to get it.
+    ScanCodeSet = 0xF0,
     Identify = 0xF2,
+    /// Sets the sample rate of a mouse, or the typematic rate and delay of a
+    /// keyboard; takes the setting as an argument.
+    SetRate = 0xF3,
     EnableScanning = 0xF4,
     DisableScanning = 0xF5,
+    Reset = 0xFF,
 }
 
-/*
-pub fn tmp() {
-    let status = unsafe { read_status() };
-    unsafe {
-        if let Some(mut tty) = VGATextWriter::get_instance() {
-            match status {
-                Ok(s) => {
-                    if s.output_buf_full {
-                        tty.println_ascii("Output buf".as_bytes());
-                    }
-                    if s.input_buf_full {
-                        tty.println_ascii("Input buf".as_bytes());
-                    }
-                    if s.sys_flag {
-                        tty.println_ascii("Sysflag".as_bytes());
-                    }
-                    if s.data_for_device {
-                        tty.println_ascii("Device data".as_bytes());
-                    }
-                    tty.println_ascii("done".as_bytes());
-                }
-                Err(PS2Error::TimeOut) => tty.println_ascii("Timeout".as_bytes()),
-                Err(PS2Error::Parity) => tty.println_ascii("Parity".as_bytes()),
-            }
-        }
+/// The last configuration byte written. Reading it back from the controller
+/// once interrupts are enabled would race with the interrupt handlers, which
+/// may take the answer from the output buffer.
+static CONFIG: AtomicU8 = AtomicU8::new(0);
+
+/// What `init` found.
+#[derive(Clone, Copy)]
+pub struct PS2Controller {
+    pub dual_channel: bool,
+    /// Whether each port passed its interface test and its device reset.
+    pub working: [bool; 2],
+}
+
+impl PS2Controller {
+    pub fn is_working(&self, port: PS2Port) -> bool {
+        self.working[port as usize]
+    }
+
+    /// The current configuration byte.
+    pub fn config(&self) -> u8 {
+        CONFIG.load(Ordering::Relaxed)
+    }
+
+    /// Lets the device on a port raise its IRQ, 1 for the first port and 12
+    /// for the second.
+    pub fn enable_interrupt(&self, port: PS2Port) -> Result<(), PS2Error> {
+        write_config(self.config() | interrupt_bit(port))
+    }
+
+    /// Stops the device on a port from raising its IRQ, so it can be polled.
+    pub fn disable_interrupt(&self, port: PS2Port) -> Result<(), PS2Error> {
+        write_config(self.config() & !interrupt_bit(port))
+    }
+
+    /// Turns the translation of scan code set 2 to set 1 on the first port on
+    /// or off.
+    pub fn set_translation(&self, enabled: bool) -> Result<(), PS2Error> {
+        let config = self.config();
+        write_config(if enabled {
+            config | CONFIG_PORT1_TRANSLATION
+        } else {
+            config & !CONFIG_PORT1_TRANSLATION
+        })
+    }
+
+    pub fn translation(&self) -> bool {
+        read_bit_mask(self.config(), CONFIG_PORT1_TRANSLATION)
+    }
+}
+
+fn interrupt_bit(port: PS2Port) -> u8 {
+    match port {
+        PS2Port::First => CONFIG_PORT1_INTERRUPT,
+        PS2Port::Second => CONFIG_PORT2_INTERRUPT,
     }
 }
-*/
 
-unsafe fn read_status() -> Result<PS2Status, PS2Error> {
-    let status_reg = read_port_byte(Port::PS2StatusCmdReg as u16);
+pub fn read_status() -> Result<PS2Status, PS2Error> {
+    let status_reg = read_port_byte(Port::PS2StatusCmdReg.into());
     if read_bit_mask(status_reg, TIME_OUT_ERROR) {
         return Err(PS2Error::TimeOut);
     }
@@ -71,54 +190,244 @@ unsafe fn read_status() -> Result<PS2Status, PS2Error> {
         input_buf_full: read_bit_mask(status_reg, INPUT_BUFFER_STATUS),
         sys_flag: read_bit_mask(status_reg, SYSTEM_FLAG),
         data_for_device: read_bit_mask(status_reg, COMMAND_OR_DATA),
+        from_second_port: read_bit_mask(status_reg, AUX_OUTPUT_BUFFER),
     })
 }
 
-pub fn init_ps2() {
-    // TODO Init USB
-    // TODO Check PS/2 exists.
-    // TODO disable data during initialisation.
-    // TODO flush output buffer
-    // TODO set configuration byte
-    // TODO perform self test
-    // TODO check if there are 2 channels
-    // TODO perform interface tests
-    // TODO enable devices
-    // TODO reset devices
+/// Polls the status register until `ready` holds, for about `timeout_us`.
+fn wait_for(ready: impl Fn(u8) -> bool, timeout_us: u32) -> Result<(), PS2Error> {
+    for _ in 0..timeout_us {
+        if ready(read_port_byte(Port::PS2StatusCmdReg.into())) {
+            return Ok(());
+        }
+        io_wait();
+    }
+    Err(PS2Error::TimeOut)
+}
+
+fn write_command(command: ControllerCommand) -> Result<(), PS2Error> {
+    wait_for(
+        |status| !read_bit_mask(status, INPUT_BUFFER_STATUS),
+        TIMEOUT_US,
+    )?;
+    write_port_byte(Port::PS2StatusCmdReg.into(), command as u8);
+    Ok(())
+}
+
+fn write_data(data: u8) -> Result<(), PS2Error> {
+    wait_for(
+        |status| !read_bit_mask(status, INPUT_BUFFER_STATUS),
+        TIMEOUT_US,
+    )?;
+    write_port_byte(Port::PS2DataPort.into(), data);
+    Ok(())
+}
+
+/// Reads a byte from the output buffer, waiting up to `timeout_us` for one.
+pub fn read_data_timeout(timeout_us: u32) -> Result<u8, PS2Error> {
+    wait_for(
+        |status| read_bit_mask(status, OUTPUT_BUFFER_STATUS),
+        timeout_us,
+    )?;
+    let status = read_port_byte(Port::PS2StatusCmdReg.into());
+    let data = read_port_byte(Port::PS2DataPort.into());
+    if read_bit_mask(status, PARITY_ERROR) {
+        return Err(PS2Error::Parity);
+    }
+    Ok(data)
+}
+
+pub fn read_data() -> Result<u8, PS2Error> {
+    read_data_timeout(TIMEOUT_US)
+}
+
+/// Discards anything left in the output buffer.
+pub fn flush() {
+    for _ in 0..MAX_FLUSH {
+        let status = read_port_byte(Port::PS2StatusCmdReg.into());
+        if !read_bit_mask(status, OUTPUT_BUFFER_STATUS) {
+            return;
+        }
+        read_port_byte(Port::PS2DataPort.into());
+    }
+}
+
+fn read_config() -> Result<u8, PS2Error> {
+    write_command(ControllerCommand::ReadConfig)?;
+    read_data()
+}
+
+fn write_config(config: u8) -> Result<(), PS2Error> {
+    write_command(ControllerCommand::WriteConfig)?;
+    write_data(config)?;
+    CONFIG.store(config, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Sends a byte to the device on a port, without waiting for an answer.
+pub fn write_device(port: PS2Port, data: u8) -> Result<(), PS2Error> {
+    if port == PS2Port::Second {
+        write_command(ControllerCommand::WritePort2)?;
+    }
+    write_data(data)
+}
+
+/// Sends a command or its argument to a device and waits for the ACK,
+/// resending the byte if the device asks for it.
+pub fn device_command(port: PS2Port, data: u8) -> Result<(), PS2Error> {
+    let mut response = DEVICE_RESEND;
+    for _ in 0..MAX_RESENDS {
+        write_device(port, data)?;
+        response = read_data()?;
+        if response != DEVICE_RESEND {
+            break;
+        }
+    }
+    if response == DEVICE_ACK {
+        Ok(())
+    } else {
+        Err(PS2Error::NoAck(response))
+    }
+}
+
+/// Resets a device. Devices answer with an ACK and the self test result, in
+/// either order; mice then send their ID, which is discarded.
+fn reset_device(port: PS2Port) -> Result<(), PS2Error> {
+    write_device(port, PS2DeviceCommand::Reset as u8)?;
+    let mut acked = false;
+    let mut passed = false;
+    while !(acked && passed) {
+        match read_data_timeout(RESET_TIMEOUT_US)? {
+            DEVICE_ACK => acked = true,
+            DEVICE_SELF_TEST_PASSED => passed = true,
+            other => return Err(PS2Error::ResetFailed(other)),
+        }
+    }
+    flush();
+    Ok(())
+}
+
+/// Reads the ID of the device on a port, with scanning disabled meanwhile.
+/// Keyboards answer with two bytes, e.g. 0xAB 0x83, mice with one. Missing
+/// bytes are returned as 0xFF.
+pub fn identify(port: PS2Port) -> Result<[u8; 2], PS2Error> {
+    device_command(port, PS2DeviceCommand::DisableScanning as u8)?;
+    let id = read_id(port)?;
+    device_command(port, PS2DeviceCommand::EnableScanning as u8)?;
+    Ok(id)
+}
+
+/// Like `identify`, for a device that already has scanning disabled.
+pub fn read_id(port: PS2Port) -> Result<[u8; 2], PS2Error> {
+    device_command(port, PS2DeviceCommand::Identify as u8)?;
+    let mut id = [0xFF; 2];
+    for byte in id.iter_mut() {
+        match read_data() {
+            Ok(b) => *byte = b,
+            Err(PS2Error::TimeOut) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(id)
+}
+
+/// Initialises the controller and resets its devices. Interrupts are only
+/// enabled for the first port, the second is left to its driver.
+///
+/// Must be called before the keyboard IRQ is unmasked, since the controller
+/// is polled.
+pub fn init_ps2(acpi: Option<&ACPI>) -> Result<PS2Controller, PS2Error> {
+    // The FADT may tell there is no controller, its ports are then left alone.
+    if let Some(fadt) = acpi.and_then(|acpi| unsafe { FADT::from_acpi(acpi) })
+        && !fadt.has_8042()
+    {
+        return Err(PS2Error::NoController);
+    }
+    // A missing controller reads as all ones on an open bus.
+    if read_port_byte(Port::PS2StatusCmdReg.into()) == 0xFF {
+        return Err(PS2Error::NoController);
+    }
+
+    // Disable the devices so they can't interfere, then drop what they sent.
+    write_command(ControllerCommand::DisablePort1).map_err(|_| PS2Error::NoController)?;
+    write_command(ControllerCommand::DisablePort2)?;
+    flush();
+
+    let mut config = read_config()?;
+    config &= !(CONFIG_PORT1_INTERRUPT | CONFIG_PORT2_INTERRUPT | CONFIG_PORT1_CLOCK_DISABLED);
+    config |= CONFIG_PORT1_TRANSLATION;
+    write_config(config)?;
+
+    // The self test may reset the controller, so the configuration is
+    // written again afterwards.
+    write_command(ControllerCommand::SelfTest)?;
+    match read_data()? {
+        SELF_TEST_PASSED => {}
+        result => return Err(PS2Error::SelfTestFailed(result)),
+    }
+    write_config(config)?;
+
+    // Enabling the second port clears its clock disable bit only if the
+    // controller has one.
+    write_command(ControllerCommand::EnablePort2)?;
+    let dual_channel = !read_bit_mask(read_config()?, CONFIG_PORT2_CLOCK_DISABLED);
+    if dual_channel {
+        write_command(ControllerCommand::DisablePort2)?;
+        config &= !CONFIG_PORT2_CLOCK_DISABLED;
+        write_config(config)?;
+    }
+
+    let mut working = [false; 2];
+    write_command(ControllerCommand::TestPort1)?;
+    working[0] = read_data()? == PORT_TEST_PASSED;
+    if dual_channel {
+        write_command(ControllerCommand::TestPort2)?;
+        working[1] = read_data()? == PORT_TEST_PASSED;
+    }
+    if !working.contains(&true) {
+        return Err(PS2Error::NoWorkingPort);
+    }
+
+    if working[0] {
+        write_command(ControllerCommand::EnablePort1)?;
+        working[0] = reset_device(PS2Port::First).is_ok();
+    }
+    if working[1] {
+        write_command(ControllerCommand::EnablePort2)?;
+        working[1] = reset_device(PS2Port::Second).is_ok();
+    }
+
+    if working[0] {
+        config |= CONFIG_PORT1_INTERRUPT;
+    } else {
+        config |= CONFIG_PORT1_CLOCK_DISABLED;
+    }
+    write_config(config)?;
+    flush();
+
+    Ok(PS2Controller {
+        dual_channel,
+        working,
+    })
 }
 
 pub enum KeyboardInitError {
-    NoDisableAck,
-    NoIdentiyAck,
-    Unknown,
+    /// The first port has no working device.
+    NoKeyboard,
+    Identify(PS2Error),
+}
+
+impl KeyboardInitError {
+    pub fn describe(&self) -> (&'static str, Option<u8>) {
+        match self {
+            KeyboardInitError::NoKeyboard => ("no keyboard", None),
+            KeyboardInitError::Identify(err) => err.describe(),
+        }
+    }
 }
 
+/// Identifies the keyboard on the first port.
 pub fn identity_devices() -> Result<(u8, u8), KeyboardInitError> {
-    // Send disable scanning command (0xF5) to device
-    write_port_byte(
-        Port::PS2DataPort as u16,
-        PS2DeviceCommand::DisableScanning as u8,
-    );
-    // Wait for ACK (0xFA)
-    if read_port_byte(Port::PS2DataPort as u16) != 0xFA {
-        return Err(KeyboardInitError::NoDisableAck);
-    }
-
-    // Send identify command (0xF2)
-    write_port_byte(Port::PS2DataPort as u16, PS2DeviceCommand::Identify as u8);
-
-    // Wait for ACK (0xFA)
-    if read_port_byte(Port::PS2DataPort as u16) != 0xFA {
-        return Err(KeyboardInitError::NoIdentiyAck);
-    }
-
-    // Wait for reply and/or timeout
-    let b1 = read_port_byte(Port::PS2DataPort as u16);
-    let b2 = read_port_byte(Port::PS2DataPort as u16);
-    // Send enable scanning command (0xF4)
-    write_port_byte(
-        Port::PS2DataPort as u16,
-        PS2DeviceCommand::EnableScanning as u8,
-    );
+    let [b1, b2] = identify(PS2Port::First).map_err(KeyboardInitError::Identify)?;
     Ok((b1, b2))
 }
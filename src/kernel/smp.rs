@@ -0,0 +1,219 @@
+// Symmetric multiprocessing
+//
+// The bootstrap processor (BSP) starts every application processor (AP) in the
+// MADT with the INIT-SIPI-SIPI sequence. APs enter through the trampoline in
+// `platform::i386::ap_trampoline`, set up their own descriptor tables and then
+// run tasks from the kernel's run queue.
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+
+use crate::{
+    KERNEL,
+    kernel::{
+        acpi::{
+            acpi::ACPI,
+            madt::{LOCAL_APIC_ENABLED, MADT, MADTEntry},
+        },
+        apic::{self, ICR_ALL_EXCLUDING_SELF, ICR_INIT, ICR_LEVEL_ASSERT, ICR_STARTUP, LocalAPIC},
+        cpu,
+        interrupt_handlers::IRQ_BASE_VECTOR,
+        isr::load_idt,
+        kernel::Kernel,
+        platform::i386::ap_trampoline::{
+            ap_trampoline_end, ap_trampoline_gdtr, ap_trampoline_start,
+        },
+        ports::io_delay_us,
+        tss::DOUBLE_FAULT_STACK_SIZE,
+    },
+};
+
+/// The IRQ line used to wake up idle APs when tasks are queued.
+pub const WAKE_IRQ: u8 = 25;
+
+const PAGE_SIZE: usize = 0x1000;
+const REAL_MODE_LIMIT: usize = 0x100000;
+const AP_STACK_SIZE: usize = 0x4000;
+const INIT_DELAY_US: u32 = 10_000;
+const STARTUP_DELAY_US: u32 = 200;
+const ONLINE_TIMEOUT_US: u32 = 100_000;
+
+/// Stack pointer the next AP loads in `ap_protected_entry`.
+pub static AP_STACK_TOP: AtomicU32 = AtomicU32::new(0);
+/// Per-CPU slot of the next AP.
+static AP_INDEX: AtomicUsize = AtomicUsize::new(0);
+static AP_DOUBLE_FAULT_STACK_TOP: AtomicU32 = AtomicU32::new(0);
+
+/// Starts all enabled APs listed in the MADT, one at a time. Requires the APIC
+/// to be enabled. Returns the number of APs that came online.
+pub unsafe fn start_application_processors(kernel: &Kernel) -> usize {
+    unsafe {
+        let Some(lapic) = LocalAPIC::get() else {
+            return 0;
+        };
+        let Some(acpi) = ACPI::load() else {
+            return 0;
+        };
+        let Some(madt) = MADT::from_acpi(&acpi) else {
+            return 0;
+        };
+
+        let Some(vector) = install_trampoline(kernel) else {
+            return 0;
+        };
+
+        let bsp_apic_id = lapic.id();
+        let mut started = 0;
+        let mut iter = madt.iter();
+        while let Some(entry) = iter.next() {
+            let MADTEntry::LocalAPIC { apic_id, flags, .. } = entry else {
+                continue;
+            };
+            if apic_id == bsp_apic_id || flags & LOCAL_APIC_ENABLED == 0 {
+                continue;
+            }
+            let Some(index) = cpu::add(apic_id) else {
+                break;
+            };
+
+            let stacks = {
+                let mut mem = kernel.memory_manager().lock();
+                (
+                    mem.malloc(AP_STACK_SIZE, true),
+                    mem.malloc(DOUBLE_FAULT_STACK_SIZE, true),
+                )
+            };
+            let (Some(stack), Some(double_fault_stack)) = stacks else {
+                break;
+            };
+            let (stack, double_fault_stack) = (stack as usize, double_fault_stack as usize);
+            AP_INDEX.store(index, Ordering::Release);
+            AP_STACK_TOP.store((stack + AP_STACK_SIZE) as u32, Ordering::Release);
+            AP_DOUBLE_FAULT_STACK_TOP.store(
+                (double_fault_stack + DOUBLE_FAULT_STACK_SIZE) as u32,
+                Ordering::Release,
+            );
+
+            if !start_ap(&lapic, apic_id, vector, index) {
+                // The AP may still come up later and would then use the next
+                // AP's stack, so stop here.
+                break;
+            }
+            started += 1;
+        }
+        started
+    }
+}
+
+/// Copies the real mode part of the trampoline to a page below 1 MiB and
+/// points its GDT register at the BSP's GDT. Returns the startup vector,
+/// which is the page number of the trampoline.
+unsafe fn install_trampoline(kernel: &Kernel) -> Option<u8> {
+    unsafe {
+        let page = kernel.memory_manager().lock().malloc(PAGE_SIZE, true)? as usize;
+        let start = &raw const ap_trampoline_start;
+        let len = (&raw const ap_trampoline_end as usize) - start as usize;
+        if page + PAGE_SIZE > REAL_MODE_LIMIT || len > PAGE_SIZE {
+            return None;
+        }
+        core::ptr::copy_nonoverlapping(start, page as *mut u8, len);
+
+        let gdtr_offset = (&raw const ap_trampoline_gdtr as usize) - start as usize;
+        let bsp_gdt = cpu::get(cpu::BSP_INDEX)?.gdt().reg();
+        let gdtr = (page + gdtr_offset) as *mut u8;
+        (gdtr as *mut u16).write_unaligned(bsp_gdt.limit);
+        (gdtr.add(2) as *mut u32).write_unaligned(bsp_gdt.base as u32);
+
+        Some((page / PAGE_SIZE) as u8)
+    }
+}
+
+/// Sends INIT-SIPI-SIPI to an AP and waits for it to come online.
+unsafe fn start_ap(lapic: &LocalAPIC, apic_id: u8, vector: u8, index: usize) -> bool {
+    let Some(cpu) = cpu::get(index) else {
+        return false;
+    };
+    lapic.send_ipi(apic_id, ICR_INIT | ICR_LEVEL_ASSERT);
+    io_delay_us(INIT_DELAY_US);
+    for _ in 0..2 {
+        lapic.send_ipi(apic_id, ICR_STARTUP | vector as u32);
+        io_delay_us(STARTUP_DELAY_US);
+        if cpu.online.load(Ordering::Acquire) {
+            return true;
+        }
+    }
+    for _ in 0..ONLINE_TIMEOUT_US / STARTUP_DELAY_US {
+        if cpu.online.load(Ordering::Acquire) {
+            return true;
+        }
+        io_delay_us(STARTUP_DELAY_US);
+    }
+    false
+}
+
+/// Entry point of an AP, called from the trampoline on its own stack.
+pub unsafe extern "C" fn ap_main() -> ! {
+    unsafe {
+        let index = AP_INDEX.load(Ordering::Acquire);
+        let cpu = cpu::get_mut(index);
+        let stack_bottom = AP_STACK_TOP.load(Ordering::Acquire) - AP_STACK_SIZE as u32;
+        cpu.load_descriptors(
+            AP_DOUBLE_FAULT_STACK_TOP.load(Ordering::Acquire),
+            stack_bottom,
+        );
+        load_idt();
+        apic::enable_local_apic();
+        cpu.online.store(true, Ordering::Release);
+        run_tasks(index)
+    }
+}
+
+/// Runs tasks from the kernel's run queue, sleeping until woken up by
+/// `wake_application_processors` when it is empty.
+unsafe fn run_tasks(index: usize) -> ! {
+    loop {
+        unsafe {
+            // Interrupts are disabled while checking the queue: `sti` only takes
+            // effect after the next instruction, so a wake-up arriving after the
+            // check still ends the `hlt`.
+            asm!("cli");
+            if !run_next_task(index) {
+                asm!("sti", "hlt");
+            }
+        }
+    }
+}
+
+/// Runs the next queued task, if there is one, on the CPU with the given index.
+pub unsafe fn run_next_task(index: usize) -> bool {
+    let task = match KERNEL.get() {
+        Ok(kernel) => kernel.process_manager().lock().next_task(),
+        Err(_) => None,
+    };
+    match task {
+        Some(task) => {
+            unsafe { asm!("sti") };
+            task();
+            if let Some(cpu) = cpu::get(index) {
+                cpu.tasks_run.fetch_add(1, Ordering::Relaxed);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Wakes up all idle APs, so they check the run queue.
+pub fn wake_application_processors() {
+    if cpu::count() <= 1 {
+        return;
+    }
+    if let Some(lapic) = LocalAPIC::get() {
+        lapic.send_ipi(
+            0,
+            ICR_ALL_EXCLUDING_SELF | (IRQ_BASE_VECTOR + WAKE_IRQ) as u32,
+        );
+    }
+}
@@ -0,0 +1,113 @@
+// Clock sources
+//
+// The clock uses the best counter available: an invariant TSC, then a 64 bit
+// HPET, then a TSC whose rate may vary, and the PIT tick as a last resort.
+// The source is chosen once at boot, so reading the clock takes no locks and
+// works in interrupt handlers.
+
+use once_cell_no_std::OnceCell;
+
+use crate::kernel::{
+    acpi::{acpi::ACPI, hpet::HPET},
+    time::{self, hpet::HPETCounter, tsc},
+};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+static CLOCK: OnceCell<Clock> = OnceCell::new();
+
+#[derive(Clone, Copy)]
+pub enum ClockSource {
+    TSC,
+    HPET(HPETCounter),
+    /// The PIT tick counter, with the resolution of a tick.
+    Tick,
+}
+
+pub struct Clock {
+    source: ClockSource,
+    /// Counter increments per second.
+    frequency: u64,
+    /// The counter value when the clock was set up.
+    start: u64,
+}
+
+impl Clock {
+    /// Picks the best clock source. The PIT must already be running, since the
+    /// tick is the fallback.
+    pub unsafe fn detect(acpi: Option<&ACPI>) -> Self {
+        let hpet = unsafe {
+            acpi.and_then(|acpi| HPET::from_acpi(acpi))
+                .and_then(|table| HPETCounter::new(table))
+        };
+        let tsc_frequency = if tsc::is_supported() {
+            tsc::calibrate(hpet.as_ref()).filter(|frequency| *frequency > 0)
+        } else {
+            None
+        };
+
+        let (source, frequency) = match (tsc_frequency, hpet) {
+            (Some(frequency), _) if tsc::is_invariant() => (ClockSource::TSC, frequency),
+            (_, Some(hpet)) if hpet.is_64_bit() => (ClockSource::HPET(hpet), hpet.frequency()),
+            (Some(frequency), _) => (ClockSource::TSC, frequency),
+            _ => (ClockSource::Tick, time::frequency() as u64),
+        };
+        let mut clock = Self {
+            source,
+            frequency,
+            start: 0,
+        };
+        clock.start = clock.counter();
+        clock
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.source {
+            ClockSource::TSC => "TSC",
+            ClockSource::HPET(_) => "HPET",
+            ClockSource::Tick => "PIT",
+        }
+    }
+
+    pub fn frequency(&self) -> u64 {
+        self.frequency
+    }
+
+    /// The raw value of the underlying counter.
+    pub fn counter(&self) -> u64 {
+        match &self.source {
+            ClockSource::TSC => tsc::read(),
+            ClockSource::HPET(hpet) => hpet.counter(),
+            ClockSource::Tick => time::ticks(),
+        }
+    }
+
+    /// Nanoseconds since the clock was set up. Whole seconds and the remainder
+    /// are converted separately, so the multiplication can't overflow.
+    pub fn nanos(&self) -> u64 {
+        let count = self.counter().wrapping_sub(self.start);
+        let (Some(seconds), Some(rest)) = (
+            count.checked_div(self.frequency),
+            count.checked_rem(self.frequency),
+        ) else {
+            return 0;
+        };
+        seconds * NANOS_PER_SECOND + rest * NANOS_PER_SECOND / self.frequency
+    }
+}
+
+/// Sets up the system clock. Does nothing if it's already set up.
+pub unsafe fn init(acpi: Option<&ACPI>) {
+    if CLOCK.get().is_none() {
+        let _ = CLOCK.set(unsafe { Clock::detect(acpi) });
+    }
+}
+
+pub fn clock() -> Option<&'static Clock> {
+    CLOCK.get()
+}
+
+/// Nanoseconds since the system clock was set up, 0 before that.
+pub fn nanos() -> u64 {
+    clock().map_or(0, |clock| clock.nanos())
+}
@@ -0,0 +1,96 @@
+// High Precision Event Timer
+//
+// The HPET has a main counter incrementing at a fixed rate of at least
+// 10 MHz, given in femtoseconds per tick by the capabilities register. Only
+// the main counter is used; the comparators are left disabled.
+
+use crate::kernel::acpi::{acpi::GenericAddress, hpet::HPET};
+
+const REG_CAPABILITIES: usize = 0x00;
+const REG_CONFIG: usize = 0x10;
+const REG_MAIN_COUNTER: usize = 0xF0;
+
+const CAPABILITY_64_BIT: u32 = 1 << 13;
+const CONFIG_ENABLE: u32 = 1 << 0;
+
+const FEMTOSECONDS_PER_SECOND: u64 = 1_000_000_000_000_000;
+/// The specification limits the period to 100 ns.
+const MAX_PERIOD_FS: u32 = 100_000_000;
+
+#[derive(Clone, Copy)]
+pub struct HPETCounter {
+    base: usize,
+    /// The duration of a tick, in femtoseconds.
+    period_fs: u32,
+    wide: bool,
+}
+
+impl HPETCounter {
+    /// Sets up the HPET described by the ACPI table and starts its main
+    /// counter. Returns `None` if its registers aren't addressable without
+    /// paging or it reports an invalid period.
+    pub unsafe fn new(table: &HPET) -> Option<Self> {
+        let address = table.base_address();
+        let addr = address.address;
+        if address.address_space != GenericAddress::SYSTEM_MEMORY || addr > u32::MAX as u64 {
+            return None;
+        }
+        let mut hpet = Self {
+            base: addr as usize,
+            period_fs: 0,
+            wide: false,
+        };
+        unsafe {
+            let capabilities = hpet.read(REG_CAPABILITIES);
+            hpet.period_fs = hpet.read(REG_CAPABILITIES + 4);
+            hpet.wide = capabilities & CAPABILITY_64_BIT != 0;
+            if hpet.period_fs == 0 || hpet.period_fs > MAX_PERIOD_FS {
+                return None;
+            }
+            let config = hpet.read(REG_CONFIG);
+            hpet.write(REG_CONFIG, config | CONFIG_ENABLE);
+        }
+        Some(hpet)
+    }
+
+    unsafe fn read(&self, reg: usize) -> u32 {
+        unsafe { ((self.base + reg) as *const u32).read_volatile() }
+    }
+
+    unsafe fn write(&self, reg: usize, value: u32) {
+        unsafe { ((self.base + reg) as *mut u32).write_volatile(value) }
+    }
+
+    /// The number of counter ticks per second.
+    pub fn frequency(&self) -> u64 {
+        FEMTOSECONDS_PER_SECOND / self.period_fs as u64
+    }
+
+    /// Whether the main counter is 64 bits wide, rather than 32.
+    pub fn is_64_bit(&self) -> bool {
+        self.wide
+    }
+
+    /// The value the main counter wraps around after.
+    pub fn mask(&self) -> u64 {
+        if self.wide { u64::MAX } else { u32::MAX as u64 }
+    }
+
+    /// Reads the main counter. The halves of a 64 bit counter are read
+    /// separately, so the upper half is read again to detect a carry in
+    /// between.
+    pub fn counter(&self) -> u64 {
+        unsafe {
+            if !self.wide {
+                return self.read(REG_MAIN_COUNTER) as u64;
+            }
+            loop {
+                let high = self.read(REG_MAIN_COUNTER + 4);
+                let low = self.read(REG_MAIN_COUNTER);
+                if self.read(REG_MAIN_COUNTER + 4) == high {
+                    return (high as u64) << 32 | low as u64;
+                }
+            }
+        }
+    }
+}
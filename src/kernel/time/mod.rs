@@ -0,0 +1,112 @@
+// Timekeeping
+//
+// The PIT interrupts at a fixed frequency and every interrupt advances the
+// tick counter, which is the kernel's monotonic clock. Kernel timers are
+// checked against it and their callbacks run from the main loop. For finer
+// timing, the clock reads the TSC or HPET.
+
+pub mod clock;
+pub mod hpet;
+pub mod pit;
+pub mod rtc;
+pub mod timers;
+pub mod tsc;
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::kernel::ports::io_delay_us;
+
+/// The tick rate the kernel runs the PIT at, in Hz.
+pub const DEFAULT_FREQUENCY: u32 = 1000;
+
+static FREQUENCY: AtomicU32 = AtomicU32::new(0);
+
+// The tick count is 64 bits wide, but there are only 32 bit atomics. The timer
+// interrupt is the only writer and makes `SEQUENCE` odd while it updates the
+// halves, so readers retry when they see an update in progress.
+static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+static TICKS_LOW: AtomicU32 = AtomicU32::new(0);
+static TICKS_HIGH: AtomicU32 = AtomicU32::new(0);
+
+/// The low 32 bits of the tick the next kernel timer expires at.
+static NEXT_DUE: AtomicU32 = AtomicU32::new(0);
+static TIMER_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Starts the PIT at roughly `hz` ticks per second and returns the actual
+/// frequency. Should only be called once, since the uptime is derived from
+/// the tick count and the current frequency.
+pub fn init(hz: u32) -> u32 {
+    let actual = pit::set_frequency(hz);
+    FREQUENCY.store(actual, Ordering::SeqCst);
+    actual
+}
+
+/// The number of ticks per second, 0 before the PIT is started.
+pub fn frequency() -> u32 {
+    FREQUENCY.load(Ordering::Relaxed)
+}
+
+/// Advances the tick counter. Must only be called by the timer interrupt.
+/// Returns whether a kernel timer is due.
+pub fn tick() -> bool {
+    SEQUENCE.fetch_add(1, Ordering::SeqCst);
+    let low = TICKS_LOW.load(Ordering::SeqCst).wrapping_add(1);
+    if low == 0 {
+        TICKS_HIGH.fetch_add(1, Ordering::SeqCst);
+    }
+    TICKS_LOW.store(low, Ordering::SeqCst);
+    SEQUENCE.fetch_add(1, Ordering::SeqCst);
+
+    TIMER_PENDING.load(Ordering::Relaxed)
+        && low.wrapping_sub(NEXT_DUE.load(Ordering::Relaxed)) as i32 >= 0
+}
+
+/// The number of ticks since the PIT was started.
+pub fn ticks() -> u64 {
+    loop {
+        let sequence = SEQUENCE.load(Ordering::SeqCst);
+        let high = TICKS_HIGH.load(Ordering::SeqCst);
+        let low = TICKS_LOW.load(Ordering::SeqCst);
+        if sequence.is_multiple_of(2) && SEQUENCE.load(Ordering::SeqCst) == sequence {
+            return (high as u64) << 32 | low as u64;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// The number of milliseconds since the PIT was started.
+pub fn uptime_ms() -> u64 {
+    (ticks() * 1000)
+        .checked_div(frequency() as u64)
+        .unwrap_or(0)
+}
+
+/// Converts milliseconds to ticks, rounding up. Returns `None` if the PIT
+/// isn't running.
+pub fn ms_to_ticks(ms: u32) -> Option<u64> {
+    match frequency() as u64 {
+        0 => None,
+        hz => Some((ms as u64 * hz).div_ceil(1000)),
+    }
+}
+
+/// Waits for at least `ms` milliseconds. Busy waits, so other tasks can only
+/// run on other CPUs meanwhile.
+pub fn sleep_ms(ms: u32) {
+    let Some(duration) = ms_to_ticks(ms) else {
+        io_delay_us(ms.saturating_mul(1000));
+        return;
+    };
+    // Waiting for one more tick, since the current one has partly passed.
+    let deadline = ticks() + duration + 1;
+    while ticks() < deadline {
+        core::hint::spin_loop();
+    }
+}
+
+fn set_next_due(deadline: Option<u64>) {
+    if let Some(deadline) = deadline {
+        NEXT_DUE.store(deadline as u32, Ordering::Relaxed);
+    }
+    TIMER_PENDING.store(deadline.is_some(), Ordering::Relaxed);
+}
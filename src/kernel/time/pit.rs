@@ -0,0 +1,75 @@
+// Programmable Interval Timer (8253/8254)
+//
+// Channel 0 counts down from a reload value at 1.193182 MHz and raises IRQ 0
+// each time it reaches zero. Channel 1 is unused and channel 2 drives the PC
+// speaker. The gate and output of channel 2 are in port 0x61, so it can also
+// be used to wait without interrupts.
+
+use crate::kernel::ports::{Port, read_port_byte, write_port_byte};
+
+/// The frequency the counters are decremented at, in Hz.
+pub const BASE_FREQUENCY: u32 = 1_193_182;
+/// The lowest rate the PIT can interrupt at, with a reload value of 65536.
+pub const MIN_FREQUENCY: u32 = BASE_FREQUENCY / 0x10000 + 1;
+
+const CHANNEL_0: u8 = 0b00 << 6;
+const CHANNEL_2: u8 = 0b10 << 6;
+const ACCESS_LOW_HIGH: u8 = 0b11 << 4;
+
+const PORT_B_CHANNEL_2_GATE: u8 = 1 << 0;
+const PORT_B_SPEAKER_ENABLE: u8 = 1 << 1;
+const PORT_B_CHANNEL_2_OUTPUT: u8 = 1 << 5;
+/// Polls of the channel 2 output before giving up on a PIT that doesn't count.
+const MAX_POLLS: u32 = 10_000_000;
+
+#[derive(Clone, Copy)]
+pub enum PITMode {
+    /// Counts down once and raises the output when reaching zero.
+    OneShot = 0b000 << 1,
+    /// Raises an interrupt every time the counter wraps.
+    RateGenerator = 0b010 << 1,
+}
+
+/// Programs channel 0 to interrupt at roughly `hz` times per second. Returns
+/// the actual frequency, which differs since the reload value is an integer.
+pub fn set_frequency(hz: u32) -> u32 {
+    let divisor = BASE_FREQUENCY / hz.clamp(MIN_FREQUENCY, BASE_FREQUENCY);
+    start(PITMode::RateGenerator, divisor as u16);
+    BASE_FREQUENCY / divisor
+}
+
+/// Starts channel 0 counting down from `count`. A count of 0 stands for 65536.
+pub fn start(mode: PITMode, count: u16) {
+    write_port_byte(
+        Port::PITCommand.into(),
+        CHANNEL_0 | ACCESS_LOW_HIGH | mode as u8,
+    );
+    write_port_byte(Port::PITChannel0.into(), count as u8);
+    write_port_byte(Port::PITChannel0.into(), (count >> 8) as u8);
+}
+
+/// Busy waits for `count` PIT cycles using channel 2, with the speaker
+/// disconnected. Returns false if the channel never finished counting.
+pub fn wait_cycles(count: u16) -> bool {
+    let port_b = read_port_byte(Port::SystemControlB.into());
+    write_port_byte(
+        Port::SystemControlB.into(),
+        (port_b & !PORT_B_SPEAKER_ENABLE) | PORT_B_CHANNEL_2_GATE,
+    );
+    write_port_byte(
+        Port::PITCommand.into(),
+        CHANNEL_2 | ACCESS_LOW_HIGH | PITMode::OneShot as u8,
+    );
+    write_port_byte(Port::PITChannel2.into(), count as u8);
+    write_port_byte(Port::PITChannel2.into(), (count >> 8) as u8);
+
+    let mut done = false;
+    for _ in 0..MAX_POLLS {
+        if read_port_byte(Port::SystemControlB.into()) & PORT_B_CHANNEL_2_OUTPUT != 0 {
+            done = true;
+            break;
+        }
+    }
+    write_port_byte(Port::SystemControlB.into(), port_b);
+    done
+}
@@ -0,0 +1,264 @@
+// CMOS real-time clock (MC146818)
+//
+// The RTC keeps the wall-clock time in CMOS registers, selected through port
+// 0x70 and accessed through port 0x71. Depending on status register B, values
+// are BCD or binary and hours are 12 or 24 hour based. The registers are
+// updated once a second, during which they must not be read.
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::kernel::{
+    acpi::{acpi::ACPI, fadt::FADT},
+    interrupt_handlers::{
+        IrqError, register_irq,
+        rtc::{RTC_IRQ, rtc_handler, rtc_ticks},
+    },
+    ports::{Port, io_wait, read_port_byte, write_port_byte},
+};
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+const REG_STATUS_C: u8 = 0x0C;
+const REG_STATUS_D: u8 = 0x0D;
+
+/// Set on the register index to keep NMIs disabled while the RTC is accessed.
+/// The bit stays in effect until the index is written without it.
+const NMI_DISABLE: u8 = 1 << 7;
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+const STATUS_A_RATE_MASK: u8 = 0x0F;
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const STATUS_B_PERIODIC_INTERRUPT: u8 = 1 << 6;
+const HOUR_PM: u8 = 1 << 7;
+const INTERRUPT_FLAG: u32 = 1 << 9;
+
+/// The periodic interrupt fires at `32768 >> (rate - 1)` Hz. Rates 1 and 2
+/// are too fast to be useful.
+const MIN_RATE: u8 = 3;
+const MAX_RATE: u8 = 15;
+const BASE_FREQUENCY: u32 = 32768;
+
+/// Used when the FADT doesn't name a century register.
+const DEFAULT_CENTURY: u16 = 20;
+/// Reads are repeated until two in a row agree, at most this many times.
+const MAX_READS: usize = 8;
+/// An update takes about 2 ms. A missing or broken RTC that never finishes
+/// one is given up on after this long.
+const UPDATE_TIMEOUT_US: u32 = 10_000;
+
+static CENTURY_REG: AtomicU8 = AtomicU8::new(0);
+
+pub enum RTCError {
+    InvalidRate,
+    /// The IRQ 8 handler couldn't be registered.
+    IrqUnavailable,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// The number of seconds since 1970-01-01 00:00:00 UTC, assuming the RTC
+    /// runs on UTC.
+    pub fn unix_timestamp(&self) -> u64 {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        let seconds = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        (days * 86400 + seconds).max(0) as u64
+    }
+}
+
+/// The number of days between 1970-01-01 and a date of the proleptic
+/// Gregorian calendar. Years are shifted to start in March, so the leap day
+/// is the last day of a year.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Uses the century register named by the FADT, if any.
+pub unsafe fn init(acpi: Option<&ACPI>) {
+    unsafe {
+        if let Some(fadt) = acpi.and_then(|acpi| FADT::from_acpi(acpi)) {
+            CENTURY_REG.store(fadt.century_reg(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Runs `f` with interrupts disabled, so that the RTC interrupt handler
+/// can't move the register index between selecting a register and accessing
+/// it.
+fn without_interrupts<T>(f: impl FnOnce() -> T) -> T {
+    let flags: u32;
+    unsafe { asm!("pushfd", "pop {}", "cli", out(reg) flags) };
+    let result = f();
+    if flags & INTERRUPT_FLAG != 0 {
+        unsafe { asm!("sti") };
+    }
+    result
+}
+
+fn read_reg(reg: u8) -> u8 {
+    without_interrupts(|| {
+        write_port_byte(Port::CMOSAddress.into(), NMI_DISABLE | reg);
+        let value = read_port_byte(Port::CMOSData.into());
+        enable_nmi();
+        value
+    })
+}
+
+fn write_reg(reg: u8, value: u8) {
+    without_interrupts(|| {
+        write_port_byte(Port::CMOSAddress.into(), NMI_DISABLE | reg);
+        write_port_byte(Port::CMOSData.into(), value);
+        enable_nmi();
+    })
+}
+
+/// Enables NMIs again after an access, leaving the index on the read-only
+/// status register D.
+fn enable_nmi() {
+    write_port_byte(Port::CMOSAddress.into(), REG_STATUS_D);
+}
+
+fn update_in_progress() -> bool {
+    read_reg(REG_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0
+}
+
+/// Waits for an update in progress to finish. Returns whether it did.
+fn wait_for_update() -> bool {
+    for _ in 0..UPDATE_TIMEOUT_US {
+        if !update_in_progress() {
+            return true;
+        }
+        io_wait();
+    }
+    false
+}
+
+/// The raw register values: seconds, minutes, hours, day, month, year and
+/// century. `None` if the RTC seems stuck in an update.
+fn read_raw() -> Option<[u8; 7]> {
+    if !wait_for_update() {
+        return None;
+    }
+    let century_reg = CENTURY_REG.load(Ordering::Relaxed);
+    Some([
+        read_reg(REG_SECONDS),
+        read_reg(REG_MINUTES),
+        read_reg(REG_HOURS),
+        read_reg(REG_DAY),
+        read_reg(REG_MONTH),
+        read_reg(REG_YEAR),
+        if century_reg != 0 {
+            read_reg(century_reg)
+        } else {
+            0
+        },
+    ])
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+/// Reads the current date and time. An update can still start between the
+/// check and the reads, so registers are read until two reads agree.
+/// Returns `None` when the RTC doesn't respond.
+pub fn now() -> Option<DateTime> {
+    let mut raw = read_raw()?;
+    for _ in 0..MAX_READS {
+        let again = read_raw()?;
+        if again == raw {
+            break;
+        }
+        raw = again;
+    }
+    let [second, minute, hour, day, month, year, century] = raw;
+
+    let status_b = read_reg(REG_STATUS_B);
+    let binary = status_b & STATUS_B_BINARY != 0;
+    let convert = |value: u8| if binary { value } else { from_bcd(value) };
+
+    // The PM flag is set in the hours register in either format.
+    let pm = hour & HOUR_PM != 0;
+    let mut hour = convert(hour & !HOUR_PM);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    let century = if century != 0 {
+        convert(century) as u16
+    } else {
+        DEFAULT_CENTURY
+    };
+    Some(DateTime {
+        year: century * 100 + convert(year) as u16,
+        month: convert(month),
+        day: convert(day),
+        hour,
+        minute: convert(minute),
+        second: convert(second),
+    })
+}
+
+/// Enables the periodic interrupt on IRQ 8 at `32768 >> (rate - 1)` Hz, for
+/// rates 3 (8192 Hz) to 15 (2 Hz), or changes its rate once enabled. Returns
+/// the frequency.
+///
+/// Interrupts are disabled meanwhile, since the IRQ handler is registered and
+/// the status registers are read and written back.
+pub unsafe fn enable_periodic_interrupt(rate: u8) -> Result<u32, RTCError> {
+    if !(MIN_RATE..=MAX_RATE).contains(&rate) {
+        return Err(RTCError::InvalidRate);
+    }
+    without_interrupts(|| match unsafe { register_irq(RTC_IRQ, rtc_handler) } {
+        Ok(()) | Err(IrqError::AlreadyRegistered) => {
+            let status_a = read_reg(REG_STATUS_A);
+            write_reg(REG_STATUS_A, (status_a & !STATUS_A_RATE_MASK) | rate);
+            let status_b = read_reg(REG_STATUS_B);
+            write_reg(REG_STATUS_B, status_b | STATUS_B_PERIODIC_INTERRUPT);
+            // The RTC raises no further interrupts until status register C
+            // is read.
+            acknowledge_interrupt();
+            Ok(BASE_FREQUENCY >> (rate - 1))
+        }
+        Err(_) => Err(RTCError::IrqUnavailable),
+    })
+}
+
+/// The number of periodic interrupts since they were enabled.
+pub fn periodic_ticks() -> u32 {
+    rtc_ticks()
+}
+
+/// Reading status register C acknowledges an RTC interrupt.
+pub fn acknowledge_interrupt() {
+    read_reg(REG_STATUS_C);
+}
@@ -0,0 +1,112 @@
+use crate::kernel::time::{ms_to_ticks, set_next_due, ticks};
+
+const MAX_TIMERS: usize = 16;
+
+/// Called when a timer expires.
+pub type TimerCallback = fn();
+
+#[derive(Clone, Copy)]
+pub enum TimerError {
+    Full,
+    UnknownTimer,
+    /// The system timer hasn't been started yet.
+    NotRunning,
+}
+
+/// Identifies a timer, so it can be cancelled later.
+#[derive(Clone, Copy)]
+pub struct TimerId(usize);
+
+#[derive(Clone, Copy)]
+struct Timer {
+    /// The tick the timer expires at.
+    deadline: u64,
+    /// The number of ticks between expiries, 0 for a one-shot timer.
+    period: u64,
+    callback: TimerCallback,
+}
+
+static TIMERS: spin::Mutex<[Option<Timer>; MAX_TIMERS]> = spin::Mutex::new([None; MAX_TIMERS]);
+
+/// Calls `callback` once, after `ms` milliseconds.
+pub fn add_oneshot(ms: u32, callback: TimerCallback) -> Result<TimerId, TimerError> {
+    add(ms, false, callback)
+}
+
+/// Calls `callback` every `ms` milliseconds, until the timer is cancelled.
+pub fn add_periodic(ms: u32, callback: TimerCallback) -> Result<TimerId, TimerError> {
+    add(ms, true, callback)
+}
+
+fn add(ms: u32, periodic: bool, callback: TimerCallback) -> Result<TimerId, TimerError> {
+    let period = ms_to_ticks(ms).ok_or(TimerError::NotRunning)?.max(1);
+    let timer = Timer {
+        deadline: ticks() + period,
+        period: if periodic { period } else { 0 },
+        callback,
+    };
+    let mut timers = TIMERS.lock();
+    let (index, slot) = timers
+        .iter_mut()
+        .enumerate()
+        .find(|(_, slot)| slot.is_none())
+        .ok_or(TimerError::Full)?;
+    *slot = Some(timer);
+    update_next_due(&timers);
+    Ok(TimerId(index))
+}
+
+pub fn cancel(id: TimerId) -> Result<(), TimerError> {
+    let mut timers = TIMERS.lock();
+    match timers.get_mut(id.0) {
+        Some(slot @ Some(_)) => {
+            *slot = None;
+            update_next_due(&timers);
+            Ok(())
+        }
+        _ => Err(TimerError::UnknownTimer),
+    }
+}
+
+/// Calls the callbacks of the timers that expired. Must be called from the
+/// kernel's main loop, so callbacks don't run in interrupt context and may add
+/// or cancel timers themselves.
+pub fn run_expired() {
+    let now = ticks();
+    loop {
+        let callback = {
+            let mut timers = TIMERS.lock();
+            let mut callback = None;
+            for slot in timers.iter_mut() {
+                let Some(timer) = slot else {
+                    continue;
+                };
+                if timer.deadline > now {
+                    continue;
+                }
+                callback = Some(timer.callback);
+                if timer.period == 0 {
+                    *slot = None;
+                } else {
+                    // A periodic timer that fell behind skips the missed
+                    // periods rather than firing for each of them.
+                    timer.deadline += timer.period;
+                    if timer.deadline <= now {
+                        timer.deadline = now + timer.period;
+                    }
+                }
+                break;
+            }
+            update_next_due(&timers);
+            callback
+        };
+        match callback {
+            Some(callback) => callback(),
+            None => return,
+        }
+    }
+}
+
+fn update_next_due(timers: &[Option<Timer>; MAX_TIMERS]) {
+    set_next_due(timers.iter().flatten().map(|timer| timer.deadline).min());
+}
@@ -0,0 +1,75 @@
+// Time Stamp Counter
+//
+// The TSC counts CPU cycles. Its rate is unknown, so it's calibrated against
+// a timer of known frequency. Older CPUs change the rate with the clock speed;
+// with an invariant TSC it's constant.
+
+use core::arch::{asm, x86::__cpuid};
+
+use crate::kernel::time::{hpet::HPETCounter, pit};
+
+const CPUID_FEATURE_TSC: u32 = 1 << 4;
+const CPUID_EXTENDED_MAX: u32 = 0x8000_0000;
+const CPUID_ADVANCED_POWER: u32 = 0x8000_0007;
+const CPUID_INVARIANT_TSC: u32 = 1 << 8;
+
+/// Calibration measures over this fraction of a second, i.e. 10 ms.
+const CALIBRATION_DIVISOR: u64 = 100;
+/// Reads of the HPET counter before giving up on one that doesn't count.
+const MAX_POLLS: u32 = 10_000_000;
+
+pub fn is_supported() -> bool {
+    __cpuid(1).edx & CPUID_FEATURE_TSC != 0
+}
+
+/// Whether the TSC runs at a constant rate regardless of power states.
+pub fn is_invariant() -> bool {
+    __cpuid(CPUID_EXTENDED_MAX).eax >= CPUID_ADVANCED_POWER
+        && __cpuid(CPUID_ADVANCED_POWER).edx & CPUID_INVARIANT_TSC != 0
+}
+
+pub fn read() -> u64 {
+    let (low, high): (u32, u32);
+    unsafe {
+        asm!(
+            "rdtsc",
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags),
+        )
+    };
+    (high as u64) << 32 | low as u64
+}
+
+/// Measures the TSC frequency against the HPET if there is one, otherwise
+/// against PIT channel 2. Returns `None` if the reference timer doesn't run.
+pub fn calibrate(hpet: Option<&HPETCounter>) -> Option<u64> {
+    match hpet {
+        Some(hpet) => {
+            let wait = (hpet.frequency() / CALIBRATION_DIVISOR).max(1);
+            let start = hpet.counter();
+            let tsc_start = read();
+            let mut elapsed = 0;
+            let mut polls = 0;
+            // The counter may be 32 bits wide, so only the difference counts.
+            while elapsed < wait {
+                if polls == MAX_POLLS {
+                    return None;
+                }
+                polls += 1;
+                elapsed = hpet.counter().wrapping_sub(start) & hpet.mask();
+            }
+            let cycles = read() - tsc_start;
+            Some(cycles * hpet.frequency() / elapsed)
+        }
+        None => {
+            let count = (pit::BASE_FREQUENCY as u64 / CALIBRATION_DIVISOR) as u16;
+            let tsc_start = read();
+            if !pit::wait_cycles(count) {
+                return None;
+            }
+            let cycles = read() - tsc_start;
+            Some(cycles * CALIBRATION_DIVISOR)
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// Task State Segments
+//
+// Hardware task switching is only used for the double fault handler. When a
+// double fault occurs, the CPU saves the state of the running code in the
+// kernel TSS and loads the double fault TSS, which runs the handler on its own
+// stack. This way, a fault that leaves the kernel stack unusable, such as a
+// stack overflow caught by the stack segment limit (see `kernel::gdt`) or a
+// corrupted stack pointer, can still be reported.
+// Each CPU has its own pair of TSSs, see `kernel::cpu`.
+
+use core::arch::asm;
+
+use crate::kernel::{
+    gdt::{KERNEL_CS, KERNEL_DS},
+    interrupt_handlers::exception::double_fault_task,
+};
+
+pub const DOUBLE_FAULT_STACK_SIZE: usize = 4096;
+const EFLAGS_RESERVED: u32 = 1 << 1;
+
+#[repr(C, align(16))]
+struct Stack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
+/// Double fault stack of the bootstrap processor. Application processors
+/// get theirs from the memory manager.
+static mut DOUBLE_FAULT_STACK: Stack = Stack([0; DOUBLE_FAULT_STACK_SIZE]);
+
+/// A 32 bit TSS. Segment selector fields are 16 bits wide, the upper 16
+/// bits are reserved.
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct TaskStateSegment {
+    pub link: u32,
+    pub esp0: u32,
+    pub ss0: u32,
+    pub esp1: u32,
+    pub ss1: u32,
+    pub esp2: u32,
+    pub ss2: u32,
+    pub cr3: u32,
+    pub eip: u32,
+    pub eflags: u32,
+    pub eax: u32,
+    pub ecx: u32,
+    pub edx: u32,
+    pub ebx: u32,
+    pub esp: u32,
+    pub ebp: u32,
+    pub esi: u32,
+    pub edi: u32,
+    pub es: u32,
+    pub cs: u32,
+    pub ss: u32,
+    pub ds: u32,
+    pub fs: u32,
+    pub gs: u32,
+    pub ldtr: u32,
+    pub trap: u16,
+    pub iomap_base: u16,
+}
+
+impl TaskStateSegment {
+    pub const fn new() -> Self {
+        Self {
+            link: 0,
+            esp0: 0,
+            ss0: 0,
+            esp1: 0,
+            ss1: 0,
+            esp2: 0,
+            ss2: 0,
+            cr3: 0,
+            eip: 0,
+            eflags: 0,
+            eax: 0,
+            ecx: 0,
+            edx: 0,
+            ebx: 0,
+            esp: 0,
+            ebp: 0,
+            esi: 0,
+            edi: 0,
+            es: 0,
+            cs: 0,
+            ss: 0,
+            ds: 0,
+            fs: 0,
+            gs: 0,
+            ldtr: 0,
+            trap: 0,
+            // No I/O permission bitmap.
+            iomap_base: core::mem::size_of::<Self>() as u16,
+        }
+    }
+}
+
+pub fn bsp_double_fault_stack_top() -> u32 {
+    (&raw const DOUBLE_FAULT_STACK as usize + DOUBLE_FAULT_STACK_SIZE) as u32
+}
+
+/// Fills in the kernel and double fault TSSs of a CPU. Must be called before
+/// their descriptors are loaded into the GDT.
+pub unsafe fn init_tss(
+    kernel_tss: &mut TaskStateSegment,
+    double_fault_tss: &mut TaskStateSegment,
+    double_fault_stack_top: u32,
+) {
+    let cr3: u32;
+    unsafe { asm!("mov {}, cr3", out(reg) cr3, options(nomem, nostack, preserves_flags)) };
+
+    kernel_tss.ss0 = KERNEL_DS as u32;
+    kernel_tss.cr3 = cr3;
+
+    double_fault_tss.esp0 = double_fault_stack_top;
+    double_fault_tss.ss0 = KERNEL_DS as u32;
+    double_fault_tss.cr3 = cr3;
+    double_fault_tss.eip = double_fault_task as extern "C" fn() -> ! as usize as u32;
+    // Interrupts stay disabled while handling the double fault.
+    double_fault_tss.eflags = EFLAGS_RESERVED;
+    double_fault_tss.esp = double_fault_stack_top;
+    double_fault_tss.ebp = double_fault_stack_top;
+    double_fault_tss.cs = KERNEL_CS as u32;
+    double_fault_tss.ss = KERNEL_DS as u32;
+    double_fault_tss.ds = KERNEL_DS as u32;
+    double_fault_tss.es = KERNEL_DS as u32;
+    double_fault_tss.fs = KERNEL_DS as u32;
+    double_fault_tss.gs = KERNEL_DS as u32;
+}
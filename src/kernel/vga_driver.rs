@@ -1,26 +1,254 @@
-use core::ptr;
+use core::{
+    ptr, slice,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
 
 use crate::{
-    kernel::ports::{read_port_byte, write_port_byte},
-    vga::{Port, VGA},
+    kernel::{
+        pci::{
+            config::ConfigSpace,
+            device::{COMMAND_IO_SPACE, COMMAND_MEMORY_SPACE, PCIDevice},
+            driver::{PCIDriver, PCIMatch},
+        },
+        ports::{read_port_byte, write_port_byte},
+        vga_modes::{ModeRegisters, VideoMode},
+    },
+    vga::{AttributeRegister, GraphicsRegister, Port, SequencerRegister, VGA},
 };
 
 const VIDEO_MEM: *mut u8 = 0xb8000 as *mut u8;
-pub const WIDTH: u16 = 80;
-pub const HEIGHT: u16 = 25;
-pub const SCREEN_SIZE: u16 = WIDTH * HEIGHT;
+/// Where graphics modes map video memory.
+const GRAPHICS_MEM: *mut u8 = 0xa0000 as *mut u8;
+const GRAPHICS_MEM_SIZE: usize = 0x10000;
+/// The largest text mode, which buffers of screen contents are sized for.
+pub const MAX_WIDTH: u16 = 90;
+pub const MAX_HEIGHT: u16 = 60;
 pub const CHAR_SIZE: u16 = 2;
-pub const BUF_SIZE: u16 = CHAR_SIZE * SCREEN_SIZE;
 
+/// The characters of a font, each taking 32 bytes of plane 2 whatever the
+/// height of the font.
+const FONT_CHARS: usize = 256;
+const FONT_SLOT_SIZE: usize = 32;
+/// The height of the font the BIOS loads for 80x25.
+const BIOS_FONT_HEIGHT: usize = 16;
+const FONT_PLANE: u8 = 2;
+const FONT_SIZE: usize = FONT_CHARS * BIOS_FONT_HEIGHT;
+const ALL_PLANES: u8 = 0x0F;
+const MEMORY_MODE_SEQUENTIAL: u8 = 1 << 2;
+const GRAPHICS_MODE_ODD_EVEN: u8 = 1 << 4;
+const MISCELLANEOUS_CHAIN_ODD_EVEN: u8 = 1 << 1;
+const CRTC_UNLOCK: u8 = 1 << 7;
+const CRTC_PROTECT: u8 = 1 << 7;
+
+const PCI_CLASS_DISPLAY: u8 = 0x03;
+const PCI_SUBCLASS_VGA: u8 = 0x00;
+
+/// Binds the VGA compatible display controller, so `lspci` shows which
+/// device the text consoles are on.
+pub static PCI_DRIVER: PCIDriver = PCIDriver {
+    name: "vga",
+    matches: &[PCIMatch::Class {
+        class: PCI_CLASS_DISPLAY,
+        subclass: PCI_SUBCLASS_VGA,
+    }],
+    probe: probe_pci,
+};
+
+/// The index in `MODES` of the current mode, and of the text mode in use
+/// before it or the current one.
+static MODE: AtomicU32 = AtomicU32::new(0);
+static TEXT_MODE: AtomicU32 = AtomicU32::new(0);
+/// The BIOS font, saved the first time the VGA leaves 80x25, since the
+/// other modes overwrite it.
+static mut FONT: [u8; FONT_SIZE] = [0; FONT_SIZE];
+static FONT_SAVED: AtomicBool = AtomicBool::new(false);
+
+/// Set in the attribute controller's index to keep the screen on.
+const ATTRIBUTE_PALETTE_SOURCE: u8 = 1 << 5;
+/// Makes bit 7 of attributes blink characters rather than brighten their
+/// background.
+const MODE_CONTROL_BLINK: u8 = 1 << 3;
+const CURSOR_DISABLE: u8 = 1 << 5;
+const CURSOR_SCANLINE_MASK: u8 = 0x1F;
+
+/// The 16 colors of text mode.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Color {
+    Black = 0,
+    Blue = 1,
+    Green = 2,
+    Cyan = 3,
+    Red = 4,
+    Magenta = 5,
+    Brown = 6,
+    LightGray = 7,
+    DarkGray = 8,
+    LightBlue = 9,
+    LightGreen = 10,
+    LightCyan = 11,
+    LightRed = 12,
+    LightMagenta = 13,
+    Yellow = 14,
+    White = 15,
+}
+
+impl Color {
+    /// Every color, indexed by its value.
+    pub const ALL: [Color; 16] = [
+        Color::Black,
+        Color::Blue,
+        Color::Green,
+        Color::Cyan,
+        Color::Red,
+        Color::Magenta,
+        Color::Brown,
+        Color::LightGray,
+        Color::DarkGray,
+        Color::LightBlue,
+        Color::LightGreen,
+        Color::LightCyan,
+        Color::LightRed,
+        Color::LightMagenta,
+        Color::Yellow,
+        Color::White,
+    ];
+}
+
+/// The colors of a character, the foreground in the low nibble and the
+/// background in the high one. The top bit makes the character blink instead
+/// of brightening the background while blinking is enabled.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Attribute(pub u8);
+
+impl Attribute {
+    /// What the console has always used, white on black.
+    pub const DEFAULT: Self = Self::new(Color::White, Color::Black);
+    /// Brightens a color, or blinks when set in the background.
+    pub const INTENSITY: u8 = 1 << 3;
+
+    pub const fn new(foreground: Color, background: Color) -> Self {
+        Self((background as u8) << 4 | foreground as u8)
+    }
+
+    pub fn foreground(&self) -> u8 {
+        self.0 & 0x0F
+    }
+
+    pub fn background(&self) -> u8 {
+        self.0 >> 4
+    }
+
+    /// A character in this attribute, as stored in VGA memory.
+    pub const fn cell(&self, c: u8) -> u16 {
+        (self.0 as u16) << 8 | c as u16
+    }
+}
+
+/// A handle on the VGA. The mode is shared by all handles, and the text
+/// functions do nothing while a graphics mode is set.
 pub struct VGAText {}
 
 impl VGAText {
-    pub unsafe fn put_char_raw(&mut self, c: u8, x: u16, y: u16) {
+    pub fn mode(&self) -> VideoMode {
+        VideoMode::from_index(MODE.load(Ordering::Relaxed)).unwrap_or(VideoMode::Text80x25)
+    }
+
+    /// The current text mode, or the one to go back to from a graphics mode.
+    pub fn text_mode(&self) -> VideoMode {
+        VideoMode::from_index(TEXT_MODE.load(Ordering::Relaxed)).unwrap_or(VideoMode::Text80x25)
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.mode().text_size().is_some()
+    }
+
+    /// The columns of the text mode.
+    pub fn width(&self) -> u16 {
+        self.text_mode()
+            .text_size()
+            .map_or(MAX_WIDTH, |(width, _)| width)
+    }
+
+    /// The rows of the text mode.
+    pub fn height(&self) -> u16 {
+        self.text_mode()
+            .text_size()
+            .map_or(MAX_HEIGHT, |(_, height)| height)
+    }
+
+    /// Programs the registers for a mode. Entering a text mode loads a font
+    /// of the right height and leaves the screen as it was in video memory;
+    /// entering a graphics mode clears it.
+    pub unsafe fn set_mode(&mut self, mode: VideoMode) {
+        unsafe {
+            if self.mode() == VideoMode::Text80x25 && !FONT_SAVED.load(Ordering::Relaxed) {
+                self.save_font();
+                FONT_SAVED.store(true, Ordering::Relaxed);
+            }
+            self.write_registers(mode.registers());
+            MODE.store(mode.index(), Ordering::Relaxed);
+            match mode.font_height() {
+                Some(height) => {
+                    TEXT_MODE.store(mode.index(), Ordering::Relaxed);
+                    if FONT_SAVED.load(Ordering::Relaxed) {
+                        self.load_font(height as usize);
+                    }
+                }
+                None => {
+                    self.write_sequencer(SequencerRegister::MapMask, ALL_PLANES);
+                    ptr::write_bytes(GRAPHICS_MEM, 0, GRAPHICS_MEM_SIZE);
+                }
+            }
+        }
+    }
+
+    /// Sets a pixel in a graphics mode. Mode 13h takes any of its 256 colors,
+    /// mode 12h the low 4 bits.
+    pub unsafe fn put_pixel(&mut self, x: u16, y: u16, color: u8) {
+        let mode = self.mode();
+        let Some((width, height)) = mode.resolution() else {
+            return;
+        };
+        if x >= width || y >= height {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
         unsafe {
-            let char_addr: *mut u8 = VIDEO_MEM.add(2 * (y * WIDTH + x) as usize);
+            match mode {
+                VideoMode::Graphics320x200 => {
+                    GRAPHICS_MEM
+                        .add(y * width as usize + x)
+                        .write_volatile(color);
+                }
+                _ => {
+                    // Each bit of a byte is a pixel, on 4 planes. Set/reset
+                    // writes the color to all of them, for the bits the bit
+                    // mask lets through; reading first loads the latches that
+                    // keep the other bits.
+                    let addr = GRAPHICS_MEM.add((y * width as usize + x) / 8);
+                    self.write_sequencer(SequencerRegister::MapMask, ALL_PLANES);
+                    self.write_graphics(GraphicsRegister::SetReset, color & ALL_PLANES);
+                    self.write_graphics(GraphicsRegister::EnableSetReset, ALL_PLANES);
+                    self.write_graphics(GraphicsRegister::BitMask, 0x80 >> (x % 8));
+                    addr.read_volatile();
+                    addr.write_volatile(0);
+                    self.write_graphics(GraphicsRegister::EnableSetReset, 0);
+                    self.write_graphics(GraphicsRegister::BitMask, 0xFF);
+                }
+            }
+        }
+    }
+
+    pub unsafe fn put_char_raw(&mut self, c: u8, attribute: Attribute, x: u16, y: u16) {
+        if !self.is_text() || x >= self.width() || y >= self.height() {
+            return;
+        }
+        unsafe {
+            let char_addr: *mut u8 = VIDEO_MEM.add(2 * (y * self.width() + x) as usize);
             let col_addr = char_addr.add(1);
             char_addr.write_unaligned(c);
-            col_addr.write_unaligned(0x0f);
+            col_addr.write_unaligned(attribute.0);
         }
     }
 
@@ -33,7 +261,10 @@ impl VGAText {
     }
 
     pub fn update_cursor_position(&mut self, x: u16, y: u16) {
-        let offset = y * WIDTH + x;
+        if !self.is_text() {
+            return;
+        }
+        let offset = y * self.width() + x;
         let hi = offset >> 8;
         let lo = offset & 0x00ff;
         write_port_byte(Port::VGA3Out as u16, VGA::CursorLoByte as u8);
@@ -42,27 +273,243 @@ impl VGAText {
         write_port_byte(Port::VGA3In as u16, hi as u8);
     }
 
-    pub unsafe fn clear_row(&mut self, idx: u16) {
-        if idx >= HEIGHT {
+    /// Fills a row with spaces in `attribute`.
+    pub unsafe fn clear_row(&mut self, idx: u16, attribute: Attribute) {
+        if !self.is_text() || idx >= self.height() {
             return;
         }
-        let row_start = unsafe { VIDEO_MEM.add((CHAR_SIZE * WIDTH * idx) as usize) };
-        for i in 0..WIDTH {
+        let width = self.width();
+        let row_start = unsafe { VIDEO_MEM.add((CHAR_SIZE * width * idx) as usize) } as *mut u16;
+        for i in 0..width {
             unsafe {
-                let addr = row_start.add((i * CHAR_SIZE) as usize);
-                *addr = b' ';
-            }
+                row_start
+                    .add(i as usize)
+                    .write_unaligned(attribute.cell(b' '))
+            };
         }
     }
 
+    /// Chooses whether the top bit of attributes makes characters blink or
+    /// gives them a bright background.
+    pub fn set_blink(&mut self, blink: bool) {
+        let mode = self.read_attribute_register(AttributeRegister::ModeControl);
+        let mode = if blink {
+            mode | MODE_CONTROL_BLINK
+        } else {
+            mode & !MODE_CONTROL_BLINK
+        };
+        self.write_attribute_register(AttributeRegister::ModeControl, mode);
+    }
+
+    /// Sets the scanlines of the character cell the cursor covers, from the
+    /// top. The BIOS default for the 16 scanline font is 13 to 14. Scanlines
+    /// below the font are left out.
+    pub fn set_cursor_shape(&mut self, start: u8, end: u8) {
+        let last = self.read_crtc(VGA::MaximumScanLine) & CURSOR_SCANLINE_MASK;
+        let disabled = self.read_crtc(VGA::CursorStart) & CURSOR_DISABLE;
+        self.write_crtc(VGA::CursorStart, disabled | start.min(last));
+        let end_register = self.read_crtc(VGA::CursorEnd) & !CURSOR_SCANLINE_MASK;
+        self.write_crtc(VGA::CursorEnd, end_register | end.min(last));
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        let start = self.read_crtc(VGA::CursorStart);
+        let start = if visible {
+            start & !CURSOR_DISABLE
+        } else {
+            start | CURSOR_DISABLE
+        };
+        self.write_crtc(VGA::CursorStart, start);
+    }
+
+    fn read_crtc(&self, register: VGA) -> u8 {
+        write_port_byte(Port::VGA3Out as u16, register as u8);
+        read_port_byte(Port::VGA3In as u16)
+    }
+
+    fn write_crtc(&mut self, register: VGA, value: u8) {
+        write_port_byte(Port::VGA3Out as u16, register as u8);
+        write_port_byte(Port::VGA3In as u16, value);
+    }
+
+    fn read_attribute_register(&self, register: AttributeRegister) -> u8 {
+        read_port_byte(Port::InputStatus as u16);
+        write_port_byte(
+            Port::AttributeController as u16,
+            register as u8 | ATTRIBUTE_PALETTE_SOURCE,
+        );
+        read_port_byte(Port::AttributeRead as u16)
+    }
+
+    fn write_attribute_register(&mut self, register: AttributeRegister, value: u8) {
+        read_port_byte(Port::InputStatus as u16);
+        write_port_byte(
+            Port::AttributeController as u16,
+            register as u8 | ATTRIBUTE_PALETTE_SOURCE,
+        );
+        write_port_byte(Port::AttributeController as u16, value);
+    }
+
     pub fn copy_row(&mut self, src: u16, dst: u16) {
-        if src >= HEIGHT || dst >= HEIGHT {
+        let (width, height) = (self.width(), self.height());
+        if !self.is_text() || src >= height || dst >= height {
             return;
         }
         unsafe {
-            let src_row = VIDEO_MEM.add((CHAR_SIZE * WIDTH * src) as usize);
-            let dst_row = VIDEO_MEM.add((CHAR_SIZE * WIDTH * dst) as usize);
-            ptr::copy_nonoverlapping(src_row, dst_row, (WIDTH * CHAR_SIZE) as usize);
+            let src_row = VIDEO_MEM.add((CHAR_SIZE * width * src) as usize);
+            let dst_row = VIDEO_MEM.add((CHAR_SIZE * width * dst) as usize);
+            ptr::copy_nonoverlapping(src_row, dst_row, (width * CHAR_SIZE) as usize);
         }
     }
+
+    /// Fills a row with `cells`, a character and attribute byte each.
+    pub unsafe fn write_row(&mut self, idx: u16, cells: &[u16]) {
+        let width = self.width();
+        if !self.is_text() || idx >= self.height() {
+            return;
+        }
+        let len = cells.len().min(width as usize);
+        unsafe {
+            let row_start = VIDEO_MEM.add((CHAR_SIZE * width * idx) as usize) as *mut u16;
+            ptr::copy_nonoverlapping(cells.as_ptr(), row_start, len);
+        }
+    }
+
+    unsafe fn write_registers(&mut self, registers: &ModeRegisters) {
+        write_port_byte(Port::MiscOutput as u16, registers.misc);
+        for (index, value) in registers.sequencer.iter().enumerate() {
+            write_port_byte(Port::VGA1Out as u16, index as u8);
+            write_port_byte(Port::VGA1In as u16, *value);
+        }
+
+        // The CRTC timing registers are write protected until unlocked, and
+        // must stay unlocked while they are written.
+        let end_blanking = self.read_crtc(VGA::EndHorizontalBlanking);
+        self.write_crtc(VGA::EndHorizontalBlanking, end_blanking | CRTC_UNLOCK);
+        let retrace_end = self.read_crtc(VGA::VerticalRetraceEnd);
+        self.write_crtc(VGA::VerticalRetraceEnd, retrace_end & !CRTC_PROTECT);
+        for (index, value) in registers.crtc.iter().enumerate() {
+            let value = if index == VGA::EndHorizontalBlanking as usize {
+                value | CRTC_UNLOCK
+            } else if index == VGA::VerticalRetraceEnd as usize {
+                value & !CRTC_PROTECT
+            } else {
+                *value
+            };
+            write_port_byte(Port::VGA3Out as u16, index as u8);
+            write_port_byte(Port::VGA3In as u16, value);
+        }
+
+        for (index, value) in registers.graphics.iter().enumerate() {
+            write_port_byte(Port::VGA2Out as u16, index as u8);
+            write_port_byte(Port::VGA2In as u16, *value);
+        }
+
+        // Writing an index without the palette source bit blanks the screen
+        // so the palette can be changed, it is set again at the end.
+        for (index, value) in registers.attribute.iter().enumerate() {
+            read_port_byte(Port::InputStatus as u16);
+            write_port_byte(Port::AttributeController as u16, index as u8);
+            write_port_byte(Port::AttributeController as u16, *value);
+        }
+        read_port_byte(Port::InputStatus as u16);
+        write_port_byte(Port::AttributeController as u16, ATTRIBUTE_PALETTE_SOURCE);
+    }
+
+    /// Copies the font in use, which must be 16 scanlines high, out of plane 2.
+    unsafe fn save_font(&mut self) {
+        unsafe {
+            self.with_font_plane(|plane| {
+                let font = slice::from_raw_parts_mut((&raw mut FONT).cast::<u8>(), FONT_SIZE);
+                for (c, glyph) in font.chunks_exact_mut(BIOS_FONT_HEIGHT).enumerate() {
+                    for (line, row) in glyph.iter_mut().enumerate() {
+                        *row = plane.add(c * FONT_SLOT_SIZE + line).read_volatile();
+                    }
+                }
+            });
+        }
+    }
+
+    /// Loads the saved font into plane 2. An 8 scanline font is made by
+    /// merging pairs of scanlines.
+    unsafe fn load_font(&mut self, height: usize) {
+        unsafe {
+            self.with_font_plane(|plane| {
+                let font = slice::from_raw_parts((&raw const FONT).cast::<u8>(), FONT_SIZE);
+                for (c, glyph) in font.chunks_exact(BIOS_FONT_HEIGHT).enumerate() {
+                    for line in 0..FONT_SLOT_SIZE {
+                        let row = if height >= BIOS_FONT_HEIGHT {
+                            glyph.get(line).copied().unwrap_or(0)
+                        } else if line < height {
+                            let pair = glyph.get(line * 2..line * 2 + 2).unwrap_or(&[]);
+                            pair.iter().fold(0, |row, line| row | line)
+                        } else {
+                            0
+                        };
+                        plane.add(c * FONT_SLOT_SIZE + line).write_volatile(row);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Runs `f` with plane 2, where text modes keep the font, mapped flat at
+    /// the start of text mode video memory.
+    unsafe fn with_font_plane(&mut self, f: impl FnOnce(*mut u8)) {
+        let map_mask = self.read_sequencer(SequencerRegister::MapMask);
+        let memory_mode = self.read_sequencer(SequencerRegister::MemoryMode);
+        let read_map = self.read_graphics(GraphicsRegister::ReadMapSelect);
+        let graphics_mode = self.read_graphics(GraphicsRegister::GraphicsMode);
+        let miscellaneous = self.read_graphics(GraphicsRegister::Miscellaneous);
+
+        self.write_sequencer(
+            SequencerRegister::MemoryMode,
+            memory_mode | MEMORY_MODE_SEQUENTIAL,
+        );
+        self.write_graphics(
+            GraphicsRegister::GraphicsMode,
+            graphics_mode & !GRAPHICS_MODE_ODD_EVEN,
+        );
+        self.write_graphics(
+            GraphicsRegister::Miscellaneous,
+            miscellaneous & !MISCELLANEOUS_CHAIN_ODD_EVEN,
+        );
+        self.write_graphics(GraphicsRegister::ReadMapSelect, FONT_PLANE);
+        self.write_sequencer(SequencerRegister::MapMask, 1 << FONT_PLANE);
+
+        f(VIDEO_MEM);
+
+        self.write_sequencer(SequencerRegister::MapMask, map_mask);
+        self.write_sequencer(SequencerRegister::MemoryMode, memory_mode);
+        self.write_graphics(GraphicsRegister::ReadMapSelect, read_map);
+        self.write_graphics(GraphicsRegister::GraphicsMode, graphics_mode);
+        self.write_graphics(GraphicsRegister::Miscellaneous, miscellaneous);
+    }
+
+    fn read_sequencer(&self, register: SequencerRegister) -> u8 {
+        write_port_byte(Port::VGA1Out as u16, register as u8);
+        read_port_byte(Port::VGA1In as u16)
+    }
+
+    fn write_sequencer(&mut self, register: SequencerRegister, value: u8) {
+        write_port_byte(Port::VGA1Out as u16, register as u8);
+        write_port_byte(Port::VGA1In as u16, value);
+    }
+
+    fn read_graphics(&self, register: GraphicsRegister) -> u8 {
+        write_port_byte(Port::VGA2Out as u16, register as u8);
+        read_port_byte(Port::VGA2In as u16)
+    }
+
+    fn write_graphics(&mut self, register: GraphicsRegister, value: u8) {
+        write_port_byte(Port::VGA2Out as u16, register as u8);
+        write_port_byte(Port::VGA2In as u16, value);
+    }
+}
+
+/// The driver only uses the legacy VGA ports and memory, which the controller
+/// decodes as long as IO and memory space are enabled.
+unsafe fn probe_pci(device: &PCIDevice, config: &ConfigSpace) -> bool {
+    unsafe { device.enable(config, COMMAND_IO_SPACE | COMMAND_MEMORY_SPACE) };
+    true
 }
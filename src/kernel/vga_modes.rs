@@ -0,0 +1,182 @@
+// VGA modes
+//
+// The register values of the modes the VGA can be switched to without the
+// BIOS. They are written in order: the miscellaneous output register, then
+// the sequencer, CRT controller, graphics controller and attribute controller
+// registers, each from index 0.
+//
+// The text modes above 25 rows use an 8 scanline font, which the driver makes
+// from the 16 scanline font the BIOS loaded.
+
+pub const SEQUENCER_REGISTERS: usize = 5;
+pub const CRTC_REGISTERS: usize = 25;
+pub const GRAPHICS_REGISTERS: usize = 9;
+pub const ATTRIBUTE_REGISTERS: usize = 21;
+
+pub struct ModeRegisters {
+    pub misc: u8,
+    pub sequencer: [u8; SEQUENCER_REGISTERS],
+    pub crtc: [u8; CRTC_REGISTERS],
+    pub graphics: [u8; GRAPHICS_REGISTERS],
+    pub attribute: [u8; ATTRIBUTE_REGISTERS],
+}
+
+/// The attribute controller palette of the text modes and mode 12h, giving
+/// the 16 colors of text mode.
+const TEXT_PALETTE: [u8; 16] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+];
+
+/// Joins a palette and the attribute controller's mode control, overscan
+/// color, plane enable, horizontal panning and color select registers.
+const fn attribute(palette: [u8; 16], rest: [u8; 5]) -> [u8; ATTRIBUTE_REGISTERS] {
+    let mut out = [0; ATTRIBUTE_REGISTERS];
+    let mut i = 0;
+    while i < palette.len() {
+        out[i] = palette[i];
+        i += 1;
+    }
+    while i < ATTRIBUTE_REGISTERS {
+        out[i] = rest[i - palette.len()];
+        i += 1;
+    }
+    out
+}
+
+const TEXT_80X25: ModeRegisters = ModeRegisters {
+    misc: 0x67,
+    sequencer: [0x03, 0x00, 0x03, 0x00, 0x02],
+    crtc: [
+        0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x4F, 0x0D, 0x0E, 0x00, 0x00, 0x00,
+        0x50, 0x9C, 0x0E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3, 0xFF,
+    ],
+    graphics: [0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF],
+    attribute: attribute(TEXT_PALETTE, [0x0C, 0x00, 0x0F, 0x08, 0x00]),
+};
+
+const TEXT_80X50: ModeRegisters = ModeRegisters {
+    misc: 0x67,
+    sequencer: [0x03, 0x00, 0x03, 0x00, 0x02],
+    crtc: [
+        0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x01,
+        0x40, 0x9C, 0x8E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3, 0xFF,
+    ],
+    graphics: [0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF],
+    attribute: attribute(TEXT_PALETTE, [0x0C, 0x00, 0x0F, 0x08, 0x00]),
+};
+
+const TEXT_90X60: ModeRegisters = ModeRegisters {
+    misc: 0xE7,
+    sequencer: [0x03, 0x01, 0x03, 0x00, 0x02],
+    crtc: [
+        0x6B, 0x59, 0x5A, 0x82, 0x60, 0x8D, 0x0B, 0x3E, 0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x00,
+        0x00, 0xEA, 0x0C, 0xDF, 0x2D, 0x08, 0xE8, 0x05, 0xA3, 0xFF,
+    ],
+    graphics: [0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF],
+    attribute: attribute(TEXT_PALETTE, [0x0C, 0x00, 0x0F, 0x08, 0x00]),
+};
+
+const GRAPHICS_640X480X16: ModeRegisters = ModeRegisters {
+    misc: 0xE3,
+    sequencer: [0x03, 0x01, 0x08, 0x00, 0x06],
+    crtc: [
+        0x5F, 0x4F, 0x50, 0x82, 0x54, 0x80, 0x0B, 0x3E, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xEA, 0x0C, 0xDF, 0x28, 0x00, 0xE7, 0x04, 0xE3, 0xFF,
+    ],
+    graphics: [0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x05, 0x0F, 0xFF],
+    attribute: attribute(TEXT_PALETTE, [0x01, 0x00, 0x0F, 0x00, 0x00]),
+};
+
+const GRAPHICS_320X200X256: ModeRegisters = ModeRegisters {
+    misc: 0x63,
+    sequencer: [0x03, 0x01, 0x0F, 0x00, 0x0E],
+    crtc: [
+        0x5F, 0x4F, 0x50, 0x82, 0x54, 0x80, 0xBF, 0x1F, 0x00, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x9C, 0x0E, 0x8F, 0x28, 0x40, 0x96, 0xB9, 0xA3, 0xFF,
+    ],
+    graphics: [0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x05, 0x0F, 0xFF],
+    attribute: attribute(
+        [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+            0x0E, 0x0F,
+        ],
+        [0x41, 0x00, 0x0F, 0x00, 0x00],
+    ),
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VideoMode {
+    /// Mode 3, what the BIOS leaves the VGA in.
+    Text80x25,
+    Text80x50,
+    Text90x60,
+    /// Mode 12h, 640x480 in 16 colors over 4 planes.
+    Graphics640x480,
+    /// Mode 13h, 320x200 in 256 colors with a byte per pixel.
+    Graphics320x200,
+}
+
+/// The modes that can be selected, by name.
+pub const MODES: [(&str, VideoMode); 5] = [
+    ("80x25", VideoMode::Text80x25),
+    ("80x50", VideoMode::Text80x50),
+    ("90x60", VideoMode::Text90x60),
+    ("12h", VideoMode::Graphics640x480),
+    ("13h", VideoMode::Graphics320x200),
+];
+
+impl VideoMode {
+    pub fn registers(&self) -> &'static ModeRegisters {
+        match self {
+            Self::Text80x25 => &TEXT_80X25,
+            Self::Text80x50 => &TEXT_80X50,
+            Self::Text90x60 => &TEXT_90X60,
+            Self::Graphics640x480 => &GRAPHICS_640X480X16,
+            Self::Graphics320x200 => &GRAPHICS_320X200X256,
+        }
+    }
+
+    /// The columns and rows of a text mode.
+    pub fn text_size(&self) -> Option<(u16, u16)> {
+        match self {
+            Self::Text80x25 => Some((80, 25)),
+            Self::Text80x50 => Some((80, 50)),
+            Self::Text90x60 => Some((90, 60)),
+            Self::Graphics640x480 | Self::Graphics320x200 => None,
+        }
+    }
+
+    /// The width and height of a graphics mode, in pixels.
+    pub fn resolution(&self) -> Option<(u16, u16)> {
+        match self {
+            Self::Graphics640x480 => Some((640, 480)),
+            Self::Graphics320x200 => Some((320, 200)),
+            Self::Text80x25 | Self::Text80x50 | Self::Text90x60 => None,
+        }
+    }
+
+    /// The scanlines of a character in a text mode.
+    pub fn font_height(&self) -> Option<u8> {
+        match self {
+            Self::Text80x25 => Some(16),
+            Self::Text80x50 | Self::Text90x60 => Some(8),
+            Self::Graphics640x480 | Self::Graphics320x200 => None,
+        }
+    }
+
+    pub fn from_index(index: u32) -> Option<Self> {
+        MODES.get(index as usize).map(|(_, mode)| *mode)
+    }
+
+    pub fn index(&self) -> u32 {
+        MODES.iter().position(|(_, mode)| mode == self).unwrap_or(0) as u32
+    }
+}
+
+/// Finds a mode by its name.
+pub fn find(name: &[u8]) -> Option<VideoMode> {
+    MODES
+        .iter()
+        .find(|(mode_name, _)| mode_name.as_bytes() == name)
+        .map(|(_, mode)| *mode)
+}
@@ -10,24 +10,36 @@ fn panic(_info: &core::panic::PanicInfo) -> ! {
     loop {}
 }
 
+mod cp437;
 mod decimal_printable;
 mod dyn_array;
+mod event_bus;
 mod hex_printable;
 mod kernel;
 mod printer;
 mod programs;
+mod ring_buffer;
 mod shell;
 mod static_str;
 mod sys_event;
+mod tty;
 mod util;
 mod vga;
 
 use core::arch::asm;
 
 use crate::{
-    kernel::{acpi::acpi::ACPI, isr::empty_event_buffer, kernel::KernelAcc},
-    printer::VGATextWriter,
-    shell::Shell,
+    event_bus::EventBus,
+    kernel::{
+        cpu,
+        kernel::KernelAcc,
+        keyboard_driver::{KEY_DECODER_EVENTS, KeyDecoder},
+        smp::{self, run_next_task},
+        time::timers,
+        vga_driver::VGAText,
+    },
+    shell::{SHELL_EVENTS, Shells},
+    tty::console::CONSOLE_COUNT,
 };
 
 static KERNEL: KernelAcc = KernelAcc::new();
@@ -82,38 +94,25 @@ pub extern "C" fn kernel_main() -> ! {
     unsafe {
         KERNEL.init();
         if let Ok(kernel) = KERNEL.get() {
-            kernel.process_manager().lock().start_process(sample_process);
+            smp::start_application_processors(kernel);
+            if kernel.spawn(sample_process).is_err() {
+                loop {}
+            }
             let mut vga = kernel.vga_driver().lock();
-            if let Some(mut tty) = VGATextWriter::get_instance(&mut vga) {
-                match ACPI::load() {
-                    Some(acpi) => {
-                        let mut iter = acpi.iter();
-                        while let Some(header) = iter.next() {
-                            tty.println_ascii(&header.signature)
-                        }
-                    }
-                    None => tty.println_ascii("No RDSP".as_bytes()),
-                }
-                let mut shell = Shell::new(tty);
-                loop {
-                    asm!("hlt");
-                    let event_buf = empty_event_buffer();
-                    for i in 0..event_buf.len() {
-                        if let Some(Some(event)) = event_buf.get(i) {
-                            match event {
-                                sys_event::SysEvent::Keyboard => {
-                                    if let Some(key) =
-                                        kernel.keyboard_driver().lock().keyboard_interrupt_handler()
-                                    {
-                                        shell.handle_key(key);
-                                    }
-                                }
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                }
+            let mut screens = [const { VGAText {} }; CONSOLE_COUNT];
+            let mut shells = Shells::new(&mut vga, &mut screens);
+            let mut key_decoder = KeyDecoder;
+            let mut bus = EventBus::new();
+            if bus.subscribe(KEY_DECODER_EVENTS, &mut key_decoder).is_err()
+                || bus.subscribe(SHELL_EVENTS, &mut shells).is_err()
+            {
+                loop {}
+            }
+            loop {
+                asm!("hlt");
+                bus.dispatch_pending();
+                timers::run_expired();
+                run_next_task(cpu::BSP_INDEX);
             }
         }
         loop {}
@@ -2,42 +2,91 @@ use crate::{
     decimal_printable::{DecimalDigits, DecimalPrintable},
     dyn_array::DynArray,
     hex_printable::HexPrintable,
-    kernel::vga_driver::{HEIGHT, VGAText, WIDTH},
+    kernel::{
+        vga_driver::{Attribute, MAX_WIDTH, VGAText},
+        vga_modes::VideoMode,
+    },
+    tty::{
+        ansi::{Action, AnsiParser, ControlSequence, Rendition},
+        console,
+    },
 };
 
-static mut X: u16 = 0;
-static mut Y: u16 = 0;
-static mut ACTIVE: bool = false;
+const TAB_WIDTH: u16 = 8;
+/// The private mode that shows the cursor.
+const CURSOR_ENABLE_MODE: u16 = 25;
+/// The scanlines of the underline cursor the BIOS sets up.
+const CURSOR_UNDERLINE_START: u8 = 13;
+const CURSOR_UNDERLINE_END: u8 = 14;
 
-/// Offers TTY printing to console when in console mode.
+/// Offers TTY printing to a virtual console when in console mode.
 ///
-/// Under the hood, a global state is maintained that
-/// contains the cursor position. When an instance is created,
-/// it copies this state, storing it back when dropped. This circumvents
-/// problems with mutably borrowing static mutables and follows borrowing
-/// rules.
+/// The cursor position lives with the console. An instance copies it when
+/// created and stores it back whenever it moves, so the console shows the
+/// cursor in the right place when it is brought to the foreground. This
+/// circumvents problems with mutably borrowing static mutables and follows
+/// borrowing rules.
+///
+/// Text printed with `print_ascii` may contain ANSI escape sequences, see
+/// `control_sequence` for the ones understood.
 pub struct VGATextWriter<'a> {
+    console: usize,
     x: u16,
     y: u16,
     driver: &'a mut VGAText,
+    parser: AnsiParser,
+    rendition: Rendition,
+    saved_cursor: (u16, u16),
+    /// The first and last rows that scroll, inclusive. `None` is the bottom
+    /// of the screen, whatever the mode.
+    scroll_top: u16,
+    scroll_bottom: Option<u16>,
 }
 
 impl<'a> VGATextWriter<'a> {
-    /// Creates a new instance using a driver. The state is not synchronized and
-    /// will overwrite existing text when needed.
+    /// Creates a new instance for the foreground console using a driver. The
+    /// state is not synchronized and will overwrite existing text when needed.
     pub unsafe fn create(driver: &'a mut VGAText) -> VGATextWriter<'a> {
-        unsafe { Self { x: X, y: Y, driver } }
+        unsafe { Self::create_on(driver, console::foreground()) }
     }
 
-    /// Obtains an instance of the TTY, if one has not been used yet.
-    /// The returned Option acts as a non-blocking lock, returning `None`
-    /// when an instance is already in use.
+    unsafe fn create_on(driver: &'a mut VGAText, index: usize) -> VGATextWriter<'a> {
+        let (x, y) = match unsafe { console::console(index) } {
+            Some(console) => (console.x, console.y),
+            None => (0, 0),
+        };
+        // The console may have been used in a mode with more rows or columns.
+        let (x, y) = (x.min(driver.width() - 1), y.min(driver.height() - 1));
+        Self {
+            console: index,
+            x,
+            y,
+            driver,
+            parser: AnsiParser::new(),
+            rendition: Rendition::new(Attribute::DEFAULT),
+            saved_cursor: (0, 0),
+            scroll_top: 0,
+            scroll_bottom: None,
+        }
+    }
+
+    /// Obtains an instance of the TTY on the first console, if one has not
+    /// been used yet.
     pub unsafe fn get_instance(driver: &'a mut VGAText) -> Option<VGATextWriter<'a>> {
+        unsafe { Self::get_console(driver, 0) }
+    }
+
+    /// Obtains an instance of the TTY on a console, if one has not been used
+    /// yet. The returned Option acts as a non-blocking lock, returning `None`
+    /// when an instance is already in use.
+    pub unsafe fn get_console(driver: &'a mut VGAText, index: usize) -> Option<VGATextWriter<'a>> {
         unsafe {
-            if ACTIVE {
-                None
-            } else {
-                Some(Self::create(driver))
+            match console::console(index) {
+                Some(console) if !console.in_use => {
+                    console.in_use = true;
+                    Some(Self::create_on(driver, index))
+                }
+                _ => None,
             }
         }
     }
@@ -45,49 +94,115 @@ impl<'a> VGATextWriter<'a> {
 
 impl<'a> Drop for VGATextWriter<'a> {
     fn drop(&mut self) {
-        unsafe {
-            X = self.x;
-            Y = self.y;
-            ACTIVE = false;
+        if let Some(console) = unsafe { console::console(self.console) } {
+            console.in_use = false;
         }
     }
 }
 
 impl<'a> VGATextWriter<'a> {
     pub unsafe fn clear(&mut self) {
-        for i in 0..HEIGHT {
-            unsafe { self.driver.clear_row(i) };
+        for i in 0..self.height() {
+            unsafe { self.clear_row(i) };
         }
         self.x = 0;
         self.y = 0;
-        self.driver.update_cursor_position(self.x, self.y);
+        self.update_cursor();
+    }
+
+    /// Sets the colors of the text printed from now on.
+    pub fn set_colors(&mut self, attribute: Attribute) {
+        self.rendition.set_colors(attribute);
+    }
+
+    /// Sets the colors the writer goes back to when escape sequences reset
+    /// them, and uses them from now on.
+    pub fn set_default_colors(&mut self, attribute: Attribute) {
+        self.rendition.set_default(attribute);
+    }
+
+    /// Switches the VGA to another mode. Back in a text mode, the foreground
+    /// console is drawn again with the cursor kept on the screen.
+    pub unsafe fn set_mode(&mut self, mode: VideoMode) {
+        unsafe {
+            self.driver.set_mode(mode);
+            if !self.driver.is_text() {
+                return;
+            }
+            self.x = self.x.min(self.width() - 1);
+            self.y = self.y.min(self.height() - 1);
+            self.update_cursor();
+            console::redraw(self.driver);
+        }
+    }
+
+    /// Plots a pixel while in a graphics mode.
+    pub unsafe fn put_pixel(&mut self, x: u16, y: u16, color: u8) {
+        unsafe { self.driver.put_pixel(x, y, color) };
+    }
+
+    /// The mode the VGA is in.
+    pub fn mode(&self) -> VideoMode {
+        self.driver.mode()
     }
 
     pub unsafe fn put_char(&mut self, c: u8) {
         unsafe {
-            self.driver.put_char_raw(c, self.x, self.y);
+            self.put_char_raw(c, self.x, self.y);
             self.move_cursor(1, 0);
         }
-        self.driver.update_cursor_position(self.x, self.y);
+        self.update_cursor();
     }
 
+    /// Scrolls the rows of the scrolling region up.
     pub unsafe fn scroll(&mut self, columns: u16) {
         unsafe {
-            for i in 0..HEIGHT {
-                if i + columns < HEIGHT {
-                    self.driver.copy_row(i + columns, i);
+            // Rows leaving the top of the screen go to the history.
+            if self.scroll_top == 0
+                && let Some(console) = console::console(self.console)
+            {
+                for i in 0..columns.min(self.scroll_bottom() + 1) {
+                    console.save_row(i);
+                }
+            }
+            for i in self.scroll_top..=self.scroll_bottom() {
+                if i + columns <= self.scroll_bottom() {
+                    self.copy_row(i + columns, i);
+                } else {
+                    self.clear_row(i);
+                }
+            }
+        }
+    }
+
+    /// Scrolls the rows of the scrolling region down.
+    unsafe fn scroll_down(&mut self, rows: u16) {
+        unsafe {
+            for i in (self.scroll_top..=self.scroll_bottom()).rev() {
+                if i >= self.scroll_top + rows {
+                    self.copy_row(i - rows, i);
                 } else {
-                    self.driver.clear_row(i);
+                    self.clear_row(i);
                 }
             }
         }
     }
 
+    /// Erases the character before the cursor. At the start of a row this is
+    /// the last character of the row above, at the top left there's nothing
+    /// to erase.
     pub unsafe fn bs(&mut self) {
+        if self.x > 0 {
+            self.x -= 1;
+        } else if self.y > 0 {
+            self.x = self.width().saturating_sub(1);
+            self.y -= 1;
+        } else {
+            return;
+        }
         unsafe {
-            self.driver.put_char_raw(b' ', self.x - 1, self.y);
-            self.move_cursor(-1, 0);
-            self.driver.update_cursor_position(self.x, self.y);
+            self.put_char_raw(b' ', self.x, self.y);
+            self.update_cursor();
         }
     }
 
@@ -97,11 +212,114 @@ impl<'a> VGATextWriter<'a> {
                 if *c == 0x00 {
                     break;
                 }
-                self.driver.put_char_raw(*c, self.x, self.y);
-                self.move_cursor(1, 0);
+                match self.parser.push(*c) {
+                    Action::None => {}
+                    Action::Print(c) => self.print_byte(c),
+                    Action::Escape(b'7') => self.saved_cursor = (self.x, self.y),
+                    Action::Escape(b'8') => (self.x, self.y) = self.saved_cursor,
+                    Action::Escape(_) => {}
+                    Action::ControlSequence(sequence) => self.control_sequence(&sequence),
+                }
+            }
+        }
+        self.update_cursor();
+    }
+
+    unsafe fn print_byte(&mut self, c: u8) {
+        unsafe {
+            match c {
+                b'\n' => self.nl(),
+                b'\r' => self.x = 0,
+                0x08 => self.x = self.x.saturating_sub(1),
+                b'\t' => self.x = ((self.x / TAB_WIDTH + 1) * TAB_WIDTH).min(self.width() - 1),
+                _ => {
+                    self.put_char_raw(c, self.x, self.y);
+                    self.move_cursor(1, 0);
+                }
+            }
+        }
+    }
+
+    /// Carries out a control sequence. Those understood are cursor movement
+    /// (`A` to `H` and `f`), erasing the screen (`J`) or line (`K`), scrolling
+    /// (`S` and `T`), colors and attributes (`m`), the scrolling region (`r`)
+    /// and saving (`s`) and restoring (`u`) the cursor.
+    unsafe fn control_sequence(&mut self, sequence: &ControlSequence) {
+        if sequence.private {
+            // DECTCEM, showing and hiding the cursor.
+            if sequence.param(0, 0) == CURSOR_ENABLE_MODE {
+                match sequence.final_byte {
+                    b'h' => self.set_cursor_visible(true),
+                    b'l' => self.set_cursor_visible(false),
+                    _ => {}
+                }
+            }
+            return;
+        }
+        if let Some(intermediate) = sequence.intermediate {
+            // DECSCUSR, the cursor style.
+            if intermediate == b' ' && sequence.final_byte == b'q' {
+                self.set_cursor_style(sequence.param(0, 0));
+            }
+            return;
+        }
+        let n = sequence.param(0, 1);
+        unsafe {
+            match sequence.final_byte {
+                b'A' => self.y = self.y.saturating_sub(n),
+                b'B' => self.y = self.y.saturating_add(n).min(self.height() - 1),
+                b'C' => self.x = self.x.saturating_add(n).min(self.width() - 1),
+                b'D' => self.x = self.x.saturating_sub(n),
+                b'E' => {
+                    self.y = self.y.saturating_add(n).min(self.height() - 1);
+                    self.x = 0;
+                }
+                b'F' => {
+                    self.y = self.y.saturating_sub(n);
+                    self.x = 0;
+                }
+                b'G' => self.x = (n - 1).min(self.width() - 1),
+                b'H' | b'f' => {
+                    self.y = (n - 1).min(self.height() - 1);
+                    self.x = (sequence.param(1, 1) - 1).min(self.width() - 1);
+                }
+                b'J' => {
+                    let cursor = self.y * self.width() + self.x;
+                    match sequence.param(0, 0) {
+                        0 => self.erase(cursor, self.height() * self.width()),
+                        1 => self.erase(0, cursor + 1),
+                        2 => self.erase(0, self.height() * self.width()),
+                        _ => {}
+                    }
+                }
+                b'K' => {
+                    let start = self.y * self.width();
+                    let cursor = start + self.x;
+                    match sequence.param(0, 0) {
+                        0 => self.erase(cursor, start + self.width()),
+                        1 => self.erase(start, cursor + 1),
+                        2 => self.erase(start, start + self.width()),
+                        _ => {}
+                    }
+                }
+                b'S' => self.scroll(n.min(self.height())),
+                b'T' => self.scroll_down(n.min(self.height())),
+                b'm' => self.rendition.select(sequence),
+                b'r' => {
+                    let top = n - 1;
+                    let bottom = sequence.param(1, self.height()).min(self.height()) - 1;
+                    if top < bottom {
+                        self.scroll_top = top;
+                        self.scroll_bottom = (bottom < self.height() - 1).then_some(bottom);
+                        self.x = 0;
+                        self.y = 0;
+                    }
+                }
+                b's' => self.saved_cursor = (self.x, self.y),
+                b'u' => (self.x, self.y) = self.saved_cursor,
+                _ => {}
             }
         }
-        self.driver.update_cursor_position(self.x, self.y);
     }
 
     pub fn nl(&mut self) {
@@ -109,7 +327,7 @@ impl<'a> VGATextWriter<'a> {
             self.move_cursor(0, 1);
         }
         self.x = 0;
-        self.driver.update_cursor_position(self.x, self.y);
+        self.update_cursor();
     }
 
     pub unsafe fn println_ascii(&mut self, s: &[u8]) {
@@ -125,13 +343,13 @@ impl<'a> VGATextWriter<'a> {
                 match buf.get(i) {
                     Ok(0) => break,
                     Ok(c) => {
-                        self.driver.put_char_raw(*c, self.x, self.y);
+                        self.put_char_raw(*c, self.x, self.y);
                         self.move_cursor(1, 0);
                     }
                     Err(_) => break,
                 }
             }
-            self.driver.update_cursor_position(self.x, self.y);
+            self.update_cursor();
         }
     }
 
@@ -153,15 +371,155 @@ impl<'a> VGATextWriter<'a> {
         }
     }
 
-    unsafe fn move_cursor(&mut self, dx: i16, dy: i16) {
-        let x_acc = self.x.wrapping_add_signed(dx);
-        self.x = x_acc % WIDTH;
-        let mut new_y = self.y.wrapping_add_signed(dy) + x_acc / WIDTH;
-        if new_y >= HEIGHT {
-            let diff = new_y.wrapping_sub(HEIGHT - 1);
+    fn is_foreground(&self) -> bool {
+        self.console == console::foreground()
+    }
+
+    fn width(&self) -> u16 {
+        self.driver.width()
+    }
+
+    fn height(&self) -> u16 {
+        self.driver.height()
+    }
+
+    /// The last row that scrolls, which stays on the screen when the mode
+    /// changes to fewer rows.
+    fn scroll_bottom(&self) -> u16 {
+        let last = self.height() - 1;
+        self.scroll_bottom.map_or(last, |bottom| bottom.min(last))
+    }
+
+    /// Whether output reaches VGA memory, which brings a console scrolled
+    /// back through its history back to the bottom first.
+    unsafe fn draws(&mut self) -> bool {
+        if !self.is_foreground() {
+            return false;
+        }
+        unsafe { console::snap_back(self.driver) };
+        true
+    }
+
+    /// Moves the hardware cursor when the console is in the foreground, and
+    /// remembers the position for when it is brought back otherwise.
+    fn update_cursor(&mut self) {
+        if let Some(console) = unsafe { console::console(self.console) } {
+            console.x = self.x;
+            console.y = self.y;
+        }
+        if self.is_foreground() {
+            self.driver.update_cursor_position(self.x, self.y);
+        }
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        if let Some(console) = unsafe { console::console(self.console) } {
+            console.cursor_visible = visible;
+        }
+        if self.is_foreground() {
+            self.driver.set_cursor_visible(visible);
+        }
+    }
+
+    /// Makes the cursor a block for DECSCUSR styles 1 and 2, or an underline
+    /// for 0, 3 and 4. VGA cursors always blink and can't be a bar. The shape
+    /// is the VGA's rather than the console's, so only the foreground console
+    /// changes it.
+    fn set_cursor_style(&mut self, style: u16) {
+        if !self.is_foreground() {
+            return;
+        }
+        match style {
+            1 | 2 => self.driver.set_cursor_shape(0, u8::MAX),
+            0 | 3 | 4 => self
+                .driver
+                .set_cursor_shape(CURSOR_UNDERLINE_START, CURSOR_UNDERLINE_END),
+            _ => {}
+        }
+    }
+
+    /// Chooses whether blinking text blinks or gets a bright background, see
+    /// `Rendition::attribute`. This holds for every console until the mode
+    /// changes.
+    pub fn set_blink(&mut self, blink: bool) {
+        self.driver.set_blink(blink);
+    }
+
+    fn cells(&self) -> Option<&'static mut [u16]> {
+        unsafe { console::console(self.console) }.and_then(|console| console.cells())
+    }
+
+    unsafe fn put_char_raw(&mut self, c: u8, x: u16, y: u16) {
+        let attribute = self.rendition.attribute();
+        if let Some(cell) = self
+            .cells()
+            .and_then(|cells| cells.get_mut(cell_index(x, y)))
+        {
+            *cell = attribute.cell(c);
+        }
+        if unsafe { self.draws() } {
+            unsafe { self.driver.put_char_raw(c, attribute, x, y) };
+        }
+    }
+
+    /// Blanks the cells from `start` up to `end`, counted from the top left
+    /// of the screen.
+    unsafe fn erase(&mut self, start: u16, end: u16) {
+        for i in start..end.min(self.height() * self.width()) {
+            unsafe { self.put_char_raw(b' ', i % self.width(), i / self.width()) };
+        }
+    }
+
+    unsafe fn clear_row(&mut self, y: u16) {
+        let attribute = self.rendition.attribute();
+        if let Some(row) = self
+            .cells()
+            .and_then(|cells| cells.get_mut(cell_index(0, y)..cell_index(0, y + 1)))
+        {
+            row.fill(attribute.cell(b' '));
+        }
+        if unsafe { self.draws() } {
+            unsafe { self.driver.clear_row(y, attribute) };
+        }
+    }
+
+    fn copy_row(&mut self, src: u16, dst: u16) {
+        if let Some(cells) = self.cells() {
+            for x in 0..MAX_WIDTH {
+                if let Some(cell) = cells.get(cell_index(x, src)).copied()
+                    && let Some(dst) = cells.get_mut(cell_index(x, dst))
+                {
+                    *dst = cell;
+                }
+            }
+        }
+        if unsafe { self.draws() } {
+            self.driver.copy_row(src, dst);
+        }
+    }
+
+    /// Moves the cursor forward, wrapping to the next row and scrolling as
+    /// needed. `bs` is the only way back.
+    unsafe fn move_cursor(&mut self, dx: u16, dy: u16) {
+        let x_acc = self.x.saturating_add(dx);
+        self.x = x_acc % self.width();
+        let mut new_y = self
+            .y
+            .saturating_add(dy)
+            .saturating_add(x_acc / self.width());
+        // Only the scrolling region scrolls, below it the cursor stops at
+        // the bottom of the screen.
+        if self.y <= self.scroll_bottom() && new_y > self.scroll_bottom() {
+            let diff = new_y.wrapping_sub(self.scroll_bottom());
             unsafe { self.scroll(diff) };
             new_y = new_y.wrapping_sub(diff);
         }
-        self.y = new_y;
+        self.y = new_y.min(self.height() - 1);
     }
 }
+
+/// The index of a cell in the screen of a console, whose rows are as wide as
+/// the widest text mode.
+fn cell_index(x: u16, y: u16) -> usize {
+    y as usize * MAX_WIDTH as usize + x as usize
+}
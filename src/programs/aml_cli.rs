@@ -0,0 +1,216 @@
+use crate::{
+    KERNEL,
+    kernel::acpi::{
+        acpi::ACPI,
+        aml::{
+            AMLError,
+            namespace::{Namespace, NodeId, NodeKind},
+        },
+    },
+    printer::VGATextWriter,
+};
+
+const CRS_NAME: [u8; 4] = *b"_CRS";
+
+/// Loads the ACPI namespace and prints it as a tree. Devices are shown with
+/// their `_STA`, the size of their `_CRS` and the entries of their `_PRT`.
+/// With a `path` like `\_SB.PCI0`, only that object is shown: the subtree of
+/// a container, or the value of anything else.
+///
+/// Everything the namespace allocated goes back to the heap afterwards, so
+/// the command can run any number of times.
+pub unsafe fn aml_cli(tty: &mut VGATextWriter, path: &[u8]) {
+    unsafe {
+        let (Some(acpi), Ok(kernel)) = (ACPI::load(), KERNEL.get()) else {
+            tty.println_ascii("No ACPI tables".as_bytes());
+            return;
+        };
+        let mem = kernel.memory_manager();
+        let mark = mem.lock().mark();
+        match Namespace::load(&acpi, mem) {
+            Ok(mut ns) => {
+                tty.print_ascii("Objects: ".as_bytes());
+                tty.print_hex(ns.len() as u32);
+                tty.print_ascii(", load errors: ".as_bytes());
+                tty.print_hex(ns.load_errors as u32);
+                tty.nl();
+                match ns.find(path) {
+                    Some(id) => print_object(tty, &mut ns, id),
+                    None => tty.println_ascii("Object not found".as_bytes()),
+                }
+            }
+            Err(err) => print_error(tty, err),
+        }
+        mem.lock().release(mark);
+    }
+}
+
+unsafe fn print_object(tty: &mut VGATextWriter, ns: &mut Namespace, id: NodeId) {
+    unsafe {
+        let Some(node) = ns.node(id).copied() else {
+            return;
+        };
+        if node.kind.is_container() {
+            print_node(tty, ns, id, 0);
+            return;
+        }
+        tty.print_ascii(&node.name);
+        tty.print_ascii(" (".as_bytes());
+        tty.print_ascii(node.kind.type_name().as_bytes());
+        tty.print_ascii(") ".as_bytes());
+        if let NodeKind::OpRegion { space, offset, len } = node.kind {
+            tty.print_ascii("space ".as_bytes());
+            tty.print_hex(space);
+            tty.print_ascii(" offset ".as_bytes());
+            tty.print_hex(offset);
+            tty.print_ascii(" length ".as_bytes());
+            tty.print_hex(len);
+            tty.nl();
+            return;
+        }
+        match ns.evaluate(id, &[]) {
+            Ok(value) => {
+                if let Some(n) = value.as_integer() {
+                    tty.print_hex(n);
+                } else if let Some(buffer) = value.as_buffer() {
+                    tty.print_ascii("buffer of ".as_bytes());
+                    tty.print_hex(buffer.len() as u16);
+                } else if let Some(package) = value.as_package() {
+                    tty.print_ascii("package of ".as_bytes());
+                    tty.print_hex(package.len() as u16);
+                }
+            }
+            Err(err) => print_error(tty, err),
+        }
+        tty.nl();
+    }
+}
+
+unsafe fn print_node(tty: &mut VGATextWriter, ns: &mut Namespace, id: NodeId, depth: usize) {
+    unsafe {
+        let Some(node) = ns.node(id).copied() else {
+            return;
+        };
+        indent(tty, depth);
+        tty.print_ascii(&node.name);
+        tty.print_ascii(" (".as_bytes());
+        tty.print_ascii(node.kind.type_name().as_bytes());
+        tty.print_ascii(")".as_bytes());
+        if matches!(node.kind, NodeKind::Device) {
+            print_device(tty, ns, id);
+        }
+        tty.nl();
+
+        // Leaf objects are listed on one line below their container.
+        let mut leaves = false;
+        for child in ns.children(id) {
+            if let Some(child) = ns.node(child).filter(|c| !c.kind.is_container()) {
+                if !leaves {
+                    indent(tty, depth + 1);
+                    leaves = true;
+                }
+                tty.print_ascii(&child.name);
+                tty.print_ascii(" ".as_bytes());
+            }
+        }
+        if leaves {
+            tty.nl();
+        }
+
+        // Evaluating methods can add objects, so the children are looked up
+        // one at a time rather than iterated.
+        let mut child = 0;
+        loop {
+            let next = ns
+                .children(id)
+                .filter(|c| *c > child)
+                .find(|c| ns.node(*c).is_some_and(|n| n.kind.is_container()));
+            let Some(next) = next else {
+                break;
+            };
+            print_node(tty, ns, next, depth + 1);
+            child = next;
+        }
+    }
+}
+
+unsafe fn print_device(tty: &mut VGATextWriter, ns: &mut Namespace, id: NodeId) {
+    unsafe {
+        tty.print_ascii(" _STA ".as_bytes());
+        match ns.device_status(id) {
+            Ok(status) => tty.print_hex(status as u8),
+            Err(err) => print_error(tty, err),
+        }
+        match ns.evaluate_child(id, CRS_NAME) {
+            Ok(Some(crs)) => {
+                if let Some(buffer) = crs.as_buffer() {
+                    tty.print_ascii(" _CRS ".as_bytes());
+                    tty.print_hex(buffer.len() as u16);
+                }
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tty.print_ascii(" _CRS ".as_bytes());
+                print_error(tty, err);
+            }
+        }
+        match ns.routing_table(id) {
+            Ok(Some(table)) => {
+                tty.print_ascii(" _PRT ".as_bytes());
+                tty.print_hex(table.len() as u16);
+                for i in 0..table.len() {
+                    let Ok(entry) = ns.routing_entry(table, i) else {
+                        continue;
+                    };
+                    tty.nl();
+                    tty.print_ascii("   dev ".as_bytes());
+                    tty.print_hex(entry.device());
+                    tty.print_ascii(" pin ".as_bytes());
+                    tty.print_hex(entry.pin);
+                    tty.print_ascii(" -> ".as_bytes());
+                    match entry.source.and_then(|source| ns.node(source)) {
+                        Some(source) => tty.print_ascii(&source.name),
+                        None => tty.print_ascii("GSI".as_bytes()),
+                    }
+                    tty.print_ascii(" ".as_bytes());
+                    tty.print_hex(entry.source_index);
+                }
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tty.print_ascii(" _PRT ".as_bytes());
+                print_error(tty, err);
+            }
+        }
+    }
+}
+
+unsafe fn print_error(tty: &mut VGATextWriter, err: AMLError) {
+    unsafe {
+        let reason = match err {
+            AMLError::UnexpectedEnd => "unexpected end of AML",
+            AMLError::Unsupported(op) => {
+                tty.print_ascii("unsupported opcode ".as_bytes());
+                tty.print_hex(op);
+                return;
+            }
+            AMLError::NotFound => "not found",
+            AMLError::TypeMismatch => "type mismatch",
+            AMLError::OutOfBounds => "out of bounds",
+            AMLError::DivideByZero => "divide by zero",
+            AMLError::NamespaceFull => "namespace full",
+            AMLError::LimitExceeded => "limit exceeded",
+            AMLError::OutOfMemory => "out of memory",
+            AMLError::NoDSDT => "no valid DSDT",
+        };
+        tty.print_ascii(reason.as_bytes());
+    }
+}
+
+unsafe fn indent(tty: &mut VGATextWriter, depth: usize) {
+    unsafe {
+        for _ in 0..depth {
+            tty.print_ascii("  ".as_bytes());
+        }
+    }
+}
@@ -1 +1,2 @@
-pub mod ps2_cli;
\ No newline at end of file
+pub mod aml_cli;
+pub mod ps2_cli;
@@ -1,7 +1,63 @@
-use crate::printer::VGATextWriter;
+use crate::{
+    KERNEL,
+    kernel::{
+        kernel::print_reason,
+        ps2::{PS2Port, read_status},
+    },
+    printer::VGATextWriter,
+};
 
 pub unsafe fn ps2_cli(tty: &mut VGATextWriter) {
     unsafe {
-        tty.println_ascii("PS2 CLI reached.".as_bytes());
+        let Ok(kernel) = KERNEL.get() else {
+            tty.println_ascii("Kernel Error.".as_bytes());
+            return;
+        };
+        let ps2 = kernel.ps2_controller();
+        if ps2.dual_channel {
+            tty.println_ascii("Dual channel controller".as_bytes());
+        } else {
+            tty.println_ascii("Single channel controller".as_bytes());
+        }
+        tty.print_ascii("Configuration byte: ".as_bytes());
+        tty.print_hex(ps2.config());
+        tty.nl();
+        for (port, name) in [(PS2Port::First, "Port 1: "), (PS2Port::Second, "Port 2: ")] {
+            tty.print_ascii(name.as_bytes());
+            if port == PS2Port::Second
+                && let Some(mouse) = kernel.mouse_driver()
+            {
+                tty.println_ascii(mouse.kind().name().as_bytes());
+            } else if ps2.is_working(port) {
+                tty.println_ascii("device ready".as_bytes());
+            } else {
+                tty.println_ascii("no device".as_bytes());
+            }
+        }
+        match read_status() {
+            Ok(status) => {
+                tty.print_ascii("Status: ".as_bytes());
+                if status.output_buf_full {
+                    tty.print_ascii("output full ".as_bytes());
+                }
+                if status.input_buf_full {
+                    tty.print_ascii("input full ".as_bytes());
+                }
+                if status.sys_flag {
+                    tty.print_ascii("system ".as_bytes());
+                }
+                if status.data_for_device {
+                    tty.print_ascii("command ".as_bytes());
+                }
+                if status.from_second_port {
+                    tty.print_ascii("second port ".as_bytes());
+                }
+                tty.nl();
+            }
+            Err(err) => {
+                tty.print_ascii("Status: ".as_bytes());
+                print_reason(tty, err.describe());
+            }
+        }
     }
 }
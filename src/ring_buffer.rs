@@ -0,0 +1,72 @@
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+
+/// A lock-free single-producer single-consumer ring buffer.
+///
+/// The producer only ever writes `head` and the consumer only ever writes
+/// `tail`, so no locking is needed as long as there is at most one of each.
+/// This makes it suitable for passing data from interrupt context to the
+/// kernel's main loop. When the buffer is full, new elements are dropped and
+/// counted instead of overwriting unread ones.
+///
+/// `head` and `tail` are free-running counters, so `N` must be a power of two
+/// for the indices to stay correct when they wrap around.
+pub struct RingBuffer<T: Copy, const N: usize> {
+    buf: UnsafeCell<[MaybeUninit<T>; N]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicU32,
+}
+
+unsafe impl<T: Copy + Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        assert!(N.is_power_of_two());
+        Self {
+            buf: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicU32::new(0),
+        }
+    }
+
+    /// Appends an element. Returns `false` and counts the element as dropped
+    /// if the buffer is full. Must only be called by the single producer.
+    pub unsafe fn push(&self, t: T) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head.wrapping_sub(tail) >= N {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        unsafe { (*self.buf.get())[head % N] = MaybeUninit::new(t) };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Removes the oldest element. Must only be called by the single consumer.
+    pub unsafe fn pop(&self) -> Option<T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let t = unsafe { (*self.buf.get())[tail % N].assume_init() };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(t)
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        self.head.load(Ordering::Acquire).wrapping_sub(tail)
+    }
+
+    /// The number of elements dropped because the buffer was full.
+    pub fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
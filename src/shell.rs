@@ -1,17 +1,76 @@
-use crate::{KERNEL, printer::VGATextWriter, programs::ps2_cli::ps2_cli, static_str::StaticString};
+use core::sync::atomic::Ordering;
 
-const BUF_SIZE: usize = 32;
+use crate::{
+    KERNEL, cp437,
+    event_bus::EventSubscriber,
+    kernel::{
+        acpi::{
+            acpi::ACPI,
+            fadt::FADT,
+            hpet::HPET,
+            madt::{LOCAL_APIC_ENABLED, LOCAL_APIC_ONLINE_CAPABLE, MADT, MADTEntry},
+            mcfg::MCFG,
+            power::{self, PowerError},
+        },
+        apic, cpu, isr,
+        kernel::print_reason,
+        keyboard_driver::RepeatMode,
+        layouts,
+        pci::{config::ConfigSpace, device::BAR},
+        time::{
+            self, clock,
+            rtc::{self, RTCError},
+        },
+        vga_driver::{Attribute, Color, VGAText},
+        vga_modes::{self, MODES},
+    },
+    printer::VGATextWriter,
+    programs::{aml_cli::aml_cli, ps2_cli::ps2_cli},
+    static_str::StaticString,
+    sys_event::{EventFilter, EventKind, InterruptEvent, SysEvent},
+    tty::{
+        console::{self, CONSOLE_COUNT, ConsoleKey},
+        line_discipline::{LINE_CAPACITY, LineDiscipline, TtySettings},
+    },
+    util::parse_decimal,
+};
+
+/// A whole line and the newline or zero ending it.
+const BUF_SIZE: usize = LINE_CAPACITY + 1;
+
+/// The events the shell needs to receive.
+pub const SHELL_EVENTS: EventFilter = EventFilter::none()
+    .with(EventKind::Key)
+    .with(EventKind::Mouse);
+/// The rows a step of the mouse wheel scrolls the view by.
+const WHEEL_ROWS: isize = 3;
 
 enum Command {
     Empty,
     PS2,
     Mem,
     Commands,
+    Cpus,
+    Irqs,
+    ACPI,
+    AML,
+    PCI,
+    Uptime,
+    Sleep,
+    Date,
+    Layout,
+    Kbd,
+    Mode,
+    Stty,
+    Color,
+    Shutdown,
+    Reboot,
 }
 pub struct Shell<'a> {
     tty: VGATextWriter<'a>,
+    line: LineDiscipline,
     buf: StaticString<BUF_SIZE, u8>,
-    cmds: [([u8; BUF_SIZE], Command); 4], // TODO this implementation needs work!
+    cmds: [([u8; BUF_SIZE], Command); 19], // TODO this implementation needs work!
 }
 
 const fn make_command(command_str: &str) -> [u8; BUF_SIZE] {
@@ -31,12 +90,28 @@ impl<'a> Shell<'a> {
     pub unsafe fn new(tty: VGATextWriter<'a>) -> Self {
         let mut self_ = Self {
             tty,
+            line: LineDiscipline::new(),
             buf: StaticString::new(0),
             cmds: [
                 (make_command(""), Command::Empty),
                 (make_command("ps2"), Command::PS2),
                 (make_command("commands"), Command::Commands),
                 (make_command("mem"), Command::Mem),
+                (make_command("cpus"), Command::Cpus),
+                (make_command("irqs"), Command::Irqs),
+                (make_command("acpi"), Command::ACPI),
+                (make_command("aml"), Command::AML),
+                (make_command("lspci"), Command::PCI),
+                (make_command("uptime"), Command::Uptime),
+                (make_command("sleep"), Command::Sleep),
+                (make_command("date"), Command::Date),
+                (make_command("layout"), Command::Layout),
+                (make_command("kbd"), Command::Kbd),
+                (make_command("mode"), Command::Mode),
+                (make_command("stty"), Command::Stty),
+                (make_command("color"), Command::Color),
+                (make_command("shutdown"), Command::Shutdown),
+                (make_command("reboot"), Command::Reboot),
             ],
         };
         unsafe { self_.print_flair() };
@@ -45,21 +120,31 @@ impl<'a> Shell<'a> {
 
     pub unsafe fn handle_key(&mut self, key: u8) {
         unsafe {
-            match key {
-                0x08 => {
-                    if self.buf.len() > 0 {
-                        self.tty.bs();
-                        self.buf.pop();
+            self.line.receive(key, &mut self.tty);
+            if self.line.take_signal().is_some() {
+                self.print_flair();
+            }
+
+            let mut line = [0u8; BUF_SIZE];
+            while let Some(len) = self.line.read(&mut line) {
+                // Ctrl-D on an empty line, the shell has nowhere to exit to.
+                if len == 0 {
+                    continue;
+                }
+                // Outside of canonical mode, input arrives as it is typed and
+                // the shell collects it into lines itself.
+                let input = line.get(..len).unwrap_or(&[]);
+                for c in input {
+                    if *c == b'\n' {
+                        self.execute_command();
+                    } else if self.buf.len() < BUF_SIZE - 1 {
+                        self.buf.push(*c);
                     }
                 }
-                0x0A => {
-                    self.tty.nl();
+                // A line finished early with Ctrl-D has no newline.
+                if self.line.settings().canonical && input.last() != Some(&b'\n') {
                     self.execute_command();
                 }
-                _ => {
-                    self.tty.put_char(key);
-                    self.buf.push(key);
-                }
             }
         }
     }
@@ -69,20 +154,37 @@ impl<'a> Shell<'a> {
 
         for (cmd_str, cmd_func) in &self.cmds {
             for i in 0..cmd.len() {
-                if cmd[i] != cmd_str[i] {
-                    break;
-                }
-                if cmd_str[i] == b'\0' {
+                // Arguments follow the command after a space.
+                if cmd_str[i] == b'\0' && (cmd[i] == b'\0' || (i > 0 && cmd[i] == b' ')) {
+                    let args = cmd.get(i + 1..).unwrap_or(&[]);
                     unsafe {
                         match cmd_func {
                             Command::Empty => {},
                             Command::PS2 => ps2_cli(&mut self.tty),
                             Command::Commands => self.print_cmd_options(),
                             Command::Mem => self.print_mem(),
+                            Command::Cpus => self.print_cpus(),
+                            Command::Irqs => self.print_irqs(),
+                            Command::ACPI => self.print_acpi_tables(),
+                            Command::AML => aml_cli(&mut self.tty, next_arg(args).0),
+                            Command::PCI => self.print_pci_devices(),
+                            Command::Uptime => self.print_uptime(),
+                            Command::Sleep => self.sleep(args),
+                            Command::Date => self.date(args),
+                            Command::Layout => self.layout(args),
+                            Command::Kbd => self.kbd(args),
+                            Command::Mode => self.mode(args),
+                            Command::Stty => self.stty(args),
+                            Command::Color => self.color(args),
+                            Command::Shutdown => self.shutdown(),
+                            Command::Reboot => power::reboot(),
                         }
                     }
                     return;
                 }
+                if cmd[i] != cmd_str[i] {
+                    break;
+                }
             }
         }
         unsafe {
@@ -110,6 +212,635 @@ impl<'a> Shell<'a> {
         }
     }
 
+    unsafe fn print_cpus(&mut self) {
+        unsafe {
+            self.tty
+                .println_ascii("CPU - APIC ID - Online - Tasks run".as_bytes());
+            for index in 0..cpu::count() {
+                if let Some(cpu) = cpu::get(index) {
+                    self.tty.print_hex(index as u8);
+                    self.tty.print_ascii(" - ".as_bytes());
+                    self.tty.print_hex(cpu.apic_id);
+                    self.tty.print_ascii(" - ".as_bytes());
+                    if cpu.online.load(Ordering::Acquire) {
+                        self.tty.print_ascii("yes".as_bytes());
+                    } else {
+                        self.tty.print_ascii("no".as_bytes());
+                    }
+                    self.tty.print_ascii(" - ".as_bytes());
+                    self.tty.print_hex(cpu.tasks_run.load(Ordering::Relaxed));
+                    self.tty.nl();
+                }
+            }
+        }
+    }
+
+    unsafe fn print_irqs(&mut self) {
+        unsafe {
+            self.tty
+                .print_ascii("Vectors with IRQ handlers:".as_bytes());
+            for vector in isr::used_vectors() {
+                self.tty.print_ascii(" ".as_bytes());
+                self.tty.print_hex(vector);
+            }
+            self.tty.nl();
+        }
+    }
+
+    unsafe fn print_acpi_tables(&mut self) {
+        unsafe {
+            let Some(acpi) = ACPI::load() else {
+                self.tty.println_ascii("No RSDP".as_bytes());
+                return;
+            };
+            self.tty.print_ascii("RSDP revision ".as_bytes());
+            self.tty.print_hex(acpi.rsdp().revision());
+            self.tty.print_ascii(", OEM ID ".as_bytes());
+            self.tty.print_ascii(&acpi.rsdp().oem_id());
+            if acpi.uses_xsdt() {
+                self.tty.println_ascii(", XSDT".as_bytes());
+            } else {
+                self.tty.println_ascii(", RSDT".as_bytes());
+            }
+            self.tty
+                .println_ascii("Table - OEM ID - OEM table ID - Length - Checksum".as_bytes());
+            let mut iter = acpi.iter();
+            while let Some(header) = iter.next() {
+                self.tty.print_ascii(&header.signature);
+                self.tty.print_ascii(" - ".as_bytes());
+                self.tty.print_ascii(&header.oem_id());
+                self.tty.print_ascii(" - ".as_bytes());
+                self.tty.print_ascii(&header.oem_table_id());
+                self.tty.print_ascii(" - ".as_bytes());
+                self.tty.print_hex(header.length());
+                if header.is_valid() {
+                    self.tty.println_ascii(" - ok".as_bytes());
+                } else {
+                    self.tty.println_ascii(" - bad".as_bytes());
+                }
+            }
+            if let Some(fadt) = FADT::from_acpi(&acpi) {
+                self.tty.print_ascii("FADT: SCI IRQ ".as_bytes());
+                self.tty.print_hex(fadt.sci_irq());
+                self.tty.print_ascii(", PM timer ".as_bytes());
+                self.tty.print_hex(fadt.pm_tmr_blk());
+                self.tty.print_ascii(", boot arch ".as_bytes());
+                self.tty.print_hex(fadt.boot_arch());
+                self.tty.print_ascii(", flags ".as_bytes());
+                self.tty.print_hex(fadt.flags());
+                self.tty.nl();
+            }
+            if let Some(madt) = MADT::from_acpi(&acpi) {
+                self.print_madt(madt);
+            }
+            if let Some(hpet) = HPET::from_acpi(&acpi) {
+                self.tty.print_ascii("HPET ".as_bytes());
+                self.tty.print_hex(hpet.hpet_number());
+                self.tty.print_ascii(": ".as_bytes());
+                self.tty.print_decimal(hpet.comparators());
+                self.tty
+                    .print_ascii(" comparators, minimum tick ".as_bytes());
+                self.tty.print_hex(hpet.minimum_tick());
+                self.tty.nl();
+            }
+            if let Some(mcfg) = MCFG::from_acpi(&acpi) {
+                self.tty.print_ascii("MCFG: ".as_bytes());
+                match mcfg.entry_for(0, 0) {
+                    Some(entry) => {
+                        let base = entry.base_addr;
+                        self.tty.print_ascii("segment 0 bus 0 at ".as_bytes());
+                        self.tty.print_hex(base);
+                        self.tty.nl();
+                    }
+                    None => self.tty.println_ascii("no allocation for bus 0".as_bytes()),
+                }
+            }
+        }
+    }
+
+    /// Prints the interrupt controllers and routing the MADT describes, one
+    /// entry per line.
+    unsafe fn print_madt(&mut self, madt: &MADT) {
+        unsafe {
+            let mut iter = madt.iter();
+            while let Some(entry) = iter.next() {
+                self.tty.print_ascii("MADT ".as_bytes());
+                match entry {
+                    MADTEntry::LocalAPIC {
+                        processor_id,
+                        apic_id,
+                        flags,
+                    } => {
+                        self.tty.print_ascii("CPU ".as_bytes());
+                        self.tty.print_hex(processor_id);
+                        self.tty.print_ascii(" APIC ID ".as_bytes());
+                        self.tty.print_hex(apic_id);
+                        if flags & LOCAL_APIC_ENABLED != 0 {
+                            self.tty.print_ascii(" enabled".as_bytes());
+                        } else if flags & LOCAL_APIC_ONLINE_CAPABLE != 0 {
+                            self.tty.print_ascii(" online capable".as_bytes());
+                        }
+                    }
+                    MADTEntry::IOAPIC { id, addr, gsi_base } => {
+                        self.tty.print_ascii("IO-APIC ".as_bytes());
+                        self.tty.print_hex(id);
+                        self.tty.print_ascii(" at ".as_bytes());
+                        self.tty.print_hex(addr);
+                        self.tty.print_ascii(" GSI base ".as_bytes());
+                        self.tty.print_hex(gsi_base);
+                    }
+                    MADTEntry::InterruptSourceOverride {
+                        bus,
+                        source,
+                        gsi,
+                        flags,
+                    } => {
+                        self.tty.print_ascii("bus ".as_bytes());
+                        self.tty.print_hex(bus);
+                        self.tty.print_ascii(" IRQ ".as_bytes());
+                        self.tty.print_hex(source);
+                        self.tty.print_ascii(" -> GSI ".as_bytes());
+                        self.tty.print_hex(gsi);
+                        self.tty.print_ascii(" flags ".as_bytes());
+                        self.tty.print_hex(flags);
+                    }
+                    MADTEntry::NMISource { flags, gsi } => {
+                        self.tty.print_ascii("NMI GSI ".as_bytes());
+                        self.tty.print_hex(gsi);
+                        self.tty.print_ascii(" flags ".as_bytes());
+                        self.tty.print_hex(flags);
+                    }
+                    MADTEntry::LocalAPICNMI {
+                        processor_id,
+                        flags,
+                        lint,
+                    } => {
+                        self.tty.print_ascii("NMI CPU ".as_bytes());
+                        self.tty.print_hex(processor_id);
+                        self.tty.print_ascii(" LINT".as_bytes());
+                        self.tty.print_hex(lint);
+                        self.tty.print_ascii(" flags ".as_bytes());
+                        self.tty.print_hex(flags);
+                    }
+                    MADTEntry::LocalAPICAddressOverride { addr } => {
+                        self.tty.print_ascii("local APIC at ".as_bytes());
+                        self.tty.print_hex(addr);
+                    }
+                    MADTEntry::Unknown { typ } => {
+                        self.tty.print_ascii("unknown entry ".as_bytes());
+                        self.tty.print_hex(typ);
+                    }
+                }
+                self.tty.nl();
+            }
+        }
+    }
+
+    unsafe fn print_pci_devices(&mut self) {
+        unsafe {
+            let Ok(kernel) = KERNEL.get() else {
+                self.tty.println_ascii("Kernel Error.".as_bytes());
+                return;
+            };
+            let bus = kernel.pci_bus().lock();
+            match bus.config() {
+                ConfigSpace::Ports => self.tty.println_ascii("Config access: ports".as_bytes()),
+                ConfigSpace::ECAM { base, .. } => {
+                    self.tty.print_ascii("Config access: ECAM at ".as_bytes());
+                    self.tty.print_hex(*base);
+                    self.tty.nl();
+                }
+            }
+            for device in bus.devices() {
+                self.tty.print_hex(device.address.bus);
+                self.tty.print_ascii(":".as_bytes());
+                self.tty.print_hex(device.address.device);
+                self.tty.print_ascii(".".as_bytes());
+                self.tty.print_hex(device.address.function);
+                self.tty.print_ascii(" ".as_bytes());
+                self.tty.print_hex(device.vendor_id);
+                self.tty.print_ascii(":".as_bytes());
+                self.tty.print_hex(device.device_id);
+                self.tty.print_ascii(" ".as_bytes());
+                self.tty.print_ascii(device.class_name().as_bytes());
+                self.tty.print_ascii(" (prog-if ".as_bytes());
+                self.tty.print_hex(device.prog_if);
+                self.tty.print_ascii(" rev ".as_bytes());
+                self.tty.print_hex(device.revision);
+                self.tty.print_ascii(")".as_bytes());
+                if device.interrupt_pin != 0 {
+                    self.tty.print_ascii(" IRQ ".as_bytes());
+                    self.tty.print_hex(device.interrupt_line);
+                }
+                if let Some(driver) = device.driver {
+                    self.tty.print_ascii(" [".as_bytes());
+                    self.tty.print_ascii(driver.as_bytes());
+                    self.tty.print_ascii("]".as_bytes());
+                }
+                self.tty.nl();
+
+                for (index, bar) in device.bars.iter().enumerate() {
+                    match *bar {
+                        BAR::Unused => {}
+                        BAR::IO { port, size } => {
+                            self.tty.print_ascii("  BAR".as_bytes());
+                            self.tty.print_hex(index as u8);
+                            self.tty.print_ascii(" IO ".as_bytes());
+                            self.tty.print_hex(port);
+                            self.tty.print_ascii(" size ".as_bytes());
+                            self.tty.print_hex(size);
+                            self.tty.nl();
+                        }
+                        BAR::Memory {
+                            base,
+                            size,
+                            prefetchable,
+                            ..
+                        } => {
+                            self.tty.print_ascii("  BAR".as_bytes());
+                            self.tty.print_hex(index as u8);
+                            self.tty.print_ascii(" MEM ".as_bytes());
+                            self.tty.print_hex(base);
+                            self.tty.print_ascii(" size ".as_bytes());
+                            self.tty.print_hex(size);
+                            if prefetchable {
+                                self.tty.print_ascii(" prefetchable".as_bytes());
+                            }
+                            self.tty.nl();
+                        }
+                    }
+                }
+            }
+            if bus.missed > 0 {
+                self.tty.print_decimal(bus.missed as u32);
+                self.tty.println_ascii(" more functions not listed".as_bytes());
+            }
+        }
+    }
+
+    unsafe fn print_uptime(&mut self) {
+        unsafe {
+            let ms = time::uptime_ms();
+            let secs = ms / 1000;
+            self.tty.print_ascii("Up ".as_bytes());
+            self.tty.print_decimal(secs / 86400);
+            self.tty.print_ascii("d ".as_bytes());
+            self.print_padded(secs / 3600 % 24, 2);
+            self.tty.print_ascii(":".as_bytes());
+            self.print_padded(secs / 60 % 60, 2);
+            self.tty.print_ascii(":".as_bytes());
+            self.print_padded(secs % 60, 2);
+            self.tty.print_ascii(".".as_bytes());
+            self.print_padded(ms % 1000, 3);
+            self.tty.print_ascii(", ".as_bytes());
+            self.tty.print_decimal(time::ticks());
+            self.tty.print_ascii(" ticks at ".as_bytes());
+            self.tty.print_decimal(time::frequency());
+            self.tty.println_ascii(" Hz".as_bytes());
+            if let Some(clock) = clock::clock() {
+                self.tty.print_ascii("Clock: ".as_bytes());
+                self.tty.print_ascii(clock.name().as_bytes());
+                self.tty.print_ascii(" at ".as_bytes());
+                self.tty.print_decimal(clock.frequency());
+                self.tty.print_ascii(" Hz, ".as_bytes());
+                self.tty.print_decimal(clock.nanos());
+                self.tty.println_ascii(" ns".as_bytes());
+            }
+            if apic::timer_frequency() != 0 {
+                self.tty.print_ascii("APIC timer: ".as_bytes());
+                self.tty.print_decimal(apic::timer_ticks());
+                self.tty.print_ascii(" interrupts, counting at ".as_bytes());
+                self.tty.print_decimal(apic::timer_frequency());
+                self.tty.println_ascii(" Hz".as_bytes());
+            }
+            self.tty.print_ascii("Events: ".as_bytes());
+            self.tty.print_decimal(isr::queued_events() as u32);
+            self.tty.print_ascii(" queued, ".as_bytes());
+            self.tty.print_decimal(isr::dropped_events());
+            self.tty.print_ascii(" dropped, up to ".as_bytes());
+            self.tty.print_decimal(isr::max_event_delay());
+            self.tty.println_ascii(" ticks late".as_bytes());
+        }
+    }
+
+    /// Waits for the given number of milliseconds, then prints how long the
+    /// wait took by the clock.
+    unsafe fn sleep(&mut self, args: &[u8]) {
+        unsafe {
+            let Some(ms) = parse_decimal(next_arg(args).0) else {
+                self.tty.println_ascii("Usage: sleep <ms>".as_bytes());
+                return;
+            };
+            let start = clock::nanos();
+            time::sleep_ms(ms);
+            self.tty.print_ascii("Slept ".as_bytes());
+            self.tty.print_decimal(clock::nanos().wrapping_sub(start));
+            self.tty.println_ascii(" ns".as_bytes());
+        }
+    }
+
+    /// Without arguments, prints the date and time. `-p <rate>` enables the
+    /// RTC's periodic interrupt at `32768 >> (rate - 1)` Hz, for rates 3 to 15.
+    unsafe fn date(&mut self, args: &[u8]) {
+        unsafe {
+            let (option, rest) = next_arg(args);
+            if option == b"-p" {
+                let (rate, _) = next_arg(rest);
+                let rate = parse_decimal(rate).and_then(|rate| u8::try_from(rate).ok());
+                match rate.map(|rate| rtc::enable_periodic_interrupt(rate)) {
+                    Some(Ok(frequency)) => {
+                        self.tty.print_ascii("RTC interrupt at ".as_bytes());
+                        self.tty.print_decimal(frequency);
+                        self.tty.println_ascii(" Hz".as_bytes());
+                    }
+                    Some(Err(RTCError::IrqUnavailable)) => {
+                        self.tty.println_ascii("IRQ 8 is unavailable.".as_bytes());
+                    }
+                    Some(Err(RTCError::InvalidRate)) | None => {
+                        self.tty
+                            .println_ascii("The rate must be 3 to 15.".as_bytes());
+                    }
+                }
+                return;
+            }
+            if !option.is_empty() {
+                self.tty.println_ascii("Usage: date [-p <rate>]".as_bytes());
+                return;
+            }
+            self.print_date();
+        }
+    }
+
+    unsafe fn print_date(&mut self) {
+        unsafe {
+            let Some(now) = rtc::now() else {
+                self.tty
+                    .println_ascii("The RTC isn't responding.".as_bytes());
+                return;
+            };
+            self.print_padded(now.year as u64, 4);
+            self.tty.print_ascii("-".as_bytes());
+            self.print_padded(now.month as u64, 2);
+            self.tty.print_ascii("-".as_bytes());
+            self.print_padded(now.day as u64, 2);
+            self.tty.print_ascii(" ".as_bytes());
+            self.print_padded(now.hour as u64, 2);
+            self.tty.print_ascii(":".as_bytes());
+            self.print_padded(now.minute as u64, 2);
+            self.tty.print_ascii(":".as_bytes());
+            self.print_padded(now.second as u64, 2);
+            self.tty.print_ascii(" UTC, Unix time ".as_bytes());
+            self.tty.print_decimal(now.unix_timestamp());
+            self.tty.nl();
+            let ticks = rtc::periodic_ticks();
+            if ticks != 0 {
+                self.tty.print_ascii("RTC interrupts: ".as_bytes());
+                self.tty.print_decimal(ticks);
+                self.tty.nl();
+            }
+        }
+    }
+
+    /// Without arguments, lists the keyboard layouts and marks the current
+    /// one. With a layout name, switches to that layout.
+    unsafe fn layout(&mut self, args: &[u8]) {
+        unsafe {
+            let Ok(kernel) = KERNEL.get() else {
+                self.tty.println_ascii("Kernel Error.".as_bytes());
+                return;
+            };
+            let (name, _) = next_arg(args);
+            let mut driver = kernel.keyboard_driver().lock();
+            if name.is_empty() {
+                for layout in layouts::LAYOUTS {
+                    if core::ptr::eq(layout, driver.layout()) {
+                        self.tty.print_ascii("* ".as_bytes());
+                    } else {
+                        self.tty.print_ascii("  ".as_bytes());
+                    }
+                    self.tty.print_ascii(layout.name.as_bytes());
+                    self.tty.print_ascii(" - ".as_bytes());
+                    self.tty.println_ascii(layout.description.as_bytes());
+                }
+                return;
+            }
+            match layouts::find(name) {
+                Some(layout) => driver.set_layout(layout),
+                None => self.tty.println_ascii("Unknown layout.".as_bytes()),
+            }
+        }
+    }
+
+    /// Without arguments, prints the terminal settings. Otherwise applies
+    /// `raw`, `cooked`, `echo` and `-echo` in order.
+    unsafe fn stty(&mut self, args: &[u8]) {
+        unsafe {
+            let mut settings = self.line.settings();
+            let (mut arg, mut rest) = next_arg(args);
+            if arg.is_empty() {
+                for (name, enabled) in [
+                    ("canonical", settings.canonical),
+                    ("echo", settings.echo),
+                    ("signals", settings.signals),
+                ] {
+                    if !enabled {
+                        self.tty.print_ascii("-".as_bytes());
+                    }
+                    self.tty.print_ascii(name.as_bytes());
+                    self.tty.print_ascii(" ".as_bytes());
+                }
+                self.tty.nl();
+                return;
+            }
+            while !arg.is_empty() {
+                match arg {
+                    b"raw" => settings = TtySettings::raw(),
+                    b"cooked" => settings = TtySettings::cooked(),
+                    b"echo" => settings.echo = true,
+                    b"-echo" => settings.echo = false,
+                    _ => {
+                        self.tty
+                            .println_ascii("Usage: stty [raw|cooked|echo|-echo]...".as_bytes());
+                        return;
+                    }
+                }
+                (arg, rest) = next_arg(rest);
+            }
+            self.line.set_settings(settings);
+        }
+    }
+
+    /// Sets the colors of the text printed from now on, given as VGA color
+    /// numbers 0 to 15. With `-d`, they also become the colors escape
+    /// sequences reset to.
+    unsafe fn color(&mut self, args: &[u8]) {
+        unsafe {
+            let (mut arg, mut rest) = next_arg(args);
+            let default = arg == b"-d";
+            if default {
+                (arg, rest) = next_arg(rest);
+            }
+            let color = |arg| {
+                parse_decimal(arg)
+                    .and_then(|n| usize::try_from(n).ok())
+                    .and_then(|n| Color::ALL.get(n).copied())
+            };
+            let (Some(foreground), Some(background)) = (color(arg), color(next_arg(rest).0)) else {
+                self.tty
+                    .println_ascii("Usage: color [-d] <foreground> <background>".as_bytes());
+                return;
+            };
+            let attribute = Attribute::new(foreground, background);
+            if default {
+                self.tty.set_default_colors(attribute);
+            } else {
+                self.tty.set_colors(attribute);
+            }
+        }
+    }
+
+    /// Without arguments, lists the video modes. Otherwise switches to one.
+    /// Graphics modes show a test pattern until a key is pressed or the mouse
+    /// is used. `-b on|off` chooses whether blinking text blinks or gets a
+    /// bright background.
+    unsafe fn mode(&mut self, args: &[u8]) {
+        unsafe {
+            let (name, rest) = next_arg(args);
+            if name == b"-b" {
+                match next_arg(rest).0 {
+                    b"on" => self.tty.set_blink(true),
+                    b"off" => self.tty.set_blink(false),
+                    _ => self.tty.println_ascii("Usage: mode -b on|off".as_bytes()),
+                }
+                return;
+            }
+            if name.is_empty() {
+                let current = self.tty.mode();
+                for (name, mode) in MODES {
+                    if mode == current {
+                        self.tty.print_ascii("* ".as_bytes());
+                    } else {
+                        self.tty.print_ascii("  ".as_bytes());
+                    }
+                    self.tty.println_ascii(name.as_bytes());
+                }
+                return;
+            }
+            let Some(mode) = vga_modes::find(name) else {
+                self.tty.println_ascii("Unknown mode.".as_bytes());
+                return;
+            };
+            self.tty.set_mode(mode);
+            if let Some((width, height)) = mode.resolution() {
+                self.draw_test_pattern(width, height);
+            }
+        }
+    }
+
+    /// Fills the screen with a bar of each of the first 16 colors.
+    unsafe fn draw_test_pattern(&mut self, width: u16, height: u16) {
+        let bar = width / 16;
+        for y in 0..height {
+            for x in 0..width {
+                unsafe { self.tty.put_pixel(x, y, (x / bar) as u8) };
+            }
+        }
+    }
+
+    /// Without arguments, shows the keyboard's settings. Otherwise sets one:
+    /// `set 1|2`, `delay <ms>`, `rate <per second>` or `repeat hw|sw`.
+    unsafe fn kbd(&mut self, args: &[u8]) {
+        unsafe {
+            let Ok(kernel) = KERNEL.get() else {
+                self.tty.println_ascii("Kernel Error.".as_bytes());
+                return;
+            };
+            let mut driver = kernel.keyboard_driver().lock();
+            let (setting, rest) = next_arg(args);
+            let (value, _) = next_arg(rest);
+            let number = parse_decimal(value);
+            let typematic = driver.typematic();
+            let result = match (setting, number) {
+                (b"", _) => {
+                    self.tty.print_ascii("Scan code set: ".as_bytes());
+                    match driver.scan_code_set() {
+                        Ok(set) => self.tty.print_decimal(set),
+                        Err(_) => self.tty.print_ascii("unknown".as_bytes()),
+                    }
+                    if kernel.ps2_controller().translation() {
+                        self.tty.println_ascii(", translated".as_bytes());
+                    } else {
+                        self.tty.nl();
+                    }
+                    self.tty.print_ascii("Typematic delay: ".as_bytes());
+                    self.tty.print_decimal(typematic.delay_ms);
+                    self.tty.print_ascii(" ms, rate: ".as_bytes());
+                    self.tty.print_decimal(typematic.rate_decihertz / 10);
+                    self.tty.put_char(b'.');
+                    self.tty.print_decimal(typematic.rate_decihertz % 10);
+                    self.tty.println_ascii("/s".as_bytes());
+                    match driver.repeat_mode() {
+                        RepeatMode::Hardware => {
+                            self.tty.println_ascii("Repeat: hardware".as_bytes())
+                        }
+                        RepeatMode::Software => {
+                            self.tty.println_ascii("Repeat: software".as_bytes())
+                        }
+                    }
+                    Ok(())
+                }
+                (b"set", Some(set)) => driver.set_scan_code_set(set as u8),
+                (b"delay", Some(delay)) => driver.set_typematic(delay, typematic.rate_decihertz),
+                (b"rate", Some(rate)) => driver.set_typematic(typematic.delay_ms, rate * 10),
+                (b"repeat", _) if value == b"hw" => {
+                    driver.set_repeat_mode(RepeatMode::Hardware);
+                    Ok(())
+                }
+                (b"repeat", _) if value == b"sw" => {
+                    driver.set_repeat_mode(RepeatMode::Software);
+                    Ok(())
+                }
+                _ => {
+                    self.tty.println_ascii(
+                        "Usage: kbd [set 1|2|delay ms|rate n|repeat hw|sw]".as_bytes(),
+                    );
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                self.tty.print_ascii("Keyboard error: ".as_bytes());
+                print_reason(&mut self.tty, err.describe());
+            }
+        }
+    }
+
+    /// Prints a number with leading zeroes up to `width` digits.
+    unsafe fn print_padded(&mut self, n: u64, width: u32) {
+        unsafe {
+            for digits in 1..width {
+                if n < 10u64.pow(digits) {
+                    self.tty.put_char(b'0');
+                }
+            }
+            self.tty.print_decimal(n);
+        }
+    }
+
+    unsafe fn shutdown(&mut self) {
+        unsafe {
+            let reason = match power::shutdown() {
+                PowerError::NoACPI => "no ACPI tables",
+                PowerError::NoFADT => "no FADT",
+                PowerError::NoDSDT => "no valid DSDT",
+                PowerError::NoS5 => "no \\_S5 object in the DSDT",
+                PowerError::ACPIModeTimeout => "couldn't enable ACPI mode",
+                PowerError::NoResponse => "the machine didn't power off",
+            };
+            self.tty.print_ascii("Shutdown failed: ".as_bytes());
+            self.tty.println_ascii(reason.as_bytes());
+        }
+    }
+
     unsafe fn print_mem(&mut self) {
         unsafe {
             match KERNEL.get() {
@@ -139,3 +870,93 @@ impl<'a> Shell<'a> {
         }
     }
 }
+
+/// A shell on every virtual console. Keyboard input goes to the one in the
+/// foreground, apart from the keys that switch and scroll consoles. The mouse
+/// wheel scrolls the foreground console.
+pub struct Shells<'a> {
+    vga: &'a mut VGAText,
+    shells: [Option<Shell<'a>>; CONSOLE_COUNT],
+}
+
+impl<'a> Shells<'a> {
+    /// Starts the shells, drawing each console through its own handle in
+    /// `screens`. `vga` is used to switch consoles.
+    pub unsafe fn new(vga: &'a mut VGAText, screens: &'a mut [VGAText; CONSOLE_COUNT]) -> Self {
+        let mut index = 0;
+        let shells = screens.each_mut().map(|screen| {
+            let tty = unsafe { VGATextWriter::get_console(screen, index) };
+            index += 1;
+            tty.map(|tty| unsafe { Shell::new(tty) })
+        });
+        Self { vga, shells }
+    }
+}
+
+impl<'a> EventSubscriber for Shells<'a> {
+    unsafe fn on_event(&mut self, event: &InterruptEvent) {
+        let (key_event, wheel) = match event.event {
+            SysEvent::Key(key_event) => (Some(key_event), 0),
+            SysEvent::Mouse {
+                dx,
+                dy,
+                wheel,
+                buttons,
+            } => {
+                if dx == 0 && dy == 0 && wheel == 0 && buttons == 0 {
+                    return;
+                }
+                (None, wheel)
+            }
+            _ => return,
+        };
+        // A key press or using the mouse leaves a graphics mode, back to the
+        // text mode the consoles were last shown in.
+        if !self.vga.is_text() {
+            if key_event.is_none_or(|key_event| key_event.pressed) {
+                unsafe {
+                    self.vga.set_mode(self.vga.text_mode());
+                    console::redraw(self.vga);
+                }
+            }
+            return;
+        }
+        let Some(key_event) = key_event else {
+            if wheel != 0 {
+                unsafe { console::scroll_view(self.vga, -(wheel as isize) * WHEEL_ROWS) };
+            }
+            return;
+        };
+        let Ok(kernel) = KERNEL.get() else {
+            return;
+        };
+        if let Some(console_key) = ConsoleKey::from_event(&key_event) {
+            unsafe { console_key.apply(self.vga) };
+            return;
+        }
+        let key = kernel
+            .keyboard_driver()
+            .lock()
+            .translate(&key_event)
+            .and_then(cp437::from_char);
+        if let Some(key) = key
+            && let Some(Some(shell)) = self.shells.get_mut(console::foreground())
+        {
+            unsafe { shell.handle_key(key) };
+        }
+    }
+}
+
+/// Splits the first space separated argument off `args`, which ends at a NUL
+/// byte.
+fn next_arg(args: &[u8]) -> (&[u8], &[u8]) {
+    let end = args.iter().position(|b| *b == b'\0').unwrap_or(args.len());
+    let args = args.get(..end).unwrap_or(&[]);
+    match args.iter().position(|b| *b == b' ') {
+        Some(space) => (
+            args.get(..space).unwrap_or(&[]),
+            args.get(space + 1..).unwrap_or(&[]),
+        ),
+        None => (args, &[]),
+    }
+}
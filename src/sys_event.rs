@@ -1,4 +1,69 @@
+use crate::kernel::keycode::KeyEvent;
+
 #[derive(Clone, Copy)]
 pub enum SysEvent {
-    Keyboard,
-}
\ No newline at end of file
+    /// A kernel timer is due. The tick it fell due at is the event's.
+    Timer,
+    /// A byte arrived from the keyboard, as read from the controller. It may
+    /// be part of a longer scan code or a reply to a command, so only the
+    /// keyboard driver can tell which key was pressed or released.
+    Scancode { scancode: u8 },
+    /// A key went down, up or repeated, as decoded by the keyboard driver
+    /// from the scan codes. Repeats are presses of a key already held.
+    Key(KeyEvent),
+    /// The mouse moved, scrolled or its buttons changed. `buttons` has a bit
+    /// per button.
+    Mouse {
+        dx: i16,
+        dy: i16,
+        wheel: i8,
+        buttons: u8,
+    },
+}
+
+/// The kinds of events, used to filter which events a subscriber receives.
+#[derive(Clone, Copy)]
+pub enum EventKind {
+    Timer = 1 << 0,
+    Scancode = 1 << 1,
+    Key = 1 << 2,
+    Mouse = 1 << 3,
+}
+
+impl SysEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            SysEvent::Timer => EventKind::Timer,
+            SysEvent::Scancode { .. } => EventKind::Scancode,
+            SysEvent::Key(_) => EventKind::Key,
+            SysEvent::Mouse { .. } => EventKind::Mouse,
+        }
+    }
+}
+
+/// A set of event kinds.
+#[derive(Clone, Copy)]
+pub struct EventFilter(u32);
+
+impl EventFilter {
+    pub const fn none() -> Self {
+        Self(0)
+    }
+
+    pub const fn with(self, kind: EventKind) -> Self {
+        Self(self.0 | kind as u32)
+    }
+
+    pub fn matches(&self, kind: EventKind) -> bool {
+        self.0 & kind as u32 != 0
+    }
+}
+
+/// An event raised in interrupt context, along with the IRQ line of the device
+/// that raised it and the timer tick it was raised at.
+#[derive(Clone, Copy)]
+pub struct InterruptEvent {
+    pub event: SysEvent,
+    pub device: u8,
+    pub tick: u32,
+}
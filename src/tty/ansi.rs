@@ -0,0 +1,234 @@
+// ANSI escape sequences
+//
+// Output to a console may contain the escape sequences of VT100 compatible
+// terminals: ESC followed by a single byte, or a control sequence, ESC [
+// followed by numeric parameters separated by semicolons and a final byte
+// naming the function. A `?` before the parameters marks the private
+// sequences of DEC terminals, and a few functions take an intermediate byte
+// between the parameters and the final byte.
+
+use crate::kernel::vga_driver::{Attribute, Color};
+
+const ESC: u8 = 0x1B;
+/// Cancels a sequence in progress.
+const CANCEL: u8 = 0x18;
+const SUBSTITUTE: u8 = 0x1A;
+const MAX_PARAMS: usize = 8;
+
+/// The colors of SGR parameters 30 to 37, as VGA colors.
+const ANSI_TO_VGA: [u8; 8] = [
+    Color::Black as u8,
+    Color::Red as u8,
+    Color::Green as u8,
+    Color::Brown as u8,
+    Color::Blue as u8,
+    Color::Magenta as u8,
+    Color::Cyan as u8,
+    Color::LightGray as u8,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    ControlSequence,
+}
+
+/// A complete control sequence.
+#[derive(Clone, Copy)]
+pub struct ControlSequence {
+    pub final_byte: u8,
+    pub private: bool,
+    /// The last intermediate byte, such as the space of `ESC [ n SP q`.
+    pub intermediate: Option<u8>,
+    params: [u16; MAX_PARAMS],
+    len: usize,
+}
+
+impl ControlSequence {
+    const fn new() -> Self {
+        Self {
+            final_byte: 0,
+            private: false,
+            intermediate: None,
+            params: [0; MAX_PARAMS],
+            len: 0,
+        }
+    }
+
+    /// The parameter at `index`, or `default` when it is left out or zero.
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(0) | None => default,
+            Some(param) => *param,
+        }
+    }
+
+    pub fn params(&self) -> &[u16] {
+        self.params.get(..self.len).unwrap_or(&[])
+    }
+}
+
+/// What a byte written to the terminal amounts to.
+pub enum Action {
+    /// The byte is part of a sequence that isn't finished yet.
+    None,
+    Print(u8),
+    /// ESC followed by this byte.
+    Escape(u8),
+    ControlSequence(ControlSequence),
+}
+
+/// Picks escape sequences out of the bytes written to a terminal.
+pub struct AnsiParser {
+    state: State,
+    sequence: ControlSequence,
+}
+
+impl AnsiParser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            sequence: ControlSequence::new(),
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) -> Action {
+        match self.state {
+            State::Ground if byte == ESC => {
+                self.state = State::Escape;
+                Action::None
+            }
+            State::Ground => Action::Print(byte),
+            State::Escape => match byte {
+                b'[' => {
+                    self.state = State::ControlSequence;
+                    self.sequence = ControlSequence::new();
+                    Action::None
+                }
+                ESC => Action::None,
+                _ => {
+                    self.state = State::Ground;
+                    Action::Escape(byte)
+                }
+            },
+            State::ControlSequence => self.push_control_sequence(byte),
+        }
+    }
+
+    fn push_control_sequence(&mut self, byte: u8) -> Action {
+        let sequence = &mut self.sequence;
+        match byte {
+            b'0'..=b'9' => {
+                sequence.len = sequence.len.max(1);
+                if let Some(param) = sequence.params.get_mut(sequence.len - 1) {
+                    *param = param
+                        .saturating_mul(10)
+                        .saturating_add((byte - b'0') as u16);
+                }
+            }
+            b';' => {
+                // An empty parameter still counts, as its default.
+                sequence.len = (sequence.len.max(1) + 1).min(MAX_PARAMS);
+            }
+            b'?' if sequence.len == 0 => sequence.private = true,
+            0x20..=0x2F => sequence.intermediate = Some(byte),
+            // Final bytes.
+            0x40..=0x7E => {
+                sequence.final_byte = byte;
+                self.state = State::Ground;
+                return Action::ControlSequence(*sequence);
+            }
+            CANCEL | SUBSTITUTE => self.state = State::Ground,
+            ESC => self.state = State::Escape,
+            // Anything else has no meaning here.
+            _ => {}
+        }
+        Action::None
+    }
+}
+
+/// The graphic rendition set by SGR sequences, `ESC [ ... m`.
+#[derive(Clone, Copy)]
+pub struct Rendition {
+    /// What SGR 0, 39 and 49 go back to.
+    default: Attribute,
+    /// VGA colors, the intensity bit included.
+    foreground: u8,
+    background: u8,
+    bold: bool,
+    blink: bool,
+    reverse: bool,
+}
+
+impl Rendition {
+    pub const fn new(default: Attribute) -> Self {
+        Self {
+            default,
+            foreground: default.0 & 0x0F,
+            background: default.0 >> 4,
+            bold: false,
+            blink: false,
+            reverse: false,
+        }
+    }
+
+    /// Changes the colors, keeping the other attributes.
+    pub fn set_colors(&mut self, attribute: Attribute) {
+        self.foreground = attribute.foreground();
+        self.background = attribute.background();
+    }
+
+    /// Changes the default colors and goes back to them.
+    pub fn set_default(&mut self, default: Attribute) {
+        *self = Self::new(default);
+    }
+
+    /// Applies the parameters of an SGR sequence.
+    pub fn select(&mut self, sequence: &ControlSequence) {
+        if sequence.params().is_empty() {
+            *self = Self::new(self.default);
+        }
+        for param in sequence.params() {
+            match *param {
+                0 => *self = Self::new(self.default),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                5 => self.blink = true,
+                25 => self.blink = false,
+                7 => self.reverse = true,
+                27 => self.reverse = false,
+                30..=37 => self.foreground = ansi_color(*param - 30),
+                39 => self.foreground = self.default.foreground(),
+                40..=47 => self.background = ansi_color(*param - 40),
+                49 => self.background = self.default.background(),
+                90..=97 => self.foreground = ansi_color(*param - 90) | Attribute::INTENSITY,
+                100..=107 => self.background = ansi_color(*param - 100) | Attribute::INTENSITY,
+                _ => {}
+            }
+        }
+    }
+
+    /// The attribute characters are written in. Blinking takes the bit that
+    /// otherwise brightens the background, so it only shows while blinking is
+    /// enabled in `VGAText`.
+    pub fn attribute(&self) -> Attribute {
+        let mut foreground = self.foreground;
+        if self.bold {
+            foreground |= Attribute::INTENSITY;
+        }
+        let (foreground, mut background) = if self.reverse {
+            (self.background, foreground)
+        } else {
+            (foreground, self.background)
+        };
+        if self.blink {
+            background |= Attribute::INTENSITY;
+        }
+        Attribute((background << 4) | (foreground & 0x0F))
+    }
+}
+
+fn ansi_color(color: u16) -> u8 {
+    ANSI_TO_VGA.get(color as usize).copied().unwrap_or(0)
+}
@@ -0,0 +1,277 @@
+// Virtual consoles
+//
+// Each console keeps its own copy of the screen and its own cursor. Writers
+// always update the copy, and only the console in the foreground also writes
+// to VGA memory. Switching consoles copies the new console's screen to VGA
+// memory.
+//
+// Rows scrolling off the top of a console are kept in its history, which can
+// be viewed with Shift+PageUp and Shift+PageDown. Output to the console
+// brings the view back to the bottom.
+
+use core::slice;
+
+use crate::{
+    kernel::{
+        keycode::{KeyCode, KeyEvent},
+        mem::MemoryManager,
+        vga_driver::{Attribute, MAX_HEIGHT, MAX_WIDTH, VGAText},
+    },
+    util::parse_decimal,
+};
+
+pub const CONSOLE_COUNT: usize = 6;
+
+/// The rows of history each console keeps, unless the `SCROLLBACK_LINES`
+/// environment variable sets another number at build time.
+const DEFAULT_SCROLLBACK_LINES: usize = 100;
+/// The history comes from the kernel heap and takes at most this share of
+/// what the screens leave, the rest is for the commands of the shell.
+const HISTORY_HEAP_DIVISOR: usize = 2;
+/// Screens and rows of history are kept as wide as the widest text mode, so
+/// they survive mode changes.
+const ROW_CELLS: usize = MAX_WIDTH as usize;
+const SCREEN_CELLS: usize = ROW_CELLS * MAX_HEIGHT as usize;
+
+pub struct Console {
+    /// The screen, a character and attribute byte per cell. `None` until
+    /// `init` allocates it, writes then only reach VGA memory.
+    cells: Option<&'static mut [u16]>,
+    /// Rows that scrolled off the screen, used as a ring.
+    history: Option<&'static mut [u16]>,
+    /// The number of rows in the history and the slot of the next one.
+    history_len: usize,
+    history_next: usize,
+    /// How many rows the view is scrolled back into the history.
+    view_offset: usize,
+    pub x: u16,
+    pub y: u16,
+    pub cursor_visible: bool,
+    /// Set while a writer is using the console.
+    pub in_use: bool,
+}
+
+impl Console {
+    const fn new() -> Self {
+        Self {
+            cells: None,
+            history: None,
+            history_len: 0,
+            history_next: 0,
+            view_offset: 0,
+            x: 0,
+            y: 0,
+            cursor_visible: true,
+            in_use: false,
+        }
+    }
+
+    pub fn cells(&mut self) -> Option<&mut [u16]> {
+        self.cells.as_deref_mut()
+    }
+
+    /// Adds a row of the screen to the history, dropping the oldest row once
+    /// it is full.
+    pub fn save_row(&mut self, y: u16) {
+        let (Some(cells), Some(history)) = (self.cells.as_deref(), self.history.as_deref_mut())
+        else {
+            return;
+        };
+        let row = ROW_CELLS;
+        let start = y as usize * row;
+        let slot = self.history_next * row;
+        if let (Some(src), Some(dst)) = (
+            cells.get(start..start + row),
+            history.get_mut(slot..slot + row),
+        ) {
+            dst.copy_from_slice(src);
+            let capacity = history.len() / row;
+            self.history_next = (self.history_next + 1) % capacity;
+            self.history_len = (self.history_len + 1).min(capacity);
+        }
+    }
+
+    /// A row of history, 1 being the most recent one.
+    fn history_row(&self, back: usize) -> Option<&[u16]> {
+        let history = self.history.as_deref()?;
+        let row = ROW_CELLS;
+        let capacity = history.len() / row;
+        if back == 0 || back > self.history_len {
+            return None;
+        }
+        let slot = (self.history_next + capacity - back) % capacity;
+        history.get(slot * row..(slot + 1) * row)
+    }
+
+    pub fn scrolled_back(&self) -> bool {
+        self.view_offset != 0
+    }
+}
+
+static mut CONSOLES: [Console; CONSOLE_COUNT] = [const { Console::new() }; CONSOLE_COUNT];
+static mut FOREGROUND: usize = 0;
+
+/// Allocates the screens and history of the consoles. The history is cut
+/// short to fit the heap, and left out when even that fails.
+pub unsafe fn init(mem: &mut MemoryManager) {
+    for index in 0..CONSOLE_COUNT {
+        unsafe {
+            let Some(console) = console(index) else {
+                continue;
+            };
+            let Some(cells) = mem.malloc(SCREEN_CELLS * size_of::<u16>(), false) else {
+                return;
+            };
+            let cells = slice::from_raw_parts_mut(cells as *mut u16, SCREEN_CELLS);
+            cells.fill(Attribute::DEFAULT.cell(b' '));
+            console.cells = Some(cells);
+        }
+    }
+
+    let row_bytes = ROW_CELLS * size_of::<u16>();
+    let heap_lines = mem.available() / HISTORY_HEAP_DIVISOR / (CONSOLE_COUNT * row_bytes);
+    let history_lines = scrollback_lines().min(heap_lines);
+    let history_cells = history_lines * ROW_CELLS;
+    for index in 0..CONSOLE_COUNT {
+        unsafe {
+            let Some(console) = console(index) else {
+                continue;
+            };
+            if history_lines > 0
+                && let Some(history) = mem.malloc(history_cells * size_of::<u16>(), false)
+            {
+                let history = slice::from_raw_parts_mut(history as *mut u16, history_cells);
+                console.history = Some(history);
+            }
+        }
+    }
+}
+
+/// The rows of history kept per console, set at build time.
+fn scrollback_lines() -> usize {
+    option_env!("SCROLLBACK_LINES")
+        .and_then(|lines| parse_decimal(lines.as_bytes()))
+        .map_or(DEFAULT_SCROLLBACK_LINES, |lines| lines as usize)
+}
+
+/// The state of a console. Like the cursor of the writers, it isn't
+/// synchronised, callers must not keep it across writes to the console.
+pub unsafe fn console(index: usize) -> Option<&'static mut Console> {
+    if index >= CONSOLE_COUNT {
+        return None;
+    }
+    Some(unsafe { &mut *(&raw mut CONSOLES).cast::<Console>().add(index) })
+}
+
+pub fn foreground() -> usize {
+    unsafe { FOREGROUND }
+}
+
+/// Brings a console to the foreground, showing its screen and cursor.
+pub unsafe fn switch(driver: &mut VGAText, index: usize) {
+    unsafe {
+        if index == FOREGROUND || index >= CONSOLE_COUNT {
+            return;
+        }
+        FOREGROUND = index;
+        redraw(driver);
+    }
+}
+
+/// Scrolls the view of the foreground console back through its history by
+/// `rows`, or forward when negative.
+pub unsafe fn scroll_view(driver: &mut VGAText, rows: isize) {
+    unsafe {
+        let Some(console) = console(FOREGROUND) else {
+            return;
+        };
+        let offset = console.view_offset.saturating_add_signed(rows);
+        console.view_offset = offset.min(console.history_len);
+        redraw(driver);
+    }
+}
+
+/// Scrolls the view of the foreground console back to the bottom, if it was
+/// scrolled back.
+pub unsafe fn snap_back(driver: &mut VGAText) {
+    unsafe {
+        if let Some(console) = console(FOREGROUND)
+            && console.scrolled_back()
+        {
+            console.view_offset = 0;
+            redraw(driver);
+        }
+    }
+}
+
+/// Shows the foreground console on the screen. The cursor is hidden while
+/// the view is scrolled back.
+pub unsafe fn redraw(driver: &mut VGAText) {
+    unsafe {
+        let Some(console) = console(FOREGROUND) else {
+            return;
+        };
+        let offset = console.view_offset;
+        for y in 0..driver.height() {
+            let row = if (y as usize) < offset {
+                console.history_row(offset - y as usize)
+            } else {
+                let start = (y as usize - offset) * ROW_CELLS;
+                console
+                    .cells
+                    .as_deref()
+                    .and_then(|cells| cells.get(start..start + ROW_CELLS))
+            };
+            if let Some(row) = row {
+                driver.write_row(y, row);
+            }
+        }
+        driver.update_cursor_position(console.x, console.y);
+        driver.set_cursor_visible(console.cursor_visible && offset == 0);
+    }
+}
+
+/// What a key does to the consoles, if it is one of their shortcuts.
+pub enum ConsoleKey {
+    /// Bring the console with this index to the foreground.
+    Switch(usize),
+    /// Scroll the view by this many half screens, back when positive.
+    Scroll(isize),
+}
+
+impl ConsoleKey {
+    /// Alt and a function key switch consoles, F1 to the first one. Shift
+    /// and PageUp or PageDown scroll by half a screen.
+    pub fn from_event(event: &KeyEvent) -> Option<Self> {
+        use KeyCode::*;
+        if !event.pressed {
+            return None;
+        }
+        if event.modifiers.alt() {
+            [F1, F2, F3, F4, F5, F6]
+                .iter()
+                .position(|k| *k == event.code)
+                .map(Self::Switch)
+        } else if event.modifiers.shift() {
+            match event.code {
+                PageUp => Some(Self::Scroll(1)),
+                PageDown => Some(Self::Scroll(-1)),
+                _ => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    pub unsafe fn apply(&self, driver: &mut VGAText) {
+        unsafe {
+            match self {
+                Self::Switch(index) => switch(driver, *index),
+                Self::Scroll(half_screens) => {
+                    let rows = half_screens * (driver.height() / 2) as isize;
+                    scroll_view(driver, rows);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use crate::printer::VGATextWriter;
+
+/// The longest line that can be edited, a screen row.
+pub const LINE_CAPACITY: usize = 80;
+/// Input that was typed but not read yet.
+const INPUT_CAPACITY: usize = 256;
+
+const INTERRUPT: u8 = 0x03; // Ctrl-C
+const END_OF_FILE: u8 = 0x04; // Ctrl-D
+const BACKSPACE: u8 = 0x08;
+const KILL_LINE: u8 = 0x15; // Ctrl-U
+const WORD_ERASE: u8 = 0x17; // Ctrl-W
+const SUSPEND: u8 = 0x1A; // Ctrl-Z
+const DELETE: u8 = 0x7F;
+const NEWLINE: u8 = b'\n';
+/// Control characters are echoed as a caret and the letter at this offset.
+const CONTROL_ECHO_OFFSET: u8 = 0x40;
+
+/// Signals raised by control characters when signals are enabled.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Ctrl-C.
+    Interrupt,
+    /// Ctrl-Z.
+    Suspend,
+}
+
+#[derive(Clone, Copy)]
+pub struct TtySettings {
+    /// Input is edited a line at a time and only readable once the line is
+    /// finished. Otherwise every byte is readable as soon as it is typed.
+    pub canonical: bool,
+    pub echo: bool,
+    /// Ctrl-C and Ctrl-Z raise signals instead of being read.
+    pub signals: bool,
+}
+
+impl TtySettings {
+    pub const fn cooked() -> Self {
+        Self {
+            canonical: true,
+            echo: true,
+            signals: true,
+        }
+    }
+
+    pub const fn raw() -> Self {
+        Self {
+            canonical: false,
+            echo: false,
+            signals: false,
+        }
+    }
+}
+
+/// Sits between the keyboard and the program reading from the terminal.
+pub struct LineDiscipline {
+    settings: TtySettings,
+    /// The line being edited in canonical mode.
+    line: [u8; LINE_CAPACITY],
+    line_len: usize,
+    /// Input ready to be read. In canonical mode, lines end with a newline,
+    /// or with Ctrl-D if it finished them early.
+    input: [u8; INPUT_CAPACITY],
+    input_len: usize,
+    /// The number of finished lines in `input`.
+    lines: usize,
+    signal: Option<Signal>,
+}
+
+impl LineDiscipline {
+    pub const fn new() -> Self {
+        Self {
+            settings: TtySettings::cooked(),
+            line: [0; LINE_CAPACITY],
+            line_len: 0,
+            input: [0; INPUT_CAPACITY],
+            input_len: 0,
+            lines: 0,
+            signal: None,
+        }
+    }
+
+    pub fn settings(&self) -> TtySettings {
+        self.settings
+    }
+
+    /// Changes the settings. A line being edited is made readable when
+    /// leaving canonical mode.
+    pub fn set_settings(&mut self, settings: TtySettings) {
+        if self.settings.canonical && !settings.canonical {
+            self.commit_line(None);
+            self.lines = 0;
+        }
+        self.settings = settings;
+    }
+
+    /// Handles a byte typed on the keyboard, echoing it to `echo`.
+    pub unsafe fn receive(&mut self, c: u8, echo: &mut VGATextWriter) {
+        unsafe {
+            if self.settings.signals {
+                let signal = match c {
+                    INTERRUPT => Some(Signal::Interrupt),
+                    SUSPEND => Some(Signal::Suspend),
+                    _ => None,
+                };
+                if let Some(signal) = signal {
+                    self.flush();
+                    self.signal = Some(signal);
+                    if self.settings.echo {
+                        echo_char(c, echo);
+                        echo.nl();
+                    }
+                    return;
+                }
+            }
+
+            if !self.settings.canonical {
+                if self.push_input(c) && self.settings.echo {
+                    echo_char(c, echo);
+                }
+                return;
+            }
+
+            match c {
+                BACKSPACE | DELETE => self.erase(1, echo),
+                KILL_LINE => self.erase(self.line_len, echo),
+                WORD_ERASE => {
+                    let line = self.line.get(..self.line_len).unwrap_or(&[]);
+                    let spaces = line.iter().rev().take_while(|b| **b == b' ').count();
+                    let word = line
+                        .iter()
+                        .rev()
+                        .skip(spaces)
+                        .take_while(|b| **b != b' ')
+                        .count();
+                    self.erase(spaces + word, echo);
+                }
+                END_OF_FILE => self.commit_line(Some(END_OF_FILE)),
+                NEWLINE => {
+                    if self.settings.echo {
+                        echo.nl();
+                    }
+                    self.commit_line(Some(NEWLINE));
+                }
+                _ => {
+                    if let Some(slot) = self.line.get_mut(self.line_len) {
+                        *slot = c;
+                        self.line_len += 1;
+                        if self.settings.echo {
+                            echo_char(c, echo);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes the signal raised since the last call, if any.
+    pub fn take_signal(&mut self) -> Option<Signal> {
+        self.signal.take()
+    }
+
+    /// Reads typed input into `buf`. In canonical mode, reads at most one
+    /// line, including its newline, and only once it is finished.
+    ///
+    /// Returns `None` when there is nothing to read yet and `Some(0)` at the
+    /// end of file, when Ctrl-D is typed on an empty line.
+    pub fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if (self.settings.canonical && self.lines == 0) || self.input_len == 0 {
+            return None;
+        }
+        let available = self.input.get(..self.input_len).unwrap_or(&[]);
+        let (len, consumed) = if self.settings.canonical {
+            let end = available
+                .iter()
+                .position(|b| *b == NEWLINE || *b == END_OF_FILE)
+                .unwrap_or(available.len());
+            let newline = available.get(end) == Some(&NEWLINE);
+            let len = (end + newline as usize).min(buf.len());
+            // A line that doesn't fit is left for the next read.
+            if len == end + newline as usize {
+                self.lines -= 1;
+                (len, end + 1)
+            } else {
+                (len, len)
+            }
+        } else {
+            let len = available.len().min(buf.len());
+            (len, len)
+        };
+
+        for (dest, src) in buf.iter_mut().zip(available.iter()).take(len) {
+            *dest = *src;
+        }
+        if let Some(input) = self.input.get_mut(..self.input_len) {
+            input.rotate_left(consumed.min(input.len()));
+        }
+        self.input_len -= consumed;
+        Some(len)
+    }
+
+    /// Discards the line being edited and input not read yet.
+    pub fn flush(&mut self) {
+        self.line_len = 0;
+        self.input_len = 0;
+        self.lines = 0;
+    }
+
+    unsafe fn erase(&mut self, count: usize, echo: &mut VGATextWriter) {
+        for _ in 0..count.min(self.line_len) {
+            self.line_len -= 1;
+            if self.settings.echo {
+                let width = self.line.get(self.line_len).map_or(1, |c| echo_width(*c));
+                for _ in 0..width {
+                    unsafe { echo.bs() };
+                }
+            }
+        }
+    }
+
+    /// Moves the line being edited to the input, followed by `end` if given.
+    /// Input that doesn't fit is dropped.
+    fn commit_line(&mut self, end: Option<u8>) {
+        for i in 0..self.line_len {
+            if let Some(c) = self.line.get(i) {
+                self.push_input(*c);
+            }
+        }
+        self.line_len = 0;
+        if let Some(end) = end
+            && self.push_input(end)
+        {
+            self.lines += 1;
+        }
+    }
+
+    fn push_input(&mut self, c: u8) -> bool {
+        match self.input.get_mut(self.input_len) {
+            Some(slot) => {
+                *slot = c;
+                self.input_len += 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn is_control(c: u8) -> bool {
+    c < b' ' && c != b'\t' && c != NEWLINE
+}
+
+/// The number of columns a character takes when echoed.
+fn echo_width(c: u8) -> usize {
+    if is_control(c) { 2 } else { 1 }
+}
+
+unsafe fn echo_char(c: u8, echo: &mut VGATextWriter) {
+    unsafe {
+        if is_control(c) {
+            echo.put_char(b'^');
+            echo.put_char(c + CONTROL_ECHO_OFFSET);
+        } else if c == NEWLINE {
+            echo.nl();
+        } else {
+            echo.put_char(c);
+        }
+    }
+}
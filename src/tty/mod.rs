@@ -0,0 +1,11 @@
+// Terminals
+//
+// Keyboard input reaches programs through a line discipline, which echoes it,
+// edits lines and turns control characters into signals, the way a Unix TTY
+// does. Output goes to one of several virtual consoles, of which the one in
+// the foreground is shown on the screen. Programs can move the cursor and
+// color their output with ANSI escape sequences.
+
+pub mod ansi;
+pub mod console;
+pub mod line_discipline;
@@ -21,4 +21,13 @@ pub const fn address_hi_16_bytes(addr: usize) -> u16 {
 #[inline]
 pub const fn read_bit_mask(b: u8, mask: u8) -> bool {
     ((b & mask) > 0) as bool
-}
\ No newline at end of file
+}
+/// Parses an unsigned decimal number, stopping at the first NUL byte.
+pub fn parse_decimal(s: &[u8]) -> Option<u32> {
+    let mut digits = s.iter().take_while(|b| **b != b'\0').peekable();
+    digits.peek()?;
+    digits.try_fold(0u32, |n, b| {
+        let digit = (*b as char).to_digit(10)?;
+        n.checked_mul(10)?.checked_add(digit)
+    })
+}
@@ -1,14 +1,42 @@
-
 pub enum Port {
+    /// Takes an attribute controller register index and its value in turn.
+    AttributeController = 0x3C0,
+    AttributeRead = 0x3C1,
+    MiscOutput = 0x3C2,
     VGA1Out = 0x3C4,
     VGA1In = 0x3C5,
     VGA2Out = 0x3CE,
     VGA2In = 0x3CF,
     VGA3Out = 0x3D4,
     VGA3In = 0x3D5,
+    /// Reading it makes the attribute controller expect an index next.
+    InputStatus = 0x3DA,
 }
 
 pub enum VGA {
+    EndHorizontalBlanking = 0x03,
+    MaximumScanLine = 0x09,
+    CursorStart = 0x0A,
+    CursorEnd = 0x0B,
     CursorHiByte = 0x0E,
     CursorLoByte = 0x0F,
-}
\ No newline at end of file
+    VerticalRetraceEnd = 0x11,
+}
+
+pub enum SequencerRegister {
+    MapMask = 0x02,
+    MemoryMode = 0x04,
+}
+
+pub enum GraphicsRegister {
+    SetReset = 0x00,
+    EnableSetReset = 0x01,
+    ReadMapSelect = 0x04,
+    GraphicsMode = 0x05,
+    Miscellaneous = 0x06,
+    BitMask = 0x08,
+}
+
+pub enum AttributeRegister {
+    ModeControl = 0x10,
+}